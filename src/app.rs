@@ -2,17 +2,25 @@
 // States: Idle → Recording → Processing → Injecting → Idle
 // All inter-thread communication via tokio::sync::mpsc channels.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
 use crate::audio;
+use crate::code;
+use crate::commands;
 use crate::config::Config;
-use crate::injector;
+use crate::injector::{self, Injector};
 use crate::input::{self, InputRx, InputSignal, InputTx};
-use crate::network;
+use crate::live::LiveSession;
+use crate::network::{truncate_str, Timeouts};
+use crate::prompt::PromptContext;
+use crate::rewrite::{Mode, Rewriter};
+use crate::tracking::{Latency, TranscriptionRecord};
+use crate::transcriber::{self, TranscribeContext, Transcriber, Transcription};
 
 /// FSM states for the daemon.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,8 +28,9 @@ use crate::network;
 enum State {
     /// Waiting for CTRL+T. Minimal resource usage.
     Idle,
-    /// Microphone active, streaming audio to Gemini.
-    Recording,
+    /// Microphone active. Audio is buffered, or pushed live in realtime mode.
+    /// Carries the index of the hotkey that started it.
+    Recording(usize),
     /// Audio stopped, waiting for final transcription from API.
     Processing,
     /// Injecting transcribed text into the focused application.
@@ -32,7 +41,7 @@ impl std::fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             State::Idle => write!(f, "IDLE"),
-            State::Recording => write!(f, "RECORDING"),
+            State::Recording(_) => write!(f, "RECORDING"),
             State::Processing => write!(f, "PROCESSING"),
             State::Injecting => write!(f, "INJECTING"),
         }
@@ -44,21 +53,70 @@ impl std::fmt::Display for State {
 /// This function owns the FSM and coordinates all subsystems:
 /// - Input listener (keyboard hooks)
 /// - Audio capture
-/// - Network (WebSocket to Gemini)
+/// - Transcription backend (see `transcriber`)
 /// - Text injection
 pub async fn run(config: Config) -> Result<()> {
     let shutdown = Arc::new(AtomicBool::new(false));
 
-    // Parse the configured hotkey
+    // Instantiate the configured transcription backend once for the daemon lifetime
+    let transcriber =
+        transcriber::from_config(&config).context("Invalid transcription backend in config")?;
+    info!(backend = transcriber.name(), "Transcription backend ready");
+
+    // Retry dictations that failed earlier (possibly in a previous session)
+    if config.queue.enabled {
+        if let Err(e) = crate::spool::spawn_retry_loop(&config, transcriber.clone()) {
+            warn!(error = %format!("{e:#}"), "Offline retry queue disabled");
+        }
+    }
+
+    // Parse the configured hotkey, plus one per rewrite mode that has its own,
+    // the edit hotkey and the code dictation hotkey. `modes[i]` is the rewrite
+    // applied to dictations started by `hotkeys[i]`.
     let hotkey = input::parse_hotkey(&config.hotkey).context("Invalid hotkey in config")?;
     let hotkey_label = hotkey.label.clone();
+    let mut hotkeys = vec![hotkey];
+    let mut modes = vec![config
+        .active_mode()
+        .map(|name| Mode::resolve(&config, name))
+        .transpose()?];
+    for (name, combo) in crate::rewrite::hotkeys(&config) {
+        let hotkey = input::parse_hotkey(combo)
+            .with_context(|| format!("Invalid hotkey for rewrite mode '{name}'"))?;
+        info!(mode = name, hotkey = %hotkey.label, "Rewrite mode hotkey");
+        add_hotkey(&mut hotkeys, hotkey)?;
+        modes.push(Some(Mode::resolve(&config, name)?));
+    }
+    let edit_index = match config.edit_hotkey.as_str() {
+        "" => None,
+        combo => {
+            let hotkey = input::parse_hotkey(combo).context("Invalid edit_hotkey in config")?;
+            info!(hotkey = %hotkey.label, "Edit-selection hotkey");
+            modes.push(None);
+            Some(add_hotkey(&mut hotkeys, hotkey)?)
+        }
+    };
+    let code_index = match config.code.hotkey.as_str() {
+        "" => None,
+        combo => {
+            let hotkey = input::parse_hotkey(combo).context("Invalid [code] hotkey in config")?;
+            info!(hotkey = %hotkey.label, "Code dictation hotkey");
+            modes.push(None);
+            Some(add_hotkey(&mut hotkeys, hotkey)?)
+        }
+    };
+    let rewriter = if modes.iter().any(Option::is_some) || edit_index.is_some() {
+        Some(Rewriter::from_config(&config).context("Cannot enable rewrite modes")?)
+    } else {
+        None
+    };
 
     // Channel for keyboard input signals (Start/Stop)
     let (input_tx, mut input_rx): (InputTx, InputRx) = mpsc::channel(32);
 
     // Spawn the global keyboard listener on a dedicated OS thread
     let shutdown_clone = shutdown.clone();
-    let _input_handle = crate::input::spawn_listener(input_tx, shutdown_clone, hotkey)
+    let _input_handle = crate::input::spawn_listener(input_tx, shutdown_clone, hotkeys)
         .context("Failed to spawn keyboard listener")?;
 
     // Register SIGINT/SIGTERM handler for graceful shutdown
@@ -88,6 +146,7 @@ pub async fn run(config: Config) -> Result<()> {
     info!(hotkey = %hotkey_label, "Ready — hold hotkey to dictate.");
 
     let mut state = State::Idle;
+    let mut last: Option<LastDictation> = None;
 
     loop {
         if shutdown.load(Ordering::SeqCst) {
@@ -97,10 +156,18 @@ pub async fn run(config: Config) -> Result<()> {
 
         match state {
             State::Idle => {
-                state = state_idle(&mut input_rx, &hotkey_label, &config).await;
+                state = state_idle(&mut input_rx, &hotkey_label, &config, &transcriber).await;
             }
-            State::Recording => {
-                state = state_recording(&config, &mut input_rx, &hotkey_label).await;
+            State::Recording(index) => {
+                let pipeline = Pipeline {
+                    config: &config,
+                    transcriber: transcriber.as_ref(),
+                    injector: Arc::new(injector::Keystrokes),
+                    rewrite: rewriter.as_ref().zip(modes[index].as_ref()),
+                    edit: rewriter.as_ref().filter(|_| edit_index == Some(index)),
+                    code: code_index == Some(index),
+                };
+                state = state_recording(&pipeline, &mut input_rx, &hotkey_label, &mut last).await;
             }
             State::Processing => {
                 // Processing is handled inline within state_recording
@@ -116,18 +183,38 @@ pub async fn run(config: Config) -> Result<()> {
     }
 }
 
+/// Add a hotkey unless the same combo is already bound. Returns its index.
+fn add_hotkey(hotkeys: &mut Vec<input::Hotkey>, hotkey: input::Hotkey) -> Result<usize> {
+    if hotkeys
+        .iter()
+        .any(|h| h.trigger == hotkey.trigger && h.modifiers == hotkey.modifiers)
+    {
+        bail!("Hotkey {} is bound more than once", hotkey.label);
+    }
+    hotkeys.push(hotkey);
+    Ok(hotkeys.len() - 1)
+}
+
 /// Idle state: block until we receive a Start signal.
-async fn state_idle(input_rx: &mut InputRx, _hotkey_label: &str, config: &Config) -> State {
+async fn state_idle(
+    input_rx: &mut InputRx,
+    _hotkey_label: &str,
+    config: &Config,
+    transcriber: &Arc<dyn Transcriber>,
+) -> State {
     debug!("Idle, waiting for hotkey...");
 
     loop {
         match input_rx.recv().await {
-            Some(InputSignal::Start) => {
+            Some(InputSignal::Start(index)) => {
+                // Connect while the user speaks, not after release.
+                let warm = transcriber.clone();
+                tokio::spawn(async move { warm.warm_up().await });
                 info!("🎤 Recording...");
                 if config.sound_enabled {
                     crate::audio_feedback::play_start_beep();
                 }
-                return State::Recording;
+                return State::Recording(index);
             }
             Some(InputSignal::Stop) => {
                 // Spurious stop while idle, ignore
@@ -143,11 +230,59 @@ async fn state_idle(input_rx: &mut InputRx, _hotkey_label: &str, config: &Config
     }
 }
 
-/// Recording state: capture audio to buffer, then send to Gemini REST API.
+/// The most recent successful dictation, offered to the next prompt as
+/// `{previous_text}`.
+struct LastDictation {
+    app: Option<String>,
+    text: String,
+}
+
+/// What happens to a dictation after key release.
+struct Pipeline<'a> {
+    config: &'a Config,
+    transcriber: &'a dyn Transcriber,
+    injector: Arc<dyn Injector>,
+    /// Rewrite applied before typing, for hotkeys bound to a mode.
+    rewrite: Option<(&'a Rewriter, &'a Mode)>,
+    /// Set for the edit hotkey: the recording is an instruction for the
+    /// selected text rather than text to type.
+    edit: Option<&'a Rewriter>,
+    /// Code dictation: identifier casing and spoken symbols, see `code.rs`.
+    code: bool,
+}
+
+impl Pipeline<'_> {
+    /// Commands are left to a rewrite mode's instruction, and code dictation
+    /// has spoken symbols of its own.
+    fn spoken_commands(&self) -> bool {
+        self.config.spoken_commands && self.rewrite.is_none() && !self.code
+    }
+
+    /// What the offline queue redoes with a recovered transcription.
+    fn processing(&self) -> crate::spool::Processing {
+        crate::spool::Processing {
+            code: self.code,
+            mode: self.rewrite.map(|(_, mode)| mode.name.clone()),
+            spoken_commands: self.spoken_commands(),
+        }
+    }
+}
+
+/// Recording state: capture audio to buffer, then send it to the transcription backend.
 /// Handles the full lifecycle: Recording → Processing → Injecting → Idle.
-async fn state_recording(config: &Config, input_rx: &mut InputRx, _hotkey_label: &str) -> State {
+async fn state_recording(
+    pipeline: &Pipeline<'_>,
+    input_rx: &mut InputRx,
+    _hotkey_label: &str,
+    last: &mut Option<LastDictation>,
+) -> State {
+    let config = pipeline.config;
     debug!("Capturing audio to buffer");
 
+    // The focused app is looked up while audio is already being captured,
+    // so the helper process never clips the first words.
+    let app_lookup = tokio::task::spawn_blocking(crate::focus::focused_app);
+
     // Audio capture channel — uses std::sync::mpsc (NOT tokio) because
     // the cpal audio callback runs on a non-tokio OS thread.
     let (audio_tx, audio_rx) = audio::audio_channel();
@@ -163,26 +298,106 @@ async fn state_recording(config: &Config, input_rx: &mut InputRx, _hotkey_label:
         return State::Idle;
     }
 
+    // The Live session, if any, starts once the prompt is known; its audio
+    // sender reaches the collector through this channel.
+    let (live_sender_tx, live_sender_rx) =
+        std::sync::mpsc::channel::<mpsc::UnboundedSender<audio::AudioChunk>>();
+
     // Spawn a blocking task that drains the std::sync::mpsc receiver.
     // This runs on tokio's blocking thread pool so it won't block the async runtime.
     let collector_handle = tokio::task::spawn_blocking(move || {
         // Pre-allocate buffer for ~10 seconds of audio (160,000 samples)
         // to avoid reallocations during recording.
         let mut all_samples = Vec::<i16>::with_capacity(160_000);
+        let mut live_tx: Option<mpsc::UnboundedSender<audio::AudioChunk>> = None;
         // recv() blocks until a chunk arrives or all senders are dropped
         while let Ok(chunk) = audio_rx.recv() {
             all_samples.extend_from_slice(&chunk);
+            match &live_tx {
+                Some(tx) => {
+                    let _ = tx.send(chunk);
+                }
+                // Catch the session up on what was said before it started.
+                None => {
+                    if let Ok(tx) = live_sender_rx.try_recv() {
+                        let _ = tx.send(all_samples.clone());
+                        live_tx = Some(tx);
+                    }
+                }
+            }
+        }
+        if live_tx.is_none() {
+            if let Ok(tx) = live_sender_rx.recv() {
+                let _ = tx.send(all_samples.clone());
+            }
         }
         all_samples
     });
 
+    let app = app_lookup.await.unwrap_or_default();
+    // Plain dictation into an editor or terminal switches to code mode.
+    let code_pipeline;
+    let pipeline = if config.code.auto
+        && !pipeline.code
+        && pipeline.rewrite.is_none()
+        && pipeline.edit.is_none()
+        && code::is_code_app(config, app.as_deref())
+    {
+        debug!("Code dictation for the focused application");
+        code_pipeline = Pipeline {
+            injector: pipeline.injector.clone(),
+            code: true,
+            ..*pipeline
+        };
+        &code_pipeline
+    } else {
+        pipeline
+    };
+    // The previous dictation is context only while the user stays in the
+    // same app, and only if the user opted in.
+    let prompt_context = PromptContext {
+        previous_text: last
+            .as_ref()
+            .filter(|l| config.send_previous_text && l.app == app)
+            .map(|l| l.text.clone()),
+        app,
+    };
+    debug!(app = ?prompt_context.app, "Focused application");
+    // A translation mode may be spoken in a language of its own.
+    let mut ctx = match pipeline
+        .rewrite
+        .and_then(|(_, m)| m.source_language.clone())
+    {
+        Some(language) => {
+            let config = Config {
+                language,
+                ..config.clone()
+            };
+            TranscribeContext::from_config(&config, &prompt_context)
+        }
+        None => TranscribeContext::from_config(config, &prompt_context),
+    };
+    if pipeline.code {
+        ctx.prompt = code::transcription_prompt(config);
+    }
+
+    // Realtime mode: open the Live socket now so audio flows while the user
+    // is still speaking. Connecting happens in the background.
+    let live =
+        (config.live.enabled && pipeline.transcriber.name() == "gemini" && pipeline.edit.is_none())
+            .then(|| LiveSession::start(config, &ctx));
+    if let Some(live) = &live {
+        let _ = live_sender_tx.send(live.audio_sender());
+    }
+    drop(live_sender_tx);
+
     // Wait for Stop signal from keyboard (this blocks until hotkey release)
     loop {
         match input_rx.recv().await {
             Some(InputSignal::Stop) => {
                 break;
             }
-            Some(InputSignal::Start) => {
+            Some(InputSignal::Start(_)) => {
                 // Double press while recording, ignore
                 continue;
             }
@@ -190,11 +405,17 @@ async fn state_recording(config: &Config, input_rx: &mut InputRx, _hotkey_label:
                 error!("Input channel closed during recording");
                 recording_flag.store(false, Ordering::Relaxed);
                 collector_handle.abort();
+                if let Some(live) = live {
+                    live.cancel();
+                }
                 return State::Idle;
             }
         }
     }
 
+    // Latency phases are measured from key release.
+    let released = Instant::now();
+
     // Stop audio capture — this causes the audio thread to exit its loop,
     // drop the audio_tx sender, which closes the channel, which makes
     // the collector task finish and return the accumulated samples.
@@ -208,6 +429,7 @@ async fn state_recording(config: &Config, input_rx: &mut InputRx, _hotkey_label:
             return State::Idle;
         }
     };
+    let capture_stop_ms = millis_since(released);
 
     let duration = all_samples.len() as f64 / 16_000.0;
     info!(
@@ -220,81 +442,1016 @@ async fn state_recording(config: &Config, input_rx: &mut InputRx, _hotkey_label:
 
     if all_samples.is_empty() {
         warn!("No audio captured, skipping transcription");
+        if let Some(live) = live {
+            live.cancel();
+        }
         return State::Idle;
     }
 
-    let (transcription, usage) = match network::transcribe(config, &all_samples).await {
-        Ok(result) => result,
+    let dictation = match pipeline.edit {
+        Some(editor) => edit_selection(pipeline, editor, &all_samples, &ctx, released).await,
+        None => transcribe_and_inject(pipeline, &all_samples, &ctx, live, released).await,
+    };
+
+    let mut dictation = match dictation {
+        Ok(Some(dictation)) => dictation,
+        // edit_selection has said why.
+        Ok(None) if pipeline.edit.is_some() => return State::Idle,
+        Ok(None) => {
+            warn!("Empty transcription received, skipping injection");
+            return State::Idle;
+        }
         Err(e) => {
-            error!(%e, "Transcription failed");
-            warn!("Returning to idle due to transcription failure");
-            if config.sound_enabled {
-                crate::audio_feedback::play_error_beep();
-            }
+            // An edit needs its selection, which will be gone by the time a
+            // queued retry succeeds. On-device inference has nothing to wait
+            // out: a timed-out recording would only be run again.
+            let queued = pipeline.edit.is_none()
+                && pipeline.transcriber.name() != "local"
+                && config.queue.enabled
+                && spool_recording(&all_samples, &ctx, &pipeline.processing(), &e);
+            report_failure(config, &e, queued);
             return State::Idle;
         }
     };
+    if let Some(latency) = dictation.record.latency.as_mut() {
+        latency.capture_stop_ms = capture_stop_ms;
+    }
+    debug!(latency = ?dictation.record.latency, "Dictation timings");
 
-    if transcription.is_empty() {
-        warn!("Empty transcription received, skipping injection");
-        return State::Idle;
+    for record in std::iter::once(&dictation.record).chain(&dictation.rewrite) {
+        let log_line = crate::tracking::format_log_line(record, &config.currency);
+        info!("{}", log_line);
+
+        if let Err(e) = crate::tracking::append_record(record) {
+            warn!(%e, "Failed to save tracking record (non-fatal)");
+        }
     }
 
-    // Track cost and usage
-    let record = crate::tracking::build_record(&config.model, duration, &usage, &transcription);
+    if let Some(e) = &dictation.partial_error {
+        report_partial_failure(config, e);
+    }
+
+    if dictation.injected_ok && pipeline.edit.is_some() {
+        info!(text = %truncate_str(&dictation.text, 80), "✅ Selection replaced");
+    } else if dictation.injected_ok {
+        info!(text = %truncate_str(&dictation.text, 80), "✅ Injected");
+        *last = Some(LastDictation {
+            app: prompt_context.app,
+            text: dictation.text,
+        });
+    }
+
+    State::Idle
+}
+
+/// A transcribed and typed dictation.
+struct Dictation {
+    /// The typed text: the rewrite when a mode applied, else the transcription.
+    text: String,
+    /// Usage record for `tracking`; `capture_stop_ms` is left to the caller.
+    record: TranscriptionRecord,
+    /// Separate usage record for the rewrite call, if one was made.
+    rewrite: Option<TranscriptionRecord>,
+    /// A transcription or rewrite that failed after part of it was typed.
+    /// The dictation is kept as typed: queueing it would type it a second time.
+    partial_error: Option<anyhow::Error>,
+    /// False if any fragment failed to inject.
+    injected_ok: bool,
+}
+
+/// Transcribe captured audio and type the text as it arrives.
+/// Everything after key release except capture lives here, so tests can
+/// drive it against a mock server and injector. Returns `Ok(None)` when
+/// nothing was heard; on error nothing has been typed unless the failure
+/// came mid-stream.
+async fn transcribe_and_inject(
+    pipeline: &Pipeline<'_>,
+    samples: &[i16],
+    ctx: &TranscribeContext,
+    live: Option<LiveSession>,
+    released: Instant,
+) -> Result<Option<Dictation>> {
+    let transcriber = pipeline.transcriber;
+
+    // Text is typed as it arrives: a dedicated task drains the delta channel
+    // so keystroke injection never stalls the HTTP stream.
+    let (delta_tx, delta_rx) = mpsc::unbounded_channel::<String>();
+    let injector_handle = tokio::spawn(inject_deltas(delta_rx, pipeline.injector.clone()));
+
+    // With a rewrite mode only the rewrite is typed, and spoken commands and
+    // code dictation need the whole text; then the raw transcription goes to
+    // a channel nobody reads.
+    let spoken_commands = pipeline.spoken_commands();
+    let (raw_tx, _raw_rx) = mpsc::unbounded_channel::<String>();
+    let types_transcript = pipeline.rewrite.is_none() && !spoken_commands && !pipeline.code;
+    let transcript_tx = if types_transcript { &delta_tx } else { &raw_tx };
+
+    // Everything from here to the final text shares one deadline; when it
+    // expires, pending requests are cancelled.
+    let deadline = Timeouts::from_config(pipeline.config).overall(samples.len());
+    let started = Instant::now();
+    let outcome = transcriber::with_deadline(deadline, async {
+        match live {
+            Some(live) => match live.finish(transcript_tx).await {
+                Ok(result) => Ok(result),
+                // Part of the reply is typed; a batch retry would type it
+                // twice. Sent to `raw_tx`, nothing was typed yet.
+                Err(e) if types_transcript && transcriber::partial(&e).is_some() => Err(e),
+                Err(e) => {
+                    warn!(%e, "Gemini Live failed, falling back to batch upload");
+                    transcriber
+                        .transcribe_streaming(samples, ctx, transcript_tx)
+                        .await
+                }
+            },
+            None => {
+                transcriber
+                    .transcribe_streaming(samples, ctx, transcript_tx)
+                    .await
+            }
+        }
+    })
+    .await;
+
+    let rewritten = match (&outcome, pipeline.rewrite) {
+        (Ok(result), Some((rewriter, mode))) if !result.text.is_empty() => {
+            let remaining = deadline.saturating_sub(started.elapsed());
+            let rewrite = rewrite_and_type(
+                pipeline.config,
+                rewriter,
+                mode,
+                result,
+                samples.len(),
+                remaining,
+                &delta_tx,
+            );
+            Some(rewrite.await)
+        }
+        _ => None,
+    };
+    let responded = Instant::now();
+    // Closing the channel lets the injection task finish.
+    drop(delta_tx);
+    let (mut injected_ok, mut first_text, streamed) = injector_handle.await.unwrap_or_else(|e| {
+        error!(%e, "Injection task panicked");
+        (false, None, String::new())
+    });
+
+    let (result, transcription_error) = match outcome {
+        Ok(result) => (result, None),
+        // Streamed text is typed as it arrives, so part of a failed reply
+        // may already be in the app: keep it, with whatever usage is known.
+        Err(e) if first_text.is_some() => {
+            let sent = transcriber::partial(&e)
+                .cloned()
+                .unwrap_or_else(|| Transcription {
+                    text: streamed,
+                    model: pipeline
+                        .config
+                        .model
+                        .all()
+                        .first()
+                        .cloned()
+                        .unwrap_or_default(),
+                    ..Transcription::default()
+                });
+            (sent, Some(e))
+        }
+        Err(e) => return Err(e),
+    };
+    if result.text.is_empty() {
+        return Ok(None);
+    }
+
+    let mut typed = None;
+    if spoken_commands {
+        let language = result.detected_language.as_deref().unwrap_or(&ctx.language);
+        let steps =
+            commands::Vocabulary::for_language(pipeline.config, language)?.apply(&result.text);
+        typed = Some(commands::typed_text(&steps));
+        first_text = Some(Instant::now());
+        injected_ok = run_steps(pipeline.injector.clone(), steps).await;
+    } else if pipeline.code {
+        let text = code::format(&result.text);
+        typed = Some(text.clone());
+        first_text = Some(Instant::now());
+        injected_ok = run_steps(pipeline.injector.clone(), vec![commands::Step::Type(text)]).await;
+    }
 
-    let log_line = crate::tracking::format_log_line(&record, &config.currency);
-    info!("{}", log_line);
+    // Track cost, usage and latency
+    let duration = samples.len() as f64 / 16_000.0;
+    let mut record = crate::tracking::build_record(
+        &result.model,
+        duration,
+        &result.usage,
+        &result.text,
+        result.detected_language.as_deref(),
+    );
+    record.latency = Some(Latency {
+        first_text_ms: first_text.map_or(0, |t| millis_between(released, t)),
+        response_ms: millis_between(released, responded),
+        injection_ms: millis_since(responded),
+        ..result.latency
+    });
+
+    let rewrite = rewritten
+        .as_ref()
+        .and_then(|r| r.result.as_ref())
+        .zip(pipeline.rewrite)
+        .map(|(r, (_, mode))| {
+            let mut record =
+                crate::tracking::build_rewrite_record(&r.model, &mode.name, &r.usage, &r.text);
+            if mode.target_language.is_some() {
+                record.source_language = result.detected_language.clone().or_else(|| {
+                    (!matches!(ctx.language.as_str(), "auto" | "")).then(|| ctx.language.clone())
+                });
+                record.target_language = mode.target_language.clone();
+            }
+            record
+        });
+
+    let (text, partial_error) = match rewritten {
+        Some(r) => (r.typed, r.error),
+        None => (typed.unwrap_or(result.text), transcription_error),
+    };
+    Ok(Some(Dictation {
+        text,
+        record,
+        rewrite,
+        partial_error,
+        injected_ok,
+    }))
+}
+
+/// What `rewrite_and_type` did.
+struct Rewrite {
+    /// The rewrite call's reply, unless the transcription was typed instead.
+    result: Option<Transcription>,
+    /// Set when the rewrite failed after part of it was typed.
+    error: Option<anyhow::Error>,
+    /// Text that reached the injector.
+    typed: String,
+}
+
+/// Apply a rewrite mode to the transcription, typing the result as it
+/// arrives, within what is left of the dictation's `deadline`. If the
+/// rewrite fails before anything was typed, the user is told and the raw
+/// transcription is typed instead, so the dictation is never lost; a failure
+/// halfway through typing is returned in `Rewrite::error`.
+async fn rewrite_and_type(
+    config: &Config,
+    rewriter: &Rewriter,
+    mode: &Mode,
+    raw: &Transcription,
+    samples: usize,
+    deadline: std::time::Duration,
+    deltas: &mpsc::UnboundedSender<String>,
+) -> Rewrite {
+    info!(mode = %mode.name, "✍️ Rewriting...");
+    // Forward the rewrite to the injector, keeping what was typed.
+    let (rewrite_tx, mut rewrite_rx) = mpsc::unbounded_channel::<String>();
+    let forward_tx = deltas.clone();
+    let forwarder = tokio::spawn(async move {
+        let mut typed = String::new();
+        while let Some(delta) = rewrite_rx.recv().await {
+            typed.push_str(&delta);
+            let _ = forward_tx.send(delta);
+        }
+        typed
+    });
+
+    let outcome = transcriber::with_deadline(
+        deadline,
+        rewriter.rewrite(mode, &raw.text, samples, &rewrite_tx),
+    )
+    .await;
+    drop(rewrite_tx);
+    let typed = forwarder.await.unwrap_or_default();
+
+    let fall_back = |reason: &str| {
+        report_rewrite_skipped(config, mode, reason);
+        let _ = deltas.send(raw.text.clone());
+        Rewrite {
+            result: None,
+            error: None,
+            typed: raw.text.clone(),
+        }
+    };
+    match outcome {
+        Ok(rewritten) if !rewritten.text.is_empty() => Rewrite {
+            result: Some(rewritten),
+            error: None,
+            typed,
+        },
+        Ok(_) if typed.is_empty() => {
+            warn!(mode = %mode.name, "Rewrite came back empty, typing the transcription");
+            fall_back("The rewrite came back empty.")
+        }
+        Err(e) if typed.is_empty() => {
+            warn!(mode = %mode.name, error = %format!("{e:#}"), "Rewrite failed, typing the transcription");
+            fall_back(
+                crate::error::find(&e)
+                    .map(|e| e.user_message())
+                    .unwrap_or("The rewrite failed."),
+            )
+        }
+        Ok(_) => Rewrite {
+            result: None,
+            error: None,
+            typed,
+        },
+        Err(e) => Rewrite {
+            // Billed all the same; recorded with the usage reported so far.
+            result: transcriber::partial(&e).cloned(),
+            error: Some(e.context(format!("Rewrite mode '{}' failed", mode.name))),
+            typed,
+        },
+    }
+}
+
+/// Edit the selected text as the recording says: copy the selection,
+/// transcribe the spoken instruction, have Gemini apply it and paste the
+/// result over the selection. Returns `Ok(None)` when nothing was selected
+/// or said.
+async fn edit_selection(
+    pipeline: &Pipeline<'_>,
+    editor: &Rewriter,
+    samples: &[i16],
+    ctx: &TranscribeContext,
+    released: Instant,
+) -> Result<Option<Dictation>> {
+    // The selection is copied while the instruction is transcribed.
+    let injector = pipeline.injector.clone();
+    let selection = tokio::task::spawn_blocking(move || injector.selected_text());
+
+    // Only the instruction's first text is of interest, for its latency.
+    let (raw_tx, mut raw_rx) = mpsc::unbounded_channel::<String>();
+    let first_text = tokio::spawn(async move { raw_rx.recv().await.map(|_| Instant::now()) });
+    // The instruction and the edit share one deadline.
+    let deadline = Timeouts::from_config(pipeline.config).overall(samples.len());
+    let started = Instant::now();
+    let instruction = transcriber::with_deadline(
+        deadline,
+        pipeline
+            .transcriber
+            .transcribe_streaming(samples, ctx, &raw_tx),
+    )
+    .await;
+    let transcribed = Instant::now();
+    drop(raw_tx);
+    let first_text = first_text.await.ok().flatten();
+    let selection = selection
+        .await
+        .context("Selection task panicked")?
+        .context("Failed to copy the selection")?;
+    let instruction = instruction?;
+
+    if instruction.text.is_empty() {
+        warn!("No instruction heard, selection left as is");
+        return Ok(None);
+    }
+    if selection.trim().is_empty() {
+        warn!("Nothing selected, select the text to edit first");
+        if pipeline.config.notifications {
+            crate::notify::show(
+                "Nothing selected. Select the text to edit, then hold the edit hotkey.",
+            );
+        }
+        return Ok(None);
+    }
 
-    if let Err(e) = crate::tracking::append_record(&record) {
-        warn!(%e, "Failed to save tracking record (non-fatal)");
+    info!(instruction = %truncate_str(&instruction.text, 80), "✏️ Editing selection...");
+    let edited = transcriber::with_deadline(
+        deadline.saturating_sub(started.elapsed()),
+        editor.edit(&instruction.text, &selection, samples.len()),
+    )
+    .await?;
+    if edited.text.is_empty() {
+        bail!("Gemini returned no text for the edit");
     }
 
-    // Inject the transcribed text
+    let injector = pipeline.injector.clone();
+    let text = edited.text.clone();
+    let injected_ok =
+        match tokio::task::spawn_blocking(move || injector.replace_selection(&text)).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                error!(%e, "Failed to replace the selection");
+                false
+            }
+            Err(e) => {
+                error!(%e, "Injection task panicked");
+                false
+            }
+        };
+
+    let duration = samples.len() as f64 / 16_000.0;
+    let mut record = crate::tracking::build_record(
+        &instruction.model,
+        duration,
+        &instruction.usage,
+        &instruction.text,
+        instruction.detected_language.as_deref(),
+    );
+    // The instruction's own timings; injection covers the edit call and
+    // the paste.
+    record.latency = Some(Latency {
+        first_text_ms: millis_between(released, first_text.unwrap_or(transcribed)),
+        response_ms: millis_between(released, transcribed),
+        injection_ms: millis_since(transcribed),
+        ..instruction.latency
+    });
+    let edit =
+        crate::tracking::build_rewrite_record(&edited.model, "edit", &edited.usage, &edited.text);
+
+    Ok(Some(Dictation {
+        text: edited.text,
+        record,
+        rewrite: Some(edit),
+        partial_error: None,
+        injected_ok,
+    }))
+}
 
-    // Run injection on a blocking thread to avoid blocking the async runtime
-    let text = transcription.clone();
-    let inject_result = tokio::task::spawn_blocking(move || injector::inject(&text)).await;
+/// Save a failed recording to the offline retry queue. Returns true if saved.
+fn spool_recording(
+    samples: &[i16],
+    ctx: &TranscribeContext,
+    processing: &crate::spool::Processing,
+    err: &anyhow::Error,
+) -> bool {
+    if !crate::spool::should_spool(err) {
+        return false;
+    }
+    match crate::spool::Spool::open().and_then(|spool| spool.save(samples, ctx, processing, err)) {
+        Ok(entry) => {
+            info!(id = %entry.id, "📥 Recording saved to the retry queue");
+            true
+        }
+        Err(e) => {
+            warn!(error = %format!("{e:#}"), "Failed to queue recording for retry");
+            false
+        }
+    }
+}
 
-    match inject_result {
-        Ok(Ok(())) => {
-            info!(text = %truncate(&transcription, 80), "✅ Injected");
+/// Surface a failed transcription: log, error beep and (optionally) a desktop
+/// notification. Error text is never typed into the focused app.
+fn report_failure(config: &Config, err: &anyhow::Error, queued: bool) {
+    error!(error = %format!("{err:#}"), "Transcription failed");
+    warn!("Returning to idle due to transcription failure");
+    if config.sound_enabled {
+        crate::audio_feedback::play_error_beep();
+    }
+    if config.notifications {
+        let message = crate::error::find(err)
+            .map(|e| e.user_message())
+            .unwrap_or("Transcription failed. See the log for details.");
+        if queued {
+            crate::notify::show(&format!("{message} Saved to the retry queue."));
+        } else {
+            crate::notify::show(message);
         }
+    }
+}
+
+/// Surface a transcription or rewrite that failed after part of it was
+/// typed. Unlike a failed transcription it is not queued: the text typed so
+/// far stays.
+fn report_partial_failure(config: &Config, err: &anyhow::Error) {
+    error!(error = %format!("{err:#}"), "Failed partway, the typed text is incomplete");
+    if config.sound_enabled {
+        crate::audio_feedback::play_error_beep();
+    }
+    if config.notifications {
+        let message = crate::error::find(err)
+            .map(|e| e.user_message())
+            .unwrap_or("The reply failed partway.");
+        crate::notify::show(&format!("{message} A partial result was typed."));
+    }
+}
+
+/// Tell the user that `mode` was not applied and the transcription is typed
+/// as spoken instead — for a translation, in the wrong language.
+fn report_rewrite_skipped(config: &Config, mode: &Mode, reason: &str) {
+    if config.sound_enabled {
+        crate::audio_feedback::play_error_beep();
+    }
+    if config.notifications {
+        crate::notify::show(&format!(
+            "{reason} Mode '{}' was skipped; the text is typed as spoken.",
+            mode.name
+        ));
+    }
+}
+
+/// Type and press the steps of a dictation with spoken commands. Returns
+/// false if any step failed.
+async fn run_steps(injector: Arc<dyn Injector>, steps: Vec<commands::Step>) -> bool {
+    match tokio::task::spawn_blocking(move || commands::execute(&steps, injector.as_ref())).await {
+        Ok(Ok(())) => true,
         Ok(Err(e)) => {
             error!(%e, "Text injection failed");
+            false
         }
         Err(e) => {
             error!(%e, "Injection task panicked");
+            false
         }
     }
-
-    State::Idle
 }
 
-/// Truncate a string for log display.
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}…", &s[..max_len])
+/// Type each received text fragment in order. Returns false if any fragment
+/// failed to inject, and when the first fragment arrived.
+async fn inject_deltas(
+    mut rx: mpsc::UnboundedReceiver<String>,
+    injector: Arc<dyn Injector>,
+) -> (bool, Option<Instant>, String) {
+    let mut first = true;
+    let mut ok = true;
+    let mut first_text = None;
+    let mut typed = String::new();
+
+    while let Some(delta) = rx.recv().await {
+        first_text.get_or_insert_with(Instant::now);
+        typed.push_str(&delta);
+        // Run injection on a blocking thread to avoid blocking the async runtime
+        let is_first = first;
+        first = false;
+        let injector = injector.clone();
+        let inject_result = tokio::task::spawn_blocking(move || {
+            if is_first {
+                injector.inject(&delta)
+            } else {
+                injector.inject_delta(&delta)
+            }
+        })
+        .await;
+
+        match inject_result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                error!(%e, "Text injection failed");
+                ok = false;
+            }
+            Err(e) => {
+                error!(%e, "Injection task panicked");
+                ok = false;
+            }
+        }
     }
+
+    (ok, first_text, typed)
+}
+
+fn millis_since(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+fn millis_between(start: Instant, end: Instant) -> u64 {
+    end.saturating_duration_since(start).as_millis() as u64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_truncate() {
-        assert_eq!(truncate("hello", 10), "hello");
-        assert_eq!(truncate("hello world", 5), "hello…");
-    }
-
     #[test]
     fn test_state_display() {
         assert_eq!(format!("{}", State::Idle), "IDLE");
-        assert_eq!(format!("{}", State::Recording), "RECORDING");
+        assert_eq!(format!("{}", State::Recording(0)), "RECORDING");
         assert_eq!(format!("{}", State::Processing), "PROCESSING");
         assert_eq!(format!("{}", State::Injecting), "INJECTING");
     }
+
+    // ── End-to-end: samples → Gemini (mock) → tracking → injector ──
+
+    use crate::error::TranscribeError;
+    use crate::test_support::{GeminiReply, MockGemini, MockInjector, MOCK_USAGE};
+
+    /// One second of a quiet tone.
+    fn samples() -> Vec<i16> {
+        (0..16_000)
+            .map(|i| ((i as f64 * 0.05).sin() * 3000.0) as i16)
+            .collect()
+    }
+
+    async fn dictate(config: &Config, injector: &Arc<MockInjector>) -> Result<Option<Dictation>> {
+        dictate_with_mode(config, injector, None).await
+    }
+
+    async fn dictate_with_mode(
+        config: &Config,
+        injector: &Arc<MockInjector>,
+        mode: Option<&str>,
+    ) -> Result<Option<Dictation>> {
+        let transcriber = transcriber::from_config(config).unwrap();
+        let rewriter = Rewriter::from_config(config).unwrap();
+        let mode = mode.map(|name| Mode::resolve(config, name).unwrap());
+        let pipeline = Pipeline {
+            config,
+            transcriber: transcriber.as_ref(),
+            injector: injector.clone(),
+            rewrite: mode.as_ref().map(|mode| (&rewriter, mode)),
+            edit: None,
+            code: false,
+        };
+        let ctx = TranscribeContext::from_config(config, &PromptContext::default());
+        transcribe_and_inject(&pipeline, &samples(), &ctx, None, Instant::now()).await
+    }
+
+    /// Like `dictate`, with the audio also streamed to a Gemini Live session
+    /// at `live_url`.
+    async fn dictate_live(
+        config: &Config,
+        injector: &Arc<MockInjector>,
+        live_url: &str,
+    ) -> Result<Option<Dictation>> {
+        let mut config = config.clone();
+        config.live.enabled = true;
+        config.live.url = live_url.into();
+        let transcriber = transcriber::from_config(&config).unwrap();
+        let pipeline = Pipeline {
+            config: &config,
+            transcriber: transcriber.as_ref(),
+            injector: injector.clone(),
+            rewrite: None,
+            edit: None,
+            code: false,
+        };
+        let ctx = TranscribeContext::from_config(&config, &PromptContext::default());
+        let live = LiveSession::start(&config, &ctx);
+        live.audio_sender().send(samples()).unwrap();
+        transcribe_and_inject(&pipeline, &samples(), &ctx, Some(live), Instant::now()).await
+    }
+
+    async fn edit(config: &Config, injector: &Arc<MockInjector>) -> Result<Option<Dictation>> {
+        let transcriber = transcriber::from_config(config).unwrap();
+        let editor = Rewriter::from_config(config).unwrap();
+        let pipeline = Pipeline {
+            config,
+            transcriber: transcriber.as_ref(),
+            injector: injector.clone(),
+            rewrite: None,
+            edit: Some(&editor),
+            code: false,
+        };
+        let ctx = TranscribeContext::from_config(config, &PromptContext::default());
+        edit_selection(&pipeline, &editor, &samples(), &ctx, Instant::now()).await
+    }
+
+    async fn dictation_error(reply: GeminiReply, streaming: bool) -> (TranscribeError, String) {
+        let mock = MockGemini::start(reply).await;
+        let config = Config {
+            streaming,
+            ..mock.config()
+        };
+        let injector = Arc::new(MockInjector::default());
+        let err = dictate(&config, &injector).await.err().unwrap();
+        let err = crate::error::find(&err)
+            .unwrap_or_else(|| panic!("untyped error: {err:#}"))
+            .clone();
+        (err, injector.typed())
+    }
+
+    #[tokio::test]
+    async fn test_e2e_dictation() {
+        for streaming in [false, true] {
+            let mock = MockGemini::start(GeminiReply::Text("Ciao a tutti".into())).await;
+            let config = Config {
+                streaming,
+                ..mock.config()
+            };
+            let injector = Arc::new(MockInjector::default());
+
+            let dictation = dictate(&config, &injector).await.unwrap().unwrap();
+            assert_eq!(injector.typed(), "Ciao a tutti");
+            assert_eq!(dictation.text, "Ciao a tutti");
+            assert!(dictation.injected_ok);
+
+            let record = &dictation.record;
+            assert_eq!(record.model, "models/gemini-2.5-flash");
+            assert_eq!(record.input_tokens, MOCK_USAGE.0);
+            assert_eq!(record.output_tokens, MOCK_USAGE.1);
+            assert_eq!(record.thinking_tokens, MOCK_USAGE.2);
+            assert!(record.total_cost_usd > 0.0);
+            assert_eq!(record.word_count, 3);
+            assert!((record.audio_duration_secs - 1.0).abs() < 1e-9);
+            assert!(record.latency.is_some());
+
+            let requests = mock.requests();
+            assert_eq!(requests.len(), 1);
+            let endpoint = if streaming {
+                "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+            } else {
+                "/v1beta/models/gemini-2.5-flash:generateContent"
+            };
+            assert_eq!(requests[0].path, endpoint);
+            assert_eq!(requests[0].header("x-goog-api-key"), Some("AIzaSyMock"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_e2e_rewrite_mode() {
+        for streaming in [false, true] {
+            let mock = MockGemini::sequence(vec![
+                GeminiReply::Text("ciao ehm a tutti".into()),
+                GeminiReply::Text("Ciao a tutti.".into()),
+            ])
+            .await;
+            let config = Config {
+                streaming,
+                ..mock.config()
+            };
+            let injector = Arc::new(MockInjector::default());
+
+            let dictation = dictate_with_mode(&config, &injector, Some("grammar"))
+                .await
+                .unwrap()
+                .unwrap();
+            // Only the rewrite is typed, not the transcription as well.
+            assert_eq!(injector.typed(), "Ciao a tutti.");
+            assert_eq!(dictation.text, "Ciao a tutti.");
+            assert_eq!(dictation.record.rewrite_mode, None);
+            let rewrite = dictation.rewrite.unwrap();
+            assert_eq!(rewrite.rewrite_mode.as_deref(), Some("grammar"));
+            assert_eq!(rewrite.audio_duration_secs, 0.0);
+            assert!(rewrite.total_cost_usd > 0.0);
+
+            let requests = mock.requests();
+            assert_eq!(requests.len(), 2);
+            assert!(requests[0].body_text().contains("inlineData"));
+            assert!(!requests[1].body_text().contains("inlineData"));
+            assert!(requests[1].body_text().contains("ciao ehm a tutti"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_e2e_rewrite_failure_keeps_dictation() {
+        // Refused before anything was typed: the transcription is typed.
+        let mock = MockGemini::sequence(vec![
+            GeminiReply::Text("ciao a tutti".into()),
+            GeminiReply::Forbidden,
+        ])
+        .await;
+        let injector = Arc::new(MockInjector::default());
+        let dictation = dictate_with_mode(&mock.config(), &injector, Some("grammar"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(injector.typed(), "ciao a tutti");
+        assert!(dictation.rewrite.is_none() && dictation.partial_error.is_none());
+        assert_eq!(dictation.record.input_tokens, MOCK_USAGE.0);
+
+        // Cut off after part of it was typed: kept as typed, with the
+        // transcription's record, and the failure reported.
+        let mock = MockGemini::sequence(vec![
+            GeminiReply::Text("ciao a tutti".into()),
+            GeminiReply::MaxTokens,
+        ])
+        .await;
+        let config = Config {
+            streaming: true,
+            ..mock.config()
+        };
+        let injector = Arc::new(MockInjector::default());
+        let dictation = dictate_with_mode(&config, &injector, Some("grammar"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(injector.typed(), "Testo tronc");
+        assert_eq!(dictation.text, "Testo tronc");
+        assert_eq!(dictation.record.input_tokens, MOCK_USAGE.0);
+        assert!(dictation.partial_error.is_some());
+        // The cut-off rewrite is billed, so it is recorded too.
+        assert_eq!(dictation.rewrite.unwrap().output_tokens, MOCK_USAGE.1);
+    }
+
+    #[tokio::test]
+    async fn test_e2e_translation_mode() {
+        let mock = MockGemini::start(GeminiReply::Text("Hello everyone".into())).await;
+        let mut config = Config {
+            language: "it".into(),
+            ..mock.config()
+        };
+        config.modes.insert(
+            "support".into(),
+            crate::config::ModeConfig {
+                translate_to: Some("en".into()),
+                ..Default::default()
+            },
+        );
+        let injector = Arc::new(MockInjector::default());
+
+        let dictation = dictate_with_mode(&config, &injector, Some("support"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(injector.typed(), "Hello everyone");
+        let translation = dictation.rewrite.unwrap();
+        assert_eq!(translation.source_language.as_deref(), Some("it"));
+        assert_eq!(translation.target_language.as_deref(), Some("en"));
+        assert!(mock.requests()[1]
+            .body_text()
+            .contains("translate dictated text into English (en)"));
+    }
+
+    #[tokio::test]
+    async fn test_e2e_spoken_commands() {
+        for streaming in [false, true] {
+            let reply = "Hi comma all period new paragraph thanks select all bye";
+            let mock = MockGemini::start(GeminiReply::Text(reply.into())).await;
+            let config = Config {
+                streaming,
+                language: "en".into(),
+                spoken_commands: true,
+                ..mock.config()
+            };
+            let injector = Arc::new(MockInjector::default());
+
+            let dictation = dictate(&config, &injector).await.unwrap().unwrap();
+            assert_eq!(injector.typed(), "Hi, all.\n\nThanks{select_all}bye");
+            assert_eq!(dictation.text, "Hi, all.\n\nThanksbye");
+            assert!(dictation.injected_ok);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_e2e_code_dictation() {
+        for streaming in [false, true] {
+            let reply = "Let camel case user id equals open paren close paren semicolon.";
+            let mock = MockGemini::start(GeminiReply::Text(reply.into())).await;
+            // Spoken commands would read "semicolon" as punctuation with a
+            // space after; code mode takes over.
+            let config = Config {
+                streaming,
+                spoken_commands: true,
+                ..mock.config()
+            };
+            let transcriber = transcriber::from_config(&config).unwrap();
+            let injector = Arc::new(MockInjector::default());
+            let pipeline = Pipeline {
+                config: &config,
+                transcriber: transcriber.as_ref(),
+                injector: injector.clone(),
+                rewrite: None,
+                edit: None,
+                code: true,
+            };
+            let ctx = TranscribeContext {
+                prompt: code::transcription_prompt(&config),
+                ..TranscribeContext::from_config(&config, &PromptContext::default())
+            };
+
+            let dictation =
+                transcribe_and_inject(&pipeline, &samples(), &ctx, None, Instant::now())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(injector.typed(), "let userId = ();");
+            assert_eq!(dictation.text, "let userId = ();");
+            assert!(mock.requests()[0].body_text().contains("casing commands"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_e2e_edit_selection() {
+        let mock = MockGemini::sequence(vec![
+            GeminiReply::Text("Translate to Italian".into()),
+            GeminiReply::Text("Ciao a tutti".into()),
+        ])
+        .await;
+        let config = mock.config();
+        let injector = Arc::new(MockInjector::with_selection("Hello everyone"));
+
+        let edit_result = edit(&config, &injector).await.unwrap().unwrap();
+        // The edit result replaces the selection, not the instruction.
+        assert_eq!(injector.typed(), "[Ciao a tutti]");
+        assert_eq!(edit_result.text, "Ciao a tutti");
+        assert_eq!(edit_result.record.word_count, 3);
+        assert_eq!(
+            edit_result.rewrite.unwrap().rewrite_mode.as_deref(),
+            Some("edit")
+        );
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        let body = requests[1].body_text();
+        assert!(
+            body.contains("spoken instruction: Translate to Italian"),
+            "{body}"
+        );
+        assert!(body.contains("Hello everyone"));
+
+        // Nothing selected: no edit request, nothing pasted.
+        let mock = MockGemini::start(GeminiReply::Text("Shorten this".into())).await;
+        let injector = Arc::new(MockInjector::default());
+        assert!(edit(&mock.config(), &injector).await.unwrap().is_none());
+        assert_eq!(injector.typed(), "");
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_e2e_failures_type_nothing() {
+        for streaming in [false, true] {
+            let (err, typed) = dictation_error(GeminiReply::RateLimited, streaming).await;
+            assert!(matches!(err, TranscribeError::RateLimited { .. }));
+            assert_eq!(typed, "");
+
+            let (err, typed) = dictation_error(GeminiReply::Forbidden, streaming).await;
+            assert!(matches!(err, TranscribeError::Auth { .. }));
+            assert_eq!(typed, "");
+
+            let (err, typed) = dictation_error(GeminiReply::Safety, streaming).await;
+            assert!(matches!(err, TranscribeError::SafetyBlocked { .. }));
+            assert_eq!(typed, "");
+        }
+
+        let (err, typed) = dictation_error(GeminiReply::MaxTokens, false).await;
+        assert_eq!(err, TranscribeError::Truncated);
+        assert_eq!(typed, "");
+    }
+
+    #[tokio::test]
+    async fn test_e2e_partial_stream_is_kept() {
+        // Streamed text is typed as it arrives, so the cut-off part is
+        // already in the app: it is kept and recorded, with the failure.
+        let mock = MockGemini::start(GeminiReply::MaxTokens).await;
+        let config = Config {
+            streaming: true,
+            ..mock.config()
+        };
+        let injector = Arc::new(MockInjector::default());
+        let dictation = dictate(&config, &injector).await.unwrap().unwrap();
+        assert_eq!(injector.typed(), "Testo tronc");
+        assert_eq!(dictation.text, "Testo tronc");
+        assert_eq!(dictation.record.input_tokens, MOCK_USAGE.0);
+        assert_eq!(dictation.record.word_count, 2);
+        let err = dictation.partial_error.unwrap();
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Truncated));
+    }
+
+    #[tokio::test]
+    async fn test_e2e_live_cut_short() {
+        use crate::test_support::WsStub;
+        use serde_json::{json, Value};
+
+        // Live sends part of the reply, then closes without `turnComplete`.
+        let live = WsStub::start(|msg| {
+            if msg.get("setup").is_some() {
+                Some(vec![json!({"setupComplete": {}})])
+            } else if msg.pointer("/realtimeInput/activityEnd").is_some() {
+                Some(vec![
+                    json!({"serverContent": {"modelTurn": {"parts": [{"text": "Hi comma "}]}}}),
+                    Value::Null,
+                ])
+            } else {
+                Some(Vec::new())
+            }
+        })
+        .await;
+
+        // Typed as it arrived: kept, no batch upload to type it twice.
+        let mock = MockGemini::start(GeminiReply::Text("Hi comma all".into())).await;
+        let injector = Arc::new(MockInjector::default());
+        let dictation = dictate_live(&mock.config(), &injector, &live.url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(injector.typed(), "Hi comma");
+        assert!(dictation.partial_error.is_some());
+        assert!(mock.requests().is_empty());
+
+        // Held back for spoken commands, nothing was typed: the batch
+        // upload transcribes the dictation instead.
+        let mock = MockGemini::start(GeminiReply::Text("Hi comma all".into())).await;
+        let config = Config {
+            language: "en".into(),
+            spoken_commands: true,
+            ..mock.config()
+        };
+        let injector = Arc::new(MockInjector::default());
+        let dictation = dictate_live(&config, &injector, &live.url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(injector.typed(), "Hi, all");
+        assert!(dictation.partial_error.is_none());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_e2e_malformed_response() {
+        let mock = MockGemini::start(GeminiReply::Malformed).await;
+        let config = Config {
+            streaming: false,
+            ..mock.config()
+        };
+        let injector = Arc::new(MockInjector::default());
+        let err = dictate(&config, &injector).await.err().unwrap();
+        assert!(
+            format!("{err:#}").to_lowercase().contains("parse"),
+            "{err:#}"
+        );
+        assert_eq!(injector.typed(), "");
+    }
 }
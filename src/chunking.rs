@@ -0,0 +1,479 @@
+// chunking.rs — Split long recordings at pauses and transcribe the pieces in
+// parallel. Wraps any `Transcriber`: short recordings pass straight through,
+// long ones are cut in the quietest spot near each segment limit, sent with
+// bounded concurrency, and stitched back together in order.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+use crate::config::ChunkingConfig;
+use crate::tracking::{Latency, TokenUsage};
+use crate::transcriber::{with_partial, TranscribeContext, Transcriber, Transcription};
+
+const SAMPLE_RATE: usize = 16_000;
+/// Energy is measured over 20 ms frames when looking for a pause.
+const FRAME: usize = SAMPLE_RATE / 50;
+/// Longest word run compared when removing text repeated across a cut.
+const MAX_OVERLAP_WORDS: usize = 8;
+
+/// A slice of the recording, in sample offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Transcriber wrapper that splits long recordings (see module docs).
+pub struct ChunkedTranscriber {
+    inner: Arc<dyn Transcriber>,
+    max_segment: usize,
+    overlap: usize,
+    max_parallel: usize,
+}
+
+impl ChunkedTranscriber {
+    pub fn new(inner: Arc<dyn Transcriber>, config: &ChunkingConfig) -> Self {
+        Self {
+            inner,
+            max_segment: (config.max_segment_secs as usize).max(1) * SAMPLE_RATE,
+            overlap: config.overlap_ms as usize * SAMPLE_RATE / 1000,
+            max_parallel: config.max_parallel.max(1),
+        }
+    }
+
+    /// Transcribe every segment concurrently; `on_ready` receives each
+    /// segment's text in recording order as soon as all earlier ones are done.
+    async fn transcribe_segments(
+        &self,
+        samples: &[i16],
+        segments: &[Segment],
+        ctx: &TranscribeContext,
+        mut on_ready: impl FnMut(&str),
+    ) -> Result<Transcription> {
+        let permits = Arc::new(Semaphore::new(self.max_parallel));
+        let mut tasks = JoinSet::new();
+        for (index, seg) in segments.iter().enumerate() {
+            let inner = self.inner.clone();
+            let permits = permits.clone();
+            let audio = samples[seg.start..seg.end].to_vec();
+            let ctx = ctx.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (index, inner.transcribe(&audio, &ctx).await)
+            });
+        }
+
+        let mut results: Vec<Option<Transcription>> = vec![None; segments.len()];
+        let mut stitched = String::new();
+        let mut next = 0;
+        while let Some(joined) = tasks.join_next().await {
+            let (index, result) = joined.context("Segment transcription task panicked")?;
+            // One failed segment fails the dictation; dropping `tasks` aborts the rest.
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    let e = e.context(format!("Segment {} failed", index + 1));
+                    // Segments that finished meanwhile were billed; the rest
+                    // are cancelled before their reply, so only the finished
+                    // ones count.
+                    tasks.abort_all();
+                    while let Some(joined) = tasks.join_next().await {
+                        if let Ok((index, Ok(result))) = joined {
+                            results[index] = Some(result);
+                        }
+                    }
+                    // Earlier segments may have been typed already.
+                    let sent = merge(stitched, results.into_iter().flatten());
+                    return Err(with_partial(e, sent));
+                }
+            };
+            results[index] = Some(result);
+
+            while let Some(Some(done)) = results.get(next) {
+                let piece = stitch(&mut stitched, &done.text);
+                if !piece.is_empty() {
+                    on_ready(&piece);
+                }
+                next += 1;
+            }
+        }
+
+        Ok(merge(stitched, results.into_iter().flatten()))
+    }
+}
+
+/// Combine the usage of transcribed segments under the stitched `text`.
+/// Segments may have been answered by different fallback models; the total
+/// is reported under the one that billed the most tokens.
+fn merge(text: String, results: impl Iterator<Item = Transcription>) -> Transcription {
+    let mut usage = TokenUsage::default();
+    let mut tokens_by_model: Vec<(String, u64)> = Vec::new();
+    let mut detected_language = None;
+    // Segments run in parallel, so the slowest one is what the user waits for.
+    let mut latency = Latency::default();
+    for r in results {
+        usage.prompt_tokens += r.usage.prompt_tokens;
+        usage.candidates_tokens += r.usage.candidates_tokens;
+        usage.thoughts_tokens += r.usage.thoughts_tokens;
+        usage.total_tokens += r.usage.total_tokens;
+        match tokens_by_model.iter_mut().find(|(m, _)| *m == r.model) {
+            Some((_, tokens)) => *tokens += r.usage.total_tokens,
+            None => tokens_by_model.push((r.model, r.usage.total_tokens)),
+        }
+        detected_language = detected_language.or(r.detected_language);
+        latency.encode_ms = latency.encode_ms.max(r.latency.encode_ms);
+        latency.upload_ms = latency.upload_ms.max(r.latency.upload_ms);
+    }
+    // Earliest model wins a tie.
+    let model = tokens_by_model
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, tokens)| *tokens)
+        .map(|(model, _)| model)
+        .unwrap_or_default();
+
+    Transcription {
+        text,
+        usage,
+        model,
+        detected_language,
+        latency,
+    }
+}
+
+#[async_trait]
+impl Transcriber for ChunkedTranscriber {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn transcribe(&self, samples: &[i16], ctx: &TranscribeContext) -> Result<Transcription> {
+        let segments = split_at_silence(samples, self.max_segment, self.overlap);
+        if segments.len() <= 1 {
+            return self.inner.transcribe(samples, ctx).await;
+        }
+        debug!(
+            segments = segments.len(),
+            "Transcribing long recording in segments"
+        );
+        self.transcribe_segments(samples, &segments, ctx, |_| {})
+            .await
+    }
+
+    async fn warm_up(&self) {
+        self.inner.warm_up().await
+    }
+
+    async fn transcribe_streaming(
+        &self,
+        samples: &[i16],
+        ctx: &TranscribeContext,
+        deltas: &UnboundedSender<String>,
+    ) -> Result<Transcription> {
+        let segments = split_at_silence(samples, self.max_segment, self.overlap);
+        if segments.len() <= 1 {
+            return self.inner.transcribe_streaming(samples, ctx, deltas).await;
+        }
+        debug!(
+            segments = segments.len(),
+            "Transcribing long recording in segments"
+        );
+        self.transcribe_segments(samples, &segments, ctx, |piece| {
+            let _ = deltas.send(piece.to_string());
+        })
+        .await
+    }
+}
+
+/// Split `samples` into segments no longer than `max_len` (+ overlap).
+///
+/// Each cut is placed at the quietest 20 ms frame in the second half of the
+/// window, so words are rarely split. Segments extend `overlap` samples past
+/// each cut on both sides; `stitch` removes any words heard twice.
+pub fn split_at_silence(samples: &[i16], max_len: usize, overlap: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > max_len {
+        let window = &samples[start + max_len / 2..start + max_len];
+        let cut = start + max_len / 2 + quietest_frame(window);
+        segments.push(Segment {
+            start: start.saturating_sub(overlap),
+            end: (cut + overlap).min(samples.len()),
+        });
+        start = cut;
+    }
+    segments.push(Segment {
+        start: start.saturating_sub(overlap),
+        end: samples.len(),
+    });
+    segments
+}
+
+/// Offset of the centre of the lowest-energy frame (latest one on ties).
+fn quietest_frame(window: &[i16]) -> usize {
+    let mut best = (u64::MAX, window.len() / 2);
+    for (i, frame) in window.chunks(FRAME).enumerate() {
+        let energy: u64 = frame.iter().map(|&s| (s as i64 * s as i64) as u64).sum();
+        let energy = energy / frame.len() as u64;
+        if energy <= best.0 {
+            best = (energy, i * FRAME + frame.len() / 2);
+        }
+    }
+    best.1
+}
+
+/// Append `next` to `text`, dropping words at the start of `next` that repeat
+/// the end of `text` (heard twice because of the overlap). Returns exactly
+/// what was appended, including the joining space.
+fn stitch(text: &mut String, next: &str) -> String {
+    let next = next.trim();
+    if next.is_empty() {
+        return String::new();
+    }
+    if text.is_empty() {
+        text.push_str(next);
+        return next.to_string();
+    }
+
+    let tail: Vec<&str> = text.split_whitespace().collect();
+    let head: Vec<&str> = next.split_whitespace().collect();
+    let max = MAX_OVERLAP_WORDS.min(tail.len()).min(head.len());
+    let repeated = (1..=max)
+        .rev()
+        .find(|&k| {
+            tail[tail.len() - k..]
+                .iter()
+                .zip(&head[..k])
+                .all(|(a, b)| normalize(a) == normalize(b))
+        })
+        .unwrap_or(0);
+
+    let rest = head[repeated..].join(" ");
+    if rest.is_empty() {
+        return String::new();
+    }
+    let piece = format!(" {rest}");
+    text.push_str(&piece);
+    piece
+}
+
+/// Lowercase and strip punctuation for overlap comparison.
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Speech bursts separated by silence: `[(len, amplitude)]`.
+    fn signal(parts: &[(usize, i16)]) -> Vec<i16> {
+        parts
+            .iter()
+            .flat_map(|&(len, amp)| (0..len).map(move |i| if i % 2 == 0 { amp } else { -amp }))
+            .collect()
+    }
+
+    #[test]
+    fn test_short_recording_single_segment() {
+        let samples = vec![0i16; 1000];
+        assert_eq!(
+            split_at_silence(&samples, 2000, 100),
+            vec![Segment {
+                start: 0,
+                end: 1000
+            }]
+        );
+    }
+
+    #[test]
+    fn test_split_prefers_silence() {
+        // 1.5 s speech, 0.2 s pause, 1.5 s speech; limit 2 s.
+        let samples = signal(&[(24_000, 5000), (3_200, 0), (24_000, 5000)]);
+        let segments = split_at_silence(&samples, 32_000, 0);
+        assert_eq!(segments.len(), 2);
+        let cut = segments[0].end;
+        assert!((24_000..27_200).contains(&cut), "cut at {cut}");
+        assert_eq!(segments[1].start, cut);
+        assert_eq!(segments[1].end, samples.len());
+    }
+
+    #[test]
+    fn test_split_bounds_and_overlap() {
+        let samples = signal(&[(100_000, 3000)]);
+        let segments = split_at_silence(&samples, 30_000, 800);
+        assert!(segments.len() >= 4);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].end - pair[1].start, 1600);
+        }
+        for seg in &segments {
+            assert!(seg.end - seg.start <= 30_000 + 1600);
+        }
+    }
+
+    #[test]
+    fn test_stitch_removes_overlap() {
+        let mut text = String::new();
+        assert_eq!(
+            stitch(&mut text, " Ciao a tutti, oggi"),
+            "Ciao a tutti, oggi"
+        );
+        assert_eq!(
+            stitch(&mut text, "Oggi parliamo di Rust."),
+            " parliamo di Rust."
+        );
+        assert_eq!(stitch(&mut text, "Nuovo paragrafo"), " Nuovo paragrafo");
+        assert_eq!(stitch(&mut text, ""), "");
+        assert_eq!(text, "Ciao a tutti, oggi parliamo di Rust. Nuovo paragrafo");
+    }
+
+    /// Returns the segment's first sample value as a word; later segments
+    /// finish first to exercise ordering.
+    struct EchoTranscriber;
+
+    #[async_trait]
+    impl Transcriber for EchoTranscriber {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        async fn transcribe(
+            &self,
+            samples: &[i16],
+            _ctx: &TranscribeContext,
+        ) -> Result<Transcription> {
+            let tag = samples[samples.len() / 2].unsigned_abs();
+            tokio::time::sleep(Duration::from_millis(50 - tag as u64 * 10)).await;
+            Ok(Transcription {
+                text: format!("w{tag}"),
+                usage: TokenUsage {
+                    prompt_tokens: 10,
+                    candidates_tokens: 1,
+                    thoughts_tokens: 0,
+                    total_tokens: 11,
+                },
+                model: "echo-1".into(),
+                detected_language: None,
+                latency: Latency::default(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_segments_keep_order() {
+        let cfg = ChunkingConfig {
+            max_segment_secs: 1,
+            overlap_ms: 0,
+            ..ChunkingConfig::default()
+        };
+        let chunked = ChunkedTranscriber::new(Arc::new(EchoTranscriber), &cfg);
+        // Three ~1 s utterances with distinct amplitudes, separated by pauses.
+        let samples = signal(&[
+            (12_000, 1),
+            (2_000, 0),
+            (12_000, 2),
+            (2_000, 0),
+            (12_000, 3),
+        ]);
+        let ctx = TranscribeContext {
+            language: "auto".into(),
+            prompt: String::new(),
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = chunked
+            .transcribe_streaming(&samples, &ctx, &tx)
+            .await
+            .unwrap();
+        drop(tx);
+
+        assert_eq!(result.text, "w1 w2 w3");
+        assert_eq!(result.usage.prompt_tokens, 30);
+        assert_eq!(result.model, "echo-1");
+        assert_eq!(chunked.name(), "echo");
+
+        let mut typed = String::new();
+        while let Some(d) = rx.recv().await {
+            typed.push_str(&d);
+        }
+        assert_eq!(typed, result.text);
+    }
+
+    /// Like `EchoTranscriber`, but segment 2 fails after the others are done,
+    /// segment 3 is answered by a fallback model and segment 4 never answers.
+    struct FailingTranscriber;
+
+    #[async_trait]
+    impl Transcriber for FailingTranscriber {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn transcribe(
+            &self,
+            samples: &[i16],
+            _ctx: &TranscribeContext,
+        ) -> Result<Transcription> {
+            let tag = samples[samples.len() / 2].unsigned_abs();
+            match tag {
+                2 => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    anyhow::bail!("segment rejected");
+                }
+                4 => std::future::pending().await,
+                _ => {}
+            }
+            Ok(Transcription {
+                text: format!("w{tag}"),
+                usage: TokenUsage {
+                    prompt_tokens: 10 * tag as u64,
+                    candidates_tokens: 1,
+                    thoughts_tokens: 0,
+                    total_tokens: 10 * tag as u64 + 1,
+                },
+                model: format!("model-{tag}"),
+                detected_language: None,
+                latency: Latency::default(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_segment_records_finished_usage() {
+        let cfg = ChunkingConfig {
+            max_segment_secs: 1,
+            overlap_ms: 0,
+            ..ChunkingConfig::default()
+        };
+        let chunked = ChunkedTranscriber::new(Arc::new(FailingTranscriber), &cfg);
+        let samples = signal(&[
+            (12_000, 1),
+            (2_000, 0),
+            (12_000, 2),
+            (2_000, 0),
+            (12_000, 3),
+            (2_000, 0),
+            (12_000, 4),
+        ]);
+        let ctx = TranscribeContext {
+            language: "auto".into(),
+            prompt: String::new(),
+        };
+
+        let err = chunked.transcribe(&samples, &ctx).await.unwrap_err();
+        let sent = crate::transcriber::partial(&err).unwrap();
+        assert_eq!(sent.text, "w1");
+        // Segments 1 and 3 were billed; 2 failed and 4 was cancelled.
+        assert_eq!(sent.usage.prompt_tokens, 40);
+        assert_eq!(sent.model, "model-3");
+    }
+}
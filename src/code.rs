@@ -0,0 +1,412 @@
+// code.rs — Code dictation. Gemini is asked to write casing commands and
+// symbol names as spoken, and `format` turns them into code: "camel case
+// user id equals open paren close paren" → `userId = ()`. No sentence
+// capitalization or punctuation is added. Used from `[code] hotkey`, or from
+// the main hotkey while an editor or terminal has focus (`[code] auto`).
+
+use crate::config::Config;
+
+/// Instruction sent with the audio in code mode.
+const PROMPT: &str = "\
+Transcribe this dictation of source code or a shell command verbatim, in lowercase.
+Write casing commands (\"camel case\", \"snake case\", \"kebab case\", \"pascal case\", \
+\"screaming case\", \"end case\") and symbol names (\"open paren\", \"dot\", \"equals\", \"arrow\") as the \
+words spoken; do not turn them into symbols or join words yourself.
+Do not capitalize sentences or add punctuation. Return ONLY the transcription.
+Spell these identifiers exactly: {glossary}";
+
+/// Identifier styles: spoken command and how to join the words.
+const CASINGS: &[(&str, Casing)] = &[
+    ("camel case", Casing::Camel),
+    ("pascal case", Casing::Pascal),
+    ("snake case", Casing::Snake),
+    ("kebab case", Casing::Kebab),
+    ("screaming case", Casing::Screaming),
+    ("screaming snake case", Casing::Screaming),
+    ("constant case", Casing::Screaming),
+];
+
+/// Ends an identifier before words that are not part of it: "snake case
+/// item end case in items" → `item in items`. Otherwise it runs until the
+/// next symbol, quote or casing command.
+const END_CASE: &str = "end case";
+
+/// Spoken symbols: phrase, text, space before, space after. Opening parens
+/// and brackets still attach to a name or call before them, see `attaches`.
+const SYMBOLS: &[(&str, &str, bool, bool)] = &[
+    ("open paren", "(", true, false),
+    ("open parenthesis", "(", true, false),
+    ("close paren", ")", false, true),
+    ("close parenthesis", ")", false, true),
+    ("open bracket", "[", true, false),
+    ("close bracket", "]", false, true),
+    ("open brace", "{", true, true),
+    ("close brace", "}", true, true),
+    ("less than", "<", true, true),
+    ("greater than", ">", true, true),
+    ("arrow", "->", true, true),
+    ("fat arrow", "=>", true, true),
+    ("equals", "=", true, true),
+    ("double equals", "==", true, true),
+    ("triple equals", "===", true, true),
+    ("not equals", "!=", true, true),
+    ("plus equals", "+=", true, true),
+    ("minus equals", "-=", true, true),
+    ("plus", "+", true, true),
+    ("minus", "-", true, true),
+    ("star", "*", true, true),
+    ("times", "*", true, true),
+    ("slash", "/", true, true),
+    ("percent", "%", true, true),
+    ("double ampersand", "&&", true, true),
+    ("double pipe", "||", true, true),
+    ("pipe", "|", true, true),
+    ("ampersand", "&", true, false),
+    ("bang", "!", true, false),
+    ("hash", "#", true, false),
+    ("at sign", "@", true, false),
+    ("dollar", "$", true, false),
+    ("tilde", "~", true, false),
+    ("dot", ".", false, false),
+    ("double colon", "::", false, false),
+    ("colon", ":", false, true),
+    ("semicolon", ";", false, true),
+    ("comma", ",", false, true),
+    ("question mark", "?", false, true),
+    ("underscore", "_", false, false),
+    ("dash", "-", true, false),
+    ("backslash", "\\", false, false),
+    ("new line", "\n", false, false),
+];
+
+/// Quote characters; the first one spoken opens, the next closes.
+const QUOTES: &[(&str, char)] = &[
+    ("quote", '"'),
+    ("double quote", '"'),
+    ("single quote", '\''),
+    ("backtick", '`'),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Casing {
+    Camel,
+    Pascal,
+    Snake,
+    Kebab,
+    Screaming,
+}
+
+impl Casing {
+    fn apply(self, parts: &[String]) -> String {
+        let capitalized = |part: &String| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        };
+        match self {
+            Casing::Camel => parts
+                .iter()
+                .enumerate()
+                .map(|(i, p)| if i == 0 { p.clone() } else { capitalized(p) })
+                .collect(),
+            Casing::Pascal => parts.iter().map(capitalized).collect(),
+            Casing::Snake => parts.join("_"),
+            Casing::Kebab => parts.join("-"),
+            Casing::Screaming => parts.join("_").to_uppercase(),
+        }
+    }
+}
+
+/// Whether `app` is one of the editors and terminals in `[code] apps`.
+/// Names match whole words of the app name, optionally followed by a version
+/// number: `code` matches `Code` and `code-oss`, `iterm` matches `iTerm2`,
+/// but `warp` does not match `Warpinator`.
+pub fn is_code_app(config: &Config, app: Option<&str>) -> bool {
+    let Some(app) = app.map(words) else {
+        return false;
+    };
+    let word_matches = |app: &String, name: &String| {
+        app.strip_prefix(name.as_str())
+            .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+    };
+    config.code.apps.iter().any(|name| {
+        let name = words(name);
+        !name.is_empty()
+            && app.windows(name.len()).any(|window| {
+                window
+                    .iter()
+                    .zip(&name)
+                    .all(|(app, name)| word_matches(app, name))
+            })
+    })
+}
+
+/// Lowercase alphanumeric words of an app name: `jetbrains-idea` →
+/// `["jetbrains", "idea"]`.
+fn words(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The code-mode prompt, with the glossary as identifiers to spell exactly.
+pub fn transcription_prompt(config: &Config) -> String {
+    crate::prompt::render(PROMPT, &[("glossary", &config.glossary_terms().join(", "))])
+}
+
+/// A piece of output and whether it wants a space before and after.
+struct Token {
+    text: String,
+    space_before: bool,
+    space_after: bool,
+}
+
+impl Token {
+    fn word(text: String) -> Self {
+        Self {
+            text,
+            space_before: true,
+            space_after: true,
+        }
+    }
+}
+
+/// Turn a code-mode transcription into code.
+pub fn format(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let normalized: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+    let matches = |i: usize, phrase: &str| -> Option<usize> {
+        let phrase: Vec<&str> = phrase.split(' ').collect();
+        let found = normalized[i..].get(..phrase.len())?;
+        found.iter().eq(phrase.iter()).then_some(phrase.len())
+    };
+    // Longest phrase first, so "double equals" beats "equals".
+    let longest = |i: usize, phrases: &mut dyn Iterator<Item = &'static str>| {
+        phrases
+            .filter_map(|p| Some((matches(i, p)?, p)))
+            .max_by_key(|(len, _)| *len)
+    };
+
+    let mut tokens = Vec::new();
+    let mut open_quotes: Vec<char> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let casing = longest(i, &mut CASINGS.iter().map(|(p, _)| *p));
+        let symbol = longest(i, &mut SYMBOLS.iter().map(|(p, ..)| *p));
+        let quote = longest(i, &mut QUOTES.iter().map(|(p, _)| *p));
+
+        if let Some((len, phrase)) = casing {
+            let casing = CASINGS.iter().find(|(p, _)| *p == phrase).unwrap().1;
+            i += len;
+            // The identifier runs until `END_CASE`, or the next symbol or
+            // casing command.
+            let mut parts = Vec::new();
+            while i < words.len() {
+                if let Some(len) = matches(i, END_CASE) {
+                    i += len;
+                    break;
+                }
+                let command = CASINGS.iter().map(|(p, _)| *p);
+                let symbols = SYMBOLS.iter().map(|(p, ..)| *p);
+                let quotes = QUOTES.iter().map(|(p, _)| *p);
+                if command
+                    .chain(symbols)
+                    .chain(quotes)
+                    .any(|p| matches(i, p).is_some())
+                {
+                    break;
+                }
+                parts.extend(split_identifier(words[i]));
+                i += 1;
+            }
+            if !parts.is_empty() {
+                tokens.push(Token::word(casing.apply(&parts)));
+            }
+        } else if let Some((len, phrase)) = symbol.filter(|s| quote.is_none_or(|q| s.0 >= q.0)) {
+            let (_, text, space_before, space_after) =
+                *SYMBOLS.iter().find(|(p, ..)| *p == phrase).unwrap();
+            tokens.push(Token {
+                text: text.to_string(),
+                space_before,
+                space_after,
+            });
+            i += len;
+        } else if let Some((len, phrase)) = quote {
+            let quote = QUOTES.iter().find(|(p, _)| *p == phrase).unwrap().1;
+            let closing = open_quotes.last() == Some(&quote);
+            if closing {
+                open_quotes.pop();
+            } else {
+                open_quotes.push(quote);
+            }
+            tokens.push(Token {
+                text: quote.to_string(),
+                space_before: !closing,
+                space_after: closing,
+            });
+            i += len;
+        } else {
+            let last = i + 1 == words.len();
+            tokens.push(Token::word(clean_word(words[i], tokens.is_empty(), last)));
+            i += 1;
+        }
+    }
+
+    let mut out = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0
+            && tokens[i - 1].space_after
+            && token.space_before
+            && !attaches(&tokens[i - 1], token)
+        {
+            out.push(' ');
+        }
+        out.push_str(&token.text);
+    }
+    out
+}
+
+/// A call or index follows its name without a space: `main()`, `items[0]`,
+/// but `x = (a + b)`.
+fn attaches(prev: &Token, token: &Token) -> bool {
+    matches!(token.text.as_str(), "(" | "[")
+        && prev
+            .text
+            .ends_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | ')' | ']'))
+}
+
+/// Drop the full stop Gemini may still add after the last word, and the
+/// capital of a sentence-cased first word ("If" → "if"). Other punctuation
+/// is kept: `self;` or `std::` may be meant.
+fn clean_word(word: &str, first: bool, last: bool) -> String {
+    let word = if last {
+        word.strip_suffix('.').unwrap_or(word)
+    } else {
+        word
+    };
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) if first && c.is_uppercase() && chars.clone().all(|c| c.is_lowercase()) => {
+            c.to_lowercase().chain(chars).collect()
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// Lowercase words of an identifier, also splitting words Gemini has
+/// already joined ("userId", "user_id").
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in word.chars() {
+        if !c.is_alphanumeric() {
+            parts.push(std::mem::take(&mut current));
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            parts.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+    parts.push(current);
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_casing() {
+        assert_eq!(format("camel case user id"), "userId");
+        assert_eq!(format("pascal case http client"), "HttpClient");
+        assert_eq!(format("snake case max retries"), "max_retries");
+        assert_eq!(format("kebab case dry run"), "dry-run");
+        assert_eq!(format("screaming case api key"), "API_KEY");
+        // Words Gemini joined anyway are split and recased.
+        assert_eq!(format("snake case userId"), "user_id");
+    }
+
+    #[test]
+    fn test_words_after_identifier() {
+        assert_eq!(
+            format("for snake case item end case in items colon"),
+            "for item in items:"
+        );
+        assert_eq!(
+            format("camel case user id end case equals none"),
+            "userId = none"
+        );
+        // Without the end word the identifier runs to the next symbol.
+        assert_eq!(
+            format("for snake case item in items colon"),
+            "for item_in_items:"
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(
+            format("let snake case max retries equals three semicolon"),
+            "let max_retries = three;"
+        );
+        assert_eq!(
+            format("If camel case user id double equals none colon"),
+            "if userId == none:"
+        );
+        assert_eq!(
+            format("fn main open paren close paren arrow i32 open brace"),
+            "fn main() -> i32 {"
+        );
+        assert_eq!(
+            format(
+                "self dot camel case load config open paren quote app dot toml quote close paren"
+            ),
+            "self.loadConfig(\"app.toml\")"
+        );
+        assert_eq!(format("git commit dash dash amend."), "git commit --amend");
+        assert_eq!(format("return self; done"), "return self; done");
+        assert_eq!(
+            format("x equals open paren a plus b close paren times items open bracket zero close bracket"),
+            "x = (a + b) * items[zero]"
+        );
+    }
+
+    #[test]
+    fn test_is_code_app() {
+        let config = Config::default();
+        assert!(is_code_app(&config, Some("Code")));
+        assert!(is_code_app(&config, Some("gnome-terminal-server")));
+        assert!(is_code_app(&config, Some("jetbrains-idea")));
+        assert!(is_code_app(&config, Some("iTerm2")));
+        assert!(is_code_app(&config, Some("dev.warp.Warp")));
+        assert!(!is_code_app(&config, Some("Slack")));
+        // Names match whole words only, not inside unrelated app names.
+        assert!(!is_code_app(&config, Some("Warpinator")));
+        assert!(!is_code_app(&config, Some("Zedge")));
+        assert!(!is_code_app(&config, Some("Idealist")));
+        assert!(!is_code_app(&config, Some("Barcode Scanner")));
+        let config = Config {
+            code: crate::config::CodeConfig {
+                apps: vec!["sublime text".into()],
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        assert!(is_code_app(&config, Some("sublime_text")));
+        assert!(!is_code_app(&config, Some("sublime_merge")));
+        assert!(!is_code_app(&config, None));
+    }
+}
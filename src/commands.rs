@@ -0,0 +1,530 @@
+// commands.rs — Spoken commands: "new line", "period", "open quote",
+// "delete last word", "select all", ... are turned into text edits and key
+// presses instead of being typed literally. Each language has a built-in
+// vocabulary; `[commands.LANG]` in config.toml adds phrases or overrides them
+// (an empty value removes one). Enabled with `spoken_commands = true`.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::injector::{Injector, Shortcut};
+
+const USAGE: &str = "Usage: g-type commands [LANG]";
+
+/// Built-in vocabulary per language: spoken phrase and action (see `Action::parse`).
+const BUILTINS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "en",
+        &[
+            ("new line", "\n"),
+            ("new paragraph", "\n\n"),
+            ("period", "."),
+            ("full stop", "."),
+            ("comma", ","),
+            ("question mark", "?"),
+            ("exclamation mark", "!"),
+            ("exclamation point", "!"),
+            ("colon", ":"),
+            ("semicolon", ";"),
+            ("open quote", "\""),
+            ("close quote", "\""),
+            ("open parenthesis", "("),
+            ("close parenthesis", ")"),
+            ("delete last word", "{delete_word}"),
+            ("select all", "{select_all}"),
+            ("press enter", "{enter}"),
+        ],
+    ),
+    (
+        "it",
+        &[
+            ("a capo", "\n"),
+            ("nuovo paragrafo", "\n\n"),
+            ("punto", "."),
+            ("virgola", ","),
+            ("punto interrogativo", "?"),
+            ("punto esclamativo", "!"),
+            ("due punti", ":"),
+            ("punto e virgola", ";"),
+            ("apri virgolette", "\""),
+            ("chiudi virgolette", "\""),
+            ("apri parentesi", "("),
+            ("chiudi parentesi", ")"),
+            ("cancella ultima parola", "{delete_word}"),
+            ("seleziona tutto", "{select_all}"),
+            ("premi invio", "{enter}"),
+        ],
+    ),
+    (
+        "es",
+        &[
+            ("nueva línea", "\n"),
+            ("nuevo párrafo", "\n\n"),
+            ("punto", "."),
+            ("coma", ","),
+            ("signo de interrogación", "?"),
+            ("signo de exclamación", "!"),
+            ("dos puntos", ":"),
+            ("punto y coma", ";"),
+            ("abrir comillas", "\""),
+            ("cerrar comillas", "\""),
+            ("abrir paréntesis", "("),
+            ("cerrar paréntesis", ")"),
+            ("borrar última palabra", "{delete_word}"),
+            ("seleccionar todo", "{select_all}"),
+            ("pulsar intro", "{enter}"),
+        ],
+    ),
+    (
+        "fr",
+        &[
+            ("à la ligne", "\n"),
+            ("nouvelle ligne", "\n"),
+            ("nouveau paragraphe", "\n\n"),
+            ("point", "."),
+            ("virgule", ","),
+            ("point d'interrogation", "?"),
+            ("point d'exclamation", "!"),
+            ("deux points", ":"),
+            ("point-virgule", ";"),
+            ("ouvrez les guillemets", "\""),
+            ("fermez les guillemets", "\""),
+            ("ouvrir la parenthèse", "("),
+            ("fermer la parenthèse", ")"),
+            ("supprimer le dernier mot", "{delete_word}"),
+            ("tout sélectionner", "{select_all}"),
+            ("appuyer sur entrée", "{enter}"),
+        ],
+    ),
+    (
+        "de",
+        &[
+            ("neue Zeile", "\n"),
+            ("neuer Absatz", "\n\n"),
+            ("Punkt", "."),
+            ("Komma", ","),
+            ("Fragezeichen", "?"),
+            ("Ausrufezeichen", "!"),
+            ("Doppelpunkt", ":"),
+            ("Semikolon", ";"),
+            ("Anführungszeichen auf", "\""),
+            ("Anführungszeichen zu", "\""),
+            ("Klammer auf", "("),
+            ("Klammer zu", ")"),
+            ("letztes Wort löschen", "{delete_word}"),
+            ("alles auswählen", "{select_all}"),
+            ("Eingabetaste drücken", "{enter}"),
+        ],
+    ),
+];
+
+/// What a spoken command does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Insert text, spaced like punctuation where it is punctuation.
+    Insert(String),
+    /// Remove the last word typed by this dictation, or press the platform's
+    /// delete-word shortcut when there is none.
+    DeleteWord,
+    /// Press a key combination.
+    Press(Shortcut),
+}
+
+impl Action {
+    /// `{delete_word}`, `{select_all}`, `{undo}` or a shortcut such as
+    /// `{ctrl+z}`; anything else is text to insert.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+            Some("delete_word") => Ok(Self::DeleteWord),
+            Some(spec) => Ok(Self::Press(Shortcut::parse(spec)?)),
+            None => Ok(Self::Insert(value.to_string())),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Insert(text) if text == "\n" => "line break".into(),
+            Self::Insert(text) if text == "\n\n" => "paragraph break".into(),
+            Self::Insert(text) => format!("{text:?}"),
+            Self::DeleteWord => "delete the last word".into(),
+            Self::Press(shortcut) => format!("press {}", shortcut.label),
+        }
+    }
+}
+
+/// What to do with the focused app, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Type(String),
+    Press(Shortcut),
+}
+
+/// The command phrases for one dictation language.
+pub struct Vocabulary {
+    /// Normalized phrase words and action, longest phrase first.
+    phrases: Vec<(Vec<String>, Action)>,
+}
+
+/// Vocabulary used when the language is "auto" and the backend did not
+/// report which one was spoken.
+const FALLBACK_LANGUAGE: &str = "en";
+
+/// The vocabulary that applies to `language`. Never a mix of several:
+/// French "point" or Spanish "coma" would eat ordinary English words.
+fn vocabulary_language(language: &str) -> &str {
+    match language {
+        "auto" | "" => FALLBACK_LANGUAGE,
+        language => language,
+    }
+}
+
+impl Vocabulary {
+    /// Built-in and configured commands for `language` ("auto" means the
+    /// English ones; pass the detected language when there is one).
+    pub fn for_language(config: &Config, language: &str) -> Result<Self> {
+        let language = vocabulary_language(language);
+        let wanted = |lang: &str| lang == language;
+        let mut entries: BTreeMap<Vec<String>, Action> = BTreeMap::new();
+
+        for (lang, phrases) in BUILTINS.iter().filter(|(lang, _)| wanted(lang)) {
+            for (phrase, action) in *phrases {
+                let action = Action::parse(action)
+                    .with_context(|| format!("Built-in command '{phrase}' ({lang})"))?;
+                entries.insert(normalize_phrase(phrase), action);
+            }
+        }
+        for (lang, phrases) in config.commands.iter().filter(|(lang, _)| wanted(lang)) {
+            for (phrase, value) in phrases {
+                let key = normalize_phrase(phrase);
+                if key.is_empty() || key.iter().any(String::is_empty) {
+                    bail!("[commands.{lang}] \"{phrase}\": a phrase must be spoken words");
+                }
+                if value.is_empty() {
+                    entries.remove(&key);
+                    continue;
+                }
+                let action = Action::parse(value)
+                    .with_context(|| format!("[commands.{lang}] \"{phrase}\""))?;
+                entries.insert(key, action);
+            }
+        }
+
+        let mut phrases: Vec<_> = entries.into_iter().collect();
+        phrases.sort_by_key(|(words, _)| std::cmp::Reverse(words.len()));
+        Ok(Self { phrases })
+    }
+
+    /// Turn a transcription into text to type and keys to press.
+    pub fn apply(&self, text: &str) -> Vec<Step> {
+        let words: Vec<(&str, &str)> = split_words(text);
+        let normalized: Vec<String> = words.iter().map(|(_, w)| normalize_word(w)).collect();
+        let mut out = Output::default();
+        let mut i = 0;
+        while i < words.len() {
+            let command = self.phrases.iter().find(|(phrase, _)| {
+                normalized[i..]
+                    .get(..phrase.len())
+                    .is_some_and(|w| w == phrase.as_slice())
+            });
+            match command {
+                Some((phrase, action)) => {
+                    out.action(action);
+                    i += phrase.len();
+                }
+                None => {
+                    let (space, word) = words[i];
+                    out.word(space, word);
+                    i += 1;
+                }
+            }
+        }
+        out.finish()
+    }
+}
+
+/// Text followed by its own spacing rules, built up one word or command at
+/// a time.
+#[derive(Default)]
+struct Output {
+    steps: Vec<Step>,
+    /// Text not yet flushed into `steps`; "delete last word" edits it.
+    text: String,
+    /// Separator before the next word, when a command decided it.
+    next_space: Option<&'static str>,
+    capitalize: bool,
+    quote_open: bool,
+}
+
+impl Output {
+    fn word(&mut self, space: &str, word: &str) {
+        let space = self.next_space.take().unwrap_or(space);
+        if !self.text.is_empty() {
+            self.text.push_str(space);
+        }
+        if std::mem::take(&mut self.capitalize) {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                self.text.extend(first.to_uppercase());
+                self.text.push_str(chars.as_str());
+                return;
+            }
+        }
+        self.text.push_str(word);
+    }
+
+    fn action(&mut self, action: &Action) {
+        match action {
+            Action::Insert(insert) => self.insert(insert),
+            Action::DeleteWord if self.text.trim().is_empty() => {
+                self.flush();
+                self.steps
+                    .push(Step::Press(Shortcut::parse("delete_word").expect("named")));
+                self.next_space = Some("");
+            }
+            Action::DeleteWord => {
+                let kept = self
+                    .text
+                    .trim_end()
+                    .trim_end_matches(|c: char| !c.is_whitespace());
+                let kept = kept.trim_end_matches([' ', '\t']).len();
+                self.text.truncate(kept);
+                self.next_space = None;
+            }
+            Action::Press(shortcut) => {
+                self.flush();
+                self.steps.push(Step::Press(shortcut.clone()));
+                self.next_space = Some("");
+            }
+        }
+    }
+
+    fn insert(&mut self, insert: &str) {
+        let line_break = insert.chars().all(|c| c == '\n');
+        let attach_left = line_break
+            || (insert == "\"" && self.quote_open)
+            || insert.chars().all(|c| ".,;:!?)]}…%".contains(c));
+        let attach_right = line_break
+            || (insert == "\"" && !self.quote_open)
+            || insert.chars().all(|c| "([{¿¡".contains(c));
+        if insert == "\"" {
+            self.quote_open = !self.quote_open;
+        }
+
+        if attach_left {
+            let kept = self.text.trim_end_matches([' ', '\t']).len();
+            self.text.truncate(kept);
+        } else if !self.text.is_empty() {
+            self.text.push_str(self.next_space.take().unwrap_or(" "));
+        }
+        self.text.push_str(insert);
+        self.next_space = Some(if attach_right { "" } else { " " });
+
+        let sentence_end = insert.trim_end_matches('"').chars().last();
+        if sentence_end.is_some_and(|c| ".?!".contains(c)) {
+            self.capitalize = true;
+        } else if !line_break && !attach_right {
+            self.capitalize = false;
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            self.steps.push(Step::Type(std::mem::take(&mut self.text)));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Step> {
+        self.flush();
+        self.steps
+    }
+}
+
+/// Words with the whitespace before each.
+fn split_words(text: &str) -> Vec<(&str, &str)> {
+    let mut words = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let start = rest.find(|c: char| !c.is_whitespace()).unwrap_or(0);
+        let end = rest[start..]
+            .find(char::is_whitespace)
+            .map_or(rest.len(), |e| start + e);
+        words.push((&rest[..start], &rest[start..end]));
+        rest = &rest[end..];
+    }
+    words
+}
+
+/// Lowercase and without surrounding punctuation, which Gemini tends to put
+/// around a command ("Hello, new line, world").
+fn normalize_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+fn normalize_phrase(phrase: &str) -> Vec<String> {
+    phrase.split_whitespace().map(normalize_word).collect()
+}
+
+/// Everything the steps type, for the next dictation's prompt context.
+pub fn typed_text(steps: &[Step]) -> String {
+    steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Type(text) => Some(text.as_str()),
+            Step::Press(_) => None,
+        })
+        .collect()
+}
+
+/// Type and press the steps in order.
+pub fn execute(steps: &[Step], injector: &dyn Injector) -> Result<()> {
+    let mut first = true;
+    for step in steps {
+        match step {
+            Step::Type(text) if std::mem::take(&mut first) => injector.inject(text)?,
+            Step::Type(text) => injector.inject_delta(text)?,
+            Step::Press(shortcut) => {
+                first = false;
+                injector.press(shortcut)?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fail if a configured command cannot be parsed.
+pub fn check(config: &Config) -> Result<()> {
+    let builtin = BUILTINS.iter().map(|(lang, _)| *lang);
+    for language in builtin.chain(config.commands.keys().map(String::as_str)) {
+        Vocabulary::for_language(config, language)?;
+    }
+    Ok(())
+}
+
+/// Handle `g-type commands [LANG]` (`args` excludes the `commands` word).
+pub fn run_command(args: &[String]) -> Result<()> {
+    let config = crate::config::load()?;
+    let language = match args {
+        [] => config.language.as_str(),
+        [lang] => lang.as_str(),
+        _ => bail!("{USAGE}"),
+    };
+    let vocabulary = Vocabulary::for_language(&config, language)?;
+    let language = vocabulary_language(language);
+
+    println!();
+    println!(
+        "  \x1b[1m🗣️ Spoken commands ({language})\x1b[0m{}",
+        if config.spoken_commands {
+            ""
+        } else {
+            "  \x1b[2m— off, set spoken_commands = true\x1b[0m"
+        }
+    );
+    let mut phrases: Vec<_> = vocabulary.phrases.iter().collect();
+    phrases.sort_by(|a, b| a.0.cmp(&b.0));
+    for (words, action) in phrases {
+        println!("  • {:<28} {}", words.join(" "), action.describe());
+    }
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn apply(language: &str, text: &str) -> Vec<Step> {
+        Vocabulary::for_language(&Config::default(), language)
+            .unwrap()
+            .apply(text)
+    }
+
+    fn typed(language: &str, text: &str) -> String {
+        typed_text(&apply(language, text))
+    }
+
+    #[test]
+    fn test_punctuation_and_lines() {
+        assert_eq!(
+            typed("en", "hello comma world period how are you question mark"),
+            "hello, world. How are you?"
+        );
+        assert_eq!(
+            typed("en", "Dear Anna, new paragraph. thanks for the update"),
+            "Dear Anna,\n\nthanks for the update"
+        );
+        assert_eq!(
+            typed("en", "she said open quote hi close quote and left"),
+            "she said \"hi\" and left"
+        );
+        assert_eq!(
+            typed(
+                "it",
+                "Ciao punto e virgola a capo come stai punto interrogativo"
+            ),
+            "Ciao;\ncome stai?"
+        );
+        // Words that only contain a phrase are typed as spoken.
+        assert_eq!(typed("en", "periodically"), "periodically");
+    }
+
+    #[test]
+    fn test_edit_commands() {
+        assert_eq!(
+            typed("en", "the red delete last word blue car"),
+            "the blue car"
+        );
+        assert_eq!(
+            apply("en", "select all new text"),
+            [
+                Step::Press(Shortcut::parse("select_all").unwrap()),
+                Step::Type("new text".into()),
+            ]
+        );
+        // Nothing of this dictation to delete: use the shortcut.
+        assert_eq!(
+            apply("en", "delete last word"),
+            [Step::Press(Shortcut::parse("delete_word").unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_configured_commands() {
+        let mut config = Config::default();
+        config.commands.insert(
+            "en".into(),
+            BTreeMap::from([
+                ("smiley face".into(), "🙂".into()),
+                ("undo that".into(), "{ctrl+z}".into()),
+                ("period".into(), String::new()),
+            ]),
+        );
+        let vocabulary = Vocabulary::for_language(&config, "en").unwrap();
+        assert_eq!(
+            typed_text(&vocabulary.apply("great smiley face the period ends")),
+            "great 🙂 the period ends"
+        );
+        assert_eq!(
+            vocabulary.apply("oops undo that")[1],
+            Step::Press(Shortcut::parse("ctrl+z").unwrap())
+        );
+        // Vocabularies are never merged; "auto" without a detected
+        // language means English.
+        assert_eq!(typed("en", "uno virgola due"), "uno virgola due");
+        assert_eq!(typed("it", "uno virgola due"), "uno, due");
+        assert_eq!(typed("auto", "uno virgola due"), "uno virgola due");
+        assert_eq!(
+            typed("auto", "one comma two point five"),
+            "one, two point five"
+        );
+
+        config.commands.insert(
+            "en".into(),
+            BTreeMap::from([("x".into(), "{hyper+x}".into())]),
+        );
+        assert!(check(&config).is_err());
+    }
+}
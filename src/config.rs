@@ -1,12 +1,14 @@
 // config.rs — Safe TOML config loading with XDG-compliant directory resolution.
 // Interactive setup wizard for first-run. No manual file editing required.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
 use dialoguer::{theme::ColorfulTheme, Input, Select};
 use directories::ProjectDirs;
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -15,23 +17,475 @@ use tracing::{debug, info};
 /// Application configuration persisted to disk.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default)]
     pub api_key: String,
+    /// Gemini model, or an ordered fallback chain (see `Models`).
     #[serde(default = "default_model")]
-    pub model: String,
+    pub model: Models,
+    /// Origin of the Gemini REST API. Override for a proxy or a local mock.
+    #[serde(default = "default_api_base")]
+    pub api_base: String,
     #[serde(default = "default_hotkey")]
     pub hotkey: String,
+    /// Hold to speak an instruction for the selected text ("shorten this");
+    /// empty to disable.
+    #[serde(default)]
+    pub edit_hotkey: String,
+    /// Per-attempt HTTP timeout for a short recording (see `network::Timeouts`).
     #[serde(default = "default_timeout_secs")]
     pub timeout_secs: u64,
+    /// Limit for establishing a TCP/TLS connection.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Deadline for a whole dictation, including retries and fallbacks.
+    #[serde(default = "default_overall_timeout_secs")]
+    pub overall_timeout_secs: u64,
+    /// Added to both timeouts per minute of recorded audio.
+    #[serde(default = "default_timeout_per_audio_minute_secs")]
+    pub timeout_per_audio_minute_secs: u64,
+    /// Retries of a transient HTTP failure (429, 5xx) per request.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
     #[serde(default = "default_language")]
     pub language: String,
+    /// Product names, people, acronyms and identifiers Gemini should spell
+    /// exactly as written here.
+    #[serde(default)]
+    pub glossary: Vec<String>,
+    /// Prompt template: "default" (built in, in the dictation language) or a
+    /// key of `prompts`.
+    #[serde(default = "default_prompt")]
+    pub prompt: String,
+    /// Send the end of the previous dictation into the same app with the
+    /// prompt, as `{previous_text}`. Off by default: it leaves the machine
+    /// with every request.
+    #[serde(default)]
+    pub send_previous_text: bool,
+    /// Rewrite mode applied to dictations from `hotkey` (see `rewrite`);
+    /// empty to type the transcription as is.
+    #[serde(default)]
+    pub mode: String,
+    /// Model for rewrite modes; defaults to `model`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite_model: Option<Models>,
+    /// Name of the active entry in `profiles`; empty for none.
+    #[serde(default)]
+    pub profile: String,
     #[serde(default = "default_sound_enabled")]
     pub sound_enabled: bool,
     #[serde(default = "default_currency")]
     pub currency: String,
+    /// Show a desktop notification when a transcription fails.
+    #[serde(default = "default_notifications")]
+    pub notifications: bool,
+    /// Transcription backend (see `transcriber::BACKENDS`).
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    /// Stream Gemini output and type it while it is being generated.
+    #[serde(default = "default_streaming")]
+    pub streaming: bool,
+    /// Act on spoken commands ("new line", "select all", ...) instead of
+    /// typing them (see `commands`).
+    #[serde(default)]
+    pub spoken_commands: bool,
+    /// Ask Gemini for a JSON reply (text, detected language, silence flag)
+    /// instead of free text. Replies are typed in one piece, not streamed.
+    #[serde(default)]
+    pub structured_output: bool,
+    /// Upload encoding (see `encoder::FORMATS`).
+    #[serde(default = "default_audio_format")]
+    pub audio_format: String,
+    /// Recordings whose base64 payload exceeds this many MB are sent via the
+    /// Files API instead of inline (Gemini caps inline requests at 20 MB).
+    #[serde(default = "default_upload_threshold_mb")]
+    pub upload_threshold_mb: u64,
+    /// Settings for the `openai` backend.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub openai: OpenAiConfig,
+    /// Settings for the on-device `local` backend.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub local: LocalConfig,
+    /// Settings for the `vertex` backend (Gemini on Vertex AI).
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub vertex: VertexConfig,
+    /// Realtime mode over the Gemini Live API (gemini backend only).
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub live: LiveConfig,
+    /// Sampling, thinking and safety settings for Gemini requests.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub generation: GenerationConfig,
+    /// Splitting of long recordings into parallel requests.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub chunking: ChunkingConfig,
+    /// Offline spool for failed dictations.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub queue: QueueConfig,
+    /// Code dictation: identifier casing and spoken symbols (see `code`).
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub code: CodeConfig,
+    /// Rewrite modes by name, e.g. `[modes.email]` (see `rewrite`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modes: BTreeMap<String, ModeConfig>,
+    /// Extra spoken commands per language code, e.g. `[commands.en]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, BTreeMap<String, String>>,
+    /// User prompt templates by name (see `prompt`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prompts: BTreeMap<String, String>,
+    /// Named settings for different contexts, e.g. `[profiles.work]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Sections left at their defaults are not written back to config.toml, so
+/// the user's file only pins what they changed.
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// `[profiles.NAME]` — settings layered over the top level while
+/// `profile = "NAME"` is active.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Profile {
+    /// Terms added to the top-level `glossary`.
+    #[serde(default)]
+    pub glossary: Vec<String>,
+    /// Prompt template used instead of the top-level `prompt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Rewrite mode used instead of the top-level `mode`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+/// `[modes.NAME]` — a rewrite applied to the transcription before it is
+/// typed, optionally translating it. Built-in modes only need an entry to
+/// get a hotkey.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ModeConfig {
+    /// What to do with the text, e.g. "Turn it into a polite email.".
+    /// Defaults to the built-in instruction of the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruction: Option<String>,
+    /// Dictate with this mode by holding its own hotkey.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+    /// Language spoken with this mode, overriding `language`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Translate the text into this language before typing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate_to: Option<String>,
+}
+
+/// `model = "models/gemini-2.5-pro"` or an ordered fallback chain,
+/// `model = ["models/gemini-2.5-pro", "models/gemini-2.5-flash"]`.
+/// When a model is rate limited, overloaded or times out, the same audio is
+/// sent to the next one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Models {
+    One(String),
+    Chain(Vec<String>),
+}
+
+impl Models {
+    /// All configured models, primary first.
+    pub fn all(&self) -> &[String] {
+        match self {
+            Models::One(model) => std::slice::from_ref(model),
+            Models::Chain(models) => models,
+        }
+    }
+}
+
+impl From<&str> for Models {
+    fn from(model: &str) -> Self {
+        Models::One(model.to_string())
+    }
+}
+
+impl fmt::Display for Models {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.all().join(" → "))
+    }
+}
+
+/// Settings for OpenAI-compatible `/audio/transcriptions` servers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OpenAiConfig {
+    /// API base URL including the version segment, e.g. `http://localhost:8000/v1`.
+    #[serde(default = "default_openai_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_openai_model")]
+    pub model: String,
+    /// Bearer token. Leave empty for servers without auth.
+    #[serde(default)]
+    pub api_key: String,
+}
+
+/// Settings for the on-device Whisper backend (`--features local-whisper`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LocalConfig {
+    /// Path to a ggml Whisper model, e.g. `~/models/ggml-base.bin`
+    /// (see `model_file`).
+    #[serde(default)]
+    pub model_path: String,
+    /// Inference threads. 0 = one per available CPU.
+    #[serde(default)]
+    pub threads: u32,
+}
+
+/// Settings for Gemini on Vertex AI, authenticated with a service account.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VertexConfig {
+    /// Google Cloud project ID. Empty = `project_id` from the key file.
+    #[serde(default)]
+    pub project: String,
+    /// Vertex AI location, e.g. `us-central1`, `europe-west4` or `global`.
+    #[serde(default = "default_vertex_region")]
+    pub region: String,
+    /// Path to a service-account JSON key.
+    /// Empty = `$GOOGLE_APPLICATION_CREDENTIALS`.
+    #[serde(default)]
+    pub credentials: String,
+}
+
+/// Settings for the Gemini Live realtime mode: audio is pushed over a
+/// WebSocket while the hotkey is held instead of being uploaded on release.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LiveConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// A Live-capable model, e.g. `models/gemini-2.0-flash-live-001`.
+    #[serde(default = "default_live_model")]
+    pub model: String,
+    /// BidiGenerateContent WebSocket endpoint.
+    #[serde(default = "default_live_url")]
+    pub url: String,
+}
+
+/// `generationConfig` and `safetySettings` for Gemini requests. Thinking is
+/// set per model family (see `network::thinking_config`): unset values get
+/// the cheapest setting the model accepts, since dictation needs no reasoning.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GenerationConfig {
+    #[serde(default)]
+    pub temperature: f32,
+    #[serde(default = "default_max_output_tokens")]
+    pub max_output_tokens: u32,
+    /// Thinking tokens for 2.5 models (`0` = off, `-1` = dynamic).
+    #[serde(default)]
+    pub thinking_budget: Option<i32>,
+    /// Thinking level for 3.x models (`low`, `high`, ...).
+    #[serde(default)]
+    pub thinking_level: Option<String>,
+    /// Block threshold for every harm category, e.g. `BLOCK_ONLY_HIGH`.
+    /// Empty = provider default.
+    #[serde(default)]
+    pub safety_threshold: String,
+}
+
+/// Long recordings are cut at pauses into segments of at most
+/// `max_segment_secs` and transcribed concurrently.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChunkingConfig {
+    #[serde(default = "default_chunking_enabled")]
+    pub enabled: bool,
+    /// Recordings shorter than this are sent in one request.
+    #[serde(default = "default_max_segment_secs")]
+    pub max_segment_secs: u64,
+    /// Audio shared by neighbouring segments so no word is lost at a cut.
+    #[serde(default = "default_overlap_ms")]
+    pub overlap_ms: u64,
+    /// Segments in flight at once.
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+}
+
+/// Failed dictations are saved to a spool and retried in the background.
+/// The original focus is gone by then, so results go to the clipboard or a file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QueueConfig {
+    #[serde(default = "default_queue_enabled")]
+    pub enabled: bool,
+    /// Where recovered text goes: `clipboard` or `file`.
+    #[serde(default = "default_queue_deliver")]
+    pub deliver: String,
+    /// File for `deliver = "file"`. Empty = `queued-dictations.txt` in the data dir.
+    #[serde(default)]
+    pub output_file: String,
+    /// Seconds between background retries while the spool is not empty.
+    #[serde(default = "default_queue_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+    /// Background retries stop after this many attempts; the recording
+    /// stays queued for `g-type queue retry`.
+    #[serde(default = "default_queue_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_queue_enabled(),
+            deliver: default_queue_deliver(),
+            output_file: String::new(),
+            retry_interval_secs: default_queue_retry_interval_secs(),
+            max_attempts: default_queue_max_attempts(),
+        }
+    }
+}
+
+/// `[code]` — dictating code: "camel case user id" becomes `userId`, no
+/// sentence capitalization or punctuation.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CodeConfig {
+    /// Hold to dictate code; empty to disable.
+    #[serde(default)]
+    pub hotkey: String,
+    /// Dictate code from the main hotkey while an app in `apps` has focus.
+    #[serde(default)]
+    pub auto: bool,
+    /// Editors and terminals, matched case-insensitively against part of the
+    /// focused app's name (see `focus`).
+    #[serde(default = "default_code_apps")]
+    pub apps: Vec<String>,
+}
+
+impl Default for CodeConfig {
+    fn default() -> Self {
+        Self {
+            hotkey: String::new(),
+            auto: false,
+            apps: default_code_apps(),
+        }
+    }
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0,
+            max_output_tokens: default_max_output_tokens(),
+            thinking_budget: None,
+            thinking_level: None,
+            safety_threshold: String::new(),
+        }
+    }
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_chunking_enabled(),
+            max_segment_secs: default_max_segment_secs(),
+            overlap_ms: default_overlap_ms(),
+            max_parallel: default_max_parallel(),
+        }
+    }
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: default_live_model(),
+            url: default_live_url(),
+        }
+    }
+}
+
+impl LocalConfig {
+    /// `model_path` with a leading `~` expanded to the home directory.
+    #[cfg_attr(not(feature = "local-whisper"), allow(dead_code))]
+    pub fn model_file(&self) -> PathBuf {
+        let home = || directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf());
+        match self.model_path.strip_prefix('~') {
+            Some("") => home().unwrap_or_else(|| PathBuf::from("~")),
+            Some(rest) if rest.starts_with(['/', '\\']) => match home() {
+                Some(home) => home.join(&rest[1..]),
+                None => PathBuf::from(&self.model_path),
+            },
+            _ => PathBuf::from(&self.model_path),
+        }
+    }
+}
+
+impl Default for VertexConfig {
+    fn default() -> Self {
+        Self {
+            project: String::new(),
+            region: default_vertex_region(),
+            credentials: String::new(),
+        }
+    }
 }
 
-fn default_model() -> String {
-    "models/gemini-2.0-flash".into()
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: default_openai_base_url(),
+            model: default_openai_model(),
+            api_key: String::new(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            model: default_model(),
+            api_base: default_api_base(),
+            hotkey: default_hotkey(),
+            edit_hotkey: String::new(),
+            timeout_secs: default_timeout_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            overall_timeout_secs: default_overall_timeout_secs(),
+            timeout_per_audio_minute_secs: default_timeout_per_audio_minute_secs(),
+            max_retries: default_max_retries(),
+            language: default_language(),
+            glossary: Vec::new(),
+            prompt: default_prompt(),
+            send_previous_text: false,
+            mode: String::new(),
+            rewrite_model: None,
+            profile: String::new(),
+            sound_enabled: default_sound_enabled(),
+            currency: default_currency(),
+            notifications: default_notifications(),
+            backend: default_backend(),
+            streaming: default_streaming(),
+            spoken_commands: false,
+            structured_output: false,
+            audio_format: default_audio_format(),
+            upload_threshold_mb: default_upload_threshold_mb(),
+            openai: OpenAiConfig::default(),
+            local: LocalConfig::default(),
+            vertex: VertexConfig::default(),
+            live: LiveConfig::default(),
+            generation: GenerationConfig::default(),
+            chunking: ChunkingConfig::default(),
+            queue: QueueConfig::default(),
+            code: CodeConfig::default(),
+            modes: BTreeMap::new(),
+            commands: BTreeMap::new(),
+            prompts: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_model() -> Models {
+    Models::One("models/gemini-2.0-flash".into())
+}
+
+fn default_api_base() -> String {
+    "https://generativelanguage.googleapis.com".into()
+}
+
+fn default_prompt() -> String {
+    crate::prompt::DEFAULT_TEMPLATE.into()
 }
 
 fn default_hotkey() -> String {
@@ -42,6 +496,22 @@ fn default_timeout_secs() -> u64 {
     10
 }
 
+fn default_connect_timeout_secs() -> u64 {
+    5
+}
+
+fn default_overall_timeout_secs() -> u64 {
+    45
+}
+
+fn default_timeout_per_audio_minute_secs() -> u64 {
+    20
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
 fn default_language() -> String {
     "auto".into()
 }
@@ -54,6 +524,113 @@ fn default_currency() -> String {
     "USD".into()
 }
 
+fn default_notifications() -> bool {
+    true
+}
+
+fn default_backend() -> String {
+    "gemini".into()
+}
+
+fn default_streaming() -> bool {
+    true
+}
+
+fn default_audio_format() -> String {
+    "wav".into()
+}
+
+fn default_upload_threshold_mb() -> u64 {
+    15
+}
+
+fn default_openai_base_url() -> String {
+    "https://api.openai.com/v1".into()
+}
+
+fn default_openai_model() -> String {
+    "whisper-1".into()
+}
+
+fn default_vertex_region() -> String {
+    "us-central1".into()
+}
+
+fn default_chunking_enabled() -> bool {
+    true
+}
+
+fn default_max_segment_secs() -> u64 {
+    60
+}
+
+fn default_overlap_ms() -> u64 {
+    500
+}
+
+fn default_max_parallel() -> usize {
+    4
+}
+
+fn default_queue_enabled() -> bool {
+    true
+}
+
+fn default_queue_deliver() -> String {
+    "clipboard".into()
+}
+
+fn default_queue_retry_interval_secs() -> u64 {
+    60
+}
+
+fn default_queue_max_attempts() -> u32 {
+    5
+}
+
+fn default_code_apps() -> Vec<String> {
+    [
+        "code",
+        "cursor",
+        "zed",
+        "sublime",
+        "jetbrains",
+        "idea",
+        "pycharm",
+        "goland",
+        "clion",
+        "webstorm",
+        "rustrover",
+        "vim",
+        "emacs",
+        "xcode",
+        "terminal",
+        "iterm",
+        "konsole",
+        "alacritty",
+        "kitty",
+        "wezterm",
+        "xterm",
+        "ghostty",
+        "warp",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_max_output_tokens() -> u32 {
+    4096
+}
+
+fn default_live_model() -> String {
+    "models/gemini-2.0-flash-live-001".into()
+}
+
+fn default_live_url() -> String {
+    "wss://generativelanguage.googleapis.com/ws/google.ai.generativelanguage.v1beta.GenerativeService.BidiGenerateContent".into()
+}
+
 /// Available languages for transcription.
 const LANGUAGES: &[(&str, &str)] = &[
     ("auto", "Auto-detect"),
@@ -71,38 +648,80 @@ const LANGUAGES: &[(&str, &str)] = &[
     ("hi", "हिन्दी"),
 ];
 
-/// Get the transcription prompt for the configured language.
-pub fn transcription_prompt(language: &str) -> String {
-    let lang_instruction = match language {
-        "auto" | "" => String::new(),
-        code => {
-            let name = LANGUAGES
-                .iter()
-                .find(|(c, _)| *c == code)
-                .map(|(_, n)| *n)
-                .unwrap_or(code);
-            format!(" The audio is in {name} ({code}). Transcribe in that language.")
-        }
-    };
-    format!(
-        "Trascrivi esattamente ciò che viene detto in questo audio, parola per parola. \
-         Non aggiungere commenti, non rispondere a domande, non inventare punteggiatura. \
-         Restituisci SOLO il testo dettato. Se l'audio è silenzioso o incomprensibile, \
-         rispondi con una stringa vuota.{lang_instruction}"
-    )
+/// Display name of a language code, e.g. "Italiano" for "it".
+pub fn language_name(code: &str) -> &str {
+    LANGUAGES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, n)| *n)
+        .unwrap_or(code)
 }
 
 impl Config {
+    /// Origin of the Gemini REST API, without a trailing slash.
+    pub fn api_base(&self) -> &str {
+        self.api_base.trim_end_matches('/')
+    }
+
     /// Build the REST API URL for Gemini generateContent endpoint.
     /// The API key is NOT included in the URL — it is sent via the
-    /// `x-goog-api-key` HTTP header (see `network::transcribe`).
-    pub fn api_url(&self) -> String {
-        let model_name = self.model.strip_prefix("models/").unwrap_or(&self.model);
+    /// `x-goog-api-key` HTTP header (see `network::GeminiTranscriber`).
+    pub fn api_url(&self, model: &str) -> String {
+        let model_name = model.strip_prefix("models/").unwrap_or(model);
         format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
+            "{}/v1beta/models/{}:generateContent",
+            self.api_base(),
             model_name
         )
     }
+
+    /// Build the REST API URL for the streaming (SSE) variant of the endpoint.
+    pub fn stream_url(&self, model: &str) -> String {
+        let model_name = model.strip_prefix("models/").unwrap_or(model);
+        format!(
+            "{}/v1beta/models/{}:streamGenerateContent?alt=sse",
+            self.api_base(),
+            model_name
+        )
+    }
+
+    /// The profile selected by `profile`, if any.
+    pub fn active_profile(&self) -> Option<&Profile> {
+        self.profiles.get(&self.profile)
+    }
+
+    /// Rewrite mode for the main hotkey: the active profile's, else `mode`.
+    /// `None` when dictations are typed as is.
+    pub fn active_mode(&self) -> Option<&str> {
+        let mode = self
+            .active_profile()
+            .and_then(|p| p.mode.as_deref())
+            .unwrap_or(&self.mode)
+            .trim();
+        (!mode.is_empty()).then_some(mode)
+    }
+
+    /// Top-level glossary plus the active profile's, without duplicates.
+    pub fn glossary_terms(&self) -> Vec<String> {
+        let profile_terms = self.active_profile().map(|p| p.glossary.as_slice());
+        let mut terms: Vec<String> = Vec::new();
+        for term in self
+            .glossary
+            .iter()
+            .chain(profile_terms.unwrap_or_default())
+        {
+            let term = term.trim();
+            if !term.is_empty() && !terms.iter().any(|t| t.eq_ignore_ascii_case(term)) {
+                terms.push(term.to_string());
+            }
+        }
+        terms
+    }
+
+    /// Inline payload limit in bytes (see `upload_threshold_mb`).
+    pub fn upload_threshold_bytes(&self) -> usize {
+        (self.upload_threshold_mb as usize).saturating_mul(1024 * 1024)
+    }
 }
 
 // ── Paths ──────────────────────────────────────────────────
@@ -142,13 +761,27 @@ pub fn load() -> Result<Config> {
     let cfg: Config = toml::from_str(&raw)
         .with_context(|| format!("Failed to parse config at {}", path.display()))?;
 
-    if cfg.api_key.is_empty() || cfg.api_key == "YOUR_GEMINI_API_KEY_HERE" {
+    // Only the Gemini backend needs the Gemini API key.
+    let needs_key = cfg.backend == "gemini";
+    if needs_key && (cfg.api_key.is_empty() || cfg.api_key == "YOUR_GEMINI_API_KEY_HERE") {
         eprintln!();
         eprintln!("  API key not set. Re-running setup...");
         eprintln!();
         return interactive_setup(&path);
     }
 
+    if !cfg.profile.is_empty() && cfg.active_profile().is_none() {
+        bail!(
+            "Profile '{}' is not defined in {}. Add a [profiles.{}] section or clear `profile`",
+            cfg.profile,
+            path.display(),
+            cfg.profile
+        );
+    }
+    crate::prompt::check(&cfg)?;
+    crate::rewrite::check(&cfg)?;
+    crate::commands::check(&cfg)?;
+
     debug!(path = %path.display(), "Config loaded");
     Ok(cfg)
 }
@@ -222,7 +855,7 @@ pub fn interactive_setup(path: &PathBuf) -> Result<Config> {
         .default(0)
         .items(&models)
         .interact()?;
-    let model = models[model_idx].to_string();
+    let model = Models::from(models[model_idx]);
 
     // ── Step 4: Language ───────────────────────────────────
     let lang_labels: Vec<String> = LANGUAGES
@@ -279,10 +912,10 @@ pub fn interactive_setup(path: &PathBuf) -> Result<Config> {
         api_key,
         model,
         hotkey,
-        timeout_secs: default_timeout_secs(),
         language,
         sound_enabled,
         currency,
+        ..Config::default()
     };
 
     save(&cfg, path)?;
@@ -537,8 +1170,9 @@ fn save(cfg: &Config, path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-/// Update just the API key in an existing config.
-pub fn set_api_key(key: &str) -> Result<()> {
+/// Apply `change` to the config on disk (or to the defaults, if there is
+/// no config yet) and save the result.
+pub fn edit<T>(change: impl FnOnce(&mut Config) -> Result<T>) -> Result<T> {
     let path = config_path()?;
     let mut cfg = if path.exists() {
         let raw = fs::read_to_string(&path)
@@ -546,19 +1180,20 @@ pub fn set_api_key(key: &str) -> Result<()> {
         toml::from_str(&raw)
             .with_context(|| format!("Failed to parse config at {}", path.display()))?
     } else {
-        Config {
-            api_key: String::new(),
-            model: default_model(),
-            hotkey: default_hotkey(),
-            timeout_secs: default_timeout_secs(),
-            language: default_language(),
-            sound_enabled: default_sound_enabled(),
-            currency: default_currency(),
-        }
+        Config::default()
     };
 
-    cfg.api_key = key.to_string();
+    let result = change(&mut cfg)?;
     save(&cfg, &path)?;
+    Ok(result)
+}
+
+/// Update just the API key in an existing config.
+pub fn set_api_key(key: &str) -> Result<()> {
+    edit(|cfg| {
+        cfg.api_key = key.to_string();
+        Ok(())
+    })?;
     println!("  {} API key updated.", "✔".green().bold());
     Ok(())
 }
@@ -572,13 +1207,10 @@ mod tests {
         let cfg = Config {
             api_key: "test-key-123".into(),
             model: default_model(),
-            hotkey: default_hotkey(),
             timeout_secs: 3,
-            language: default_language(),
-            sound_enabled: default_sound_enabled(),
-            currency: default_currency(),
+            ..Config::default()
         };
-        let url = cfg.api_url();
+        let url = cfg.api_url(&cfg.model.all()[0]);
         // API key must NOT appear in the URL (sent via header).
         assert!(!url.contains("test-key-123"), "API key must not be in URL");
         assert!(url.contains("gemini-2.0-flash"));
@@ -590,12 +1222,131 @@ mod tests {
     fn test_defaults() {
         let raw = r#"api_key = "abc""#;
         let cfg: Config = toml::from_str(raw).unwrap();
-        assert_eq!(cfg.model, "models/gemini-2.0-flash");
+        assert_eq!(cfg.model.all(), ["models/gemini-2.0-flash"]);
+        assert_eq!(cfg.api_base(), "https://generativelanguage.googleapis.com");
         assert_eq!(cfg.hotkey, "ctrl+shift+space");
         assert_eq!(cfg.timeout_secs, 10);
+        assert_eq!(cfg.connect_timeout_secs, 5);
+        assert_eq!(cfg.overall_timeout_secs, 45);
+        assert_eq!(cfg.max_retries, 3);
         assert_eq!(cfg.language, "auto");
         assert!(cfg.sound_enabled);
         assert_eq!(cfg.currency, "USD");
+        assert_eq!(cfg.backend, "gemini");
+        assert!(cfg.streaming);
+        assert_eq!(cfg.openai.base_url, "https://api.openai.com/v1");
+        assert_eq!(cfg.openai.model, "whisper-1");
+        assert!(cfg.openai.api_key.is_empty());
+        assert!(cfg.local.model_path.is_empty());
+        assert_eq!(cfg.local.threads, 0);
+        assert!(!cfg.live.enabled);
+        assert_eq!(cfg.upload_threshold_mb, 15);
+        assert_eq!(cfg.audio_format, "wav");
+        assert!(cfg.notifications);
+        assert_eq!(cfg.upload_threshold_bytes(), 15 * 1024 * 1024);
+        assert!(cfg.chunking.enabled);
+        assert_eq!(cfg.chunking.max_segment_secs, 60);
+        assert_eq!(cfg.chunking.max_parallel, 4);
+        assert!(cfg.live.url.starts_with("wss://"));
+        assert!(cfg.queue.enabled);
+        assert_eq!(cfg.queue.deliver, "clipboard");
+        assert_eq!(cfg.queue.retry_interval_secs, 60);
+        assert_eq!(cfg.generation.temperature, 0.0);
+        assert_eq!(cfg.generation.max_output_tokens, 4096);
+        assert!(cfg.generation.thinking_budget.is_none());
+        assert!(cfg.generation.safety_threshold.is_empty());
+    }
+
+    #[test]
+    fn test_generation_section() {
+        let raw = r#"
+api_key = "abc"
+
+[generation]
+thinking_budget = 512
+thinking_level = "high"
+safety_threshold = "BLOCK_ONLY_HIGH"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.generation.thinking_budget, Some(512));
+        assert_eq!(cfg.generation.thinking_level.as_deref(), Some("high"));
+        assert_eq!(cfg.generation.safety_threshold, "BLOCK_ONLY_HIGH");
+        assert_eq!(cfg.generation.max_output_tokens, 4096);
+    }
+
+    #[test]
+    fn test_local_section() {
+        let raw = r#"
+backend = "local"
+
+[local]
+model_path = "/opt/models/ggml-base.bin"
+threads = 4
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.backend, "local");
+        assert_eq!(cfg.local.model_path, "/opt/models/ggml-base.bin");
+        assert_eq!(cfg.local.threads, 4);
+        assert_eq!(
+            cfg.local.model_file(),
+            PathBuf::from("/opt/models/ggml-base.bin")
+        );
+
+        let home = LocalConfig {
+            model_path: "~/models/ggml-base.bin".into(),
+            ..LocalConfig::default()
+        };
+        let file = home.model_file();
+        assert!(file.ends_with("models/ggml-base.bin"));
+        assert!(!file.starts_with("~"));
+    }
+
+    #[test]
+    fn test_live_section() {
+        let raw = r#"
+api_key = "abc"
+
+[live]
+enabled = true
+model = "models/gemini-live-2.5-flash-preview"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert!(cfg.live.enabled);
+        assert_eq!(cfg.live.model, "models/gemini-live-2.5-flash-preview");
+        assert_eq!(cfg.live.url, default_live_url());
+    }
+
+    #[test]
+    fn test_vertex_section() {
+        let raw = r#"
+backend = "vertex"
+
+[vertex]
+project = "acme-prod"
+credentials = "/etc/g-type/sa.json"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.backend, "vertex");
+        assert_eq!(cfg.vertex.project, "acme-prod");
+        assert_eq!(cfg.vertex.region, "us-central1");
+        assert_eq!(cfg.vertex.credentials, "/etc/g-type/sa.json");
+    }
+
+    #[test]
+    fn test_openai_section() {
+        let raw = r#"
+backend = "openai"
+
+[openai]
+base_url = "http://localhost:8000/v1"
+model = "Systran/faster-whisper-small"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.backend, "openai");
+        assert!(cfg.api_key.is_empty());
+        assert_eq!(cfg.openai.base_url, "http://localhost:8000/v1");
+        assert_eq!(cfg.openai.model, "Systran/faster-whisper-small");
+        assert!(cfg.openai.api_key.is_empty());
     }
 
     #[test]
@@ -608,23 +1359,89 @@ timeout_secs = 30
 "#;
         let cfg: Config = toml::from_str(raw).unwrap();
         assert_eq!(cfg.api_key, "AIzaSyTest");
-        assert_eq!(cfg.model, "models/gemini-1.5-pro");
+        assert_eq!(cfg.model.all()[0], "models/gemini-1.5-pro");
         assert_eq!(cfg.hotkey, "alt+f9");
         assert_eq!(cfg.timeout_secs, 30);
     }
 
+    #[test]
+    fn test_default_sections_not_saved() {
+        let mut cfg = Config::default();
+        let saved = toml::to_string_pretty(&cfg).unwrap();
+        for section in ["[queue]", "[chunking]", "[code]", "[live]", "[profiles"] {
+            assert!(!saved.contains(section), "{section} in {saved}");
+        }
+
+        cfg.chunking.max_parallel = 2;
+        cfg.profiles.insert("work".into(), Profile::default());
+        let saved = toml::to_string_pretty(&cfg).unwrap();
+        assert!(saved.contains("[chunking]") && saved.contains("[profiles.work]"));
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.chunking, cfg.chunking);
+        assert_eq!(reloaded.queue, QueueConfig::default());
+    }
+
+    #[test]
+    fn test_model_fallback_chain() {
+        let raw = r#"model = ["models/gemini-2.5-pro", "models/gemini-2.5-flash"]"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.model.all()[0], "models/gemini-2.5-pro");
+        assert_eq!(cfg.model.all().len(), 2);
+        assert_eq!(
+            cfg.model.to_string(),
+            "models/gemini-2.5-pro → models/gemini-2.5-flash"
+        );
+
+        // A single model is written back as a plain string.
+        let saved = toml::to_string(&Config::default()).unwrap();
+        assert!(saved.contains(r#"model = "models/gemini-2.0-flash""#));
+    }
+
+    #[test]
+    fn test_api_base_override() {
+        let raw = r#"
+api_key = "abc"
+api_base = "http://127.0.0.1:8080/"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(
+            cfg.api_url("models/gemini-2.5-flash"),
+            "http://127.0.0.1:8080/v1beta/models/gemini-2.5-flash:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_glossary_and_profiles() {
+        let raw = r#"
+api_key = "abc"
+glossary = ["Kubernetes", "G-Type"]
+profile = "work"
+
+[profiles.work]
+glossary = ["Acme Cloud", "kubernetes"]
+
+[profiles.home]
+glossary = ["Fido"]
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.profiles.len(), 2);
+        assert_eq!(cfg.glossary_terms(), ["Kubernetes", "G-Type", "Acme Cloud"]);
+
+        let none = Config {
+            profile: String::new(),
+            ..cfg
+        };
+        assert_eq!(none.glossary_terms(), ["Kubernetes", "G-Type"]);
+    }
+
     #[test]
     fn test_api_url_no_key_leak() {
         let cfg = Config {
             api_key: "AIzaSySECRET".into(),
             model: "models/gemini-2.0-flash".into(),
-            hotkey: default_hotkey(),
-            timeout_secs: 10,
-            language: default_language(),
-            sound_enabled: default_sound_enabled(),
-            currency: default_currency(),
+            ..Config::default()
         };
-        let url = cfg.api_url();
+        let url = cfg.api_url(&cfg.model.all()[0]);
         assert!(!url.contains("SECRET"), "API key must not appear in URL");
         assert!(!url.contains("key="), "No key= querystring allowed");
     }
@@ -634,13 +1451,24 @@ timeout_secs = 30
         let cfg = Config {
             api_key: "k".into(),
             model: "models/gemini-2.5-flash".into(),
-            hotkey: default_hotkey(),
-            timeout_secs: 10,
-            language: default_language(),
-            sound_enabled: default_sound_enabled(),
-            currency: default_currency(),
+            ..Config::default()
+        };
+        assert!(cfg
+            .api_url(&cfg.model.all()[0])
+            .contains("gemini-2.5-flash:generateContent"));
+    }
+
+    #[test]
+    fn test_stream_url() {
+        let cfg = Config {
+            api_key: "AIzaSySECRET".into(),
+            model: "models/gemini-2.5-flash".into(),
+            ..Config::default()
         };
-        assert!(cfg.api_url().contains("gemini-2.5-flash:generateContent"));
+        let url = cfg.stream_url(&cfg.model.all()[0]);
+        assert!(url.contains("gemini-2.5-flash:streamGenerateContent"));
+        assert!(url.ends_with("?alt=sse"));
+        assert!(!url.contains("SECRET"));
     }
 
     #[test]
@@ -648,12 +1476,10 @@ timeout_secs = 30
         let cfg = Config {
             api_key: "k".into(),
             model: "gemini-2.0-pro".into(),
-            hotkey: default_hotkey(),
-            timeout_secs: 10,
-            language: default_language(),
-            sound_enabled: default_sound_enabled(),
-            currency: default_currency(),
+            ..Config::default()
         };
-        assert!(cfg.api_url().contains("gemini-2.0-pro:generateContent"));
+        assert!(cfg
+            .api_url(&cfg.model.all()[0])
+            .contains("gemini-2.0-pro:generateContent"));
     }
 }
@@ -0,0 +1,303 @@
+// encoder.rs — Audio container/codec for uploads.
+// Backends receive raw PCM i16 16kHz mono and call `encode` with the format
+// from config. WAV is uncompressed, FLAC is lossless at roughly half the size,
+// Ogg/Opus (`--features opus`) is lossy but ~20× smaller — best on slow links.
+
+use anyhow::{anyhow, bail, Result};
+
+const SAMPLE_RATE: u32 = 16_000;
+
+/// Format identifiers accepted by the `audio_format` config key.
+pub const FORMATS: &[&str] = &["wav", "flac", "opus"];
+
+/// Upload format for recorded audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+    /// Only selectable in builds with `--features opus`.
+    #[cfg_attr(not(feature = "opus"), allow(dead_code))]
+    Opus,
+}
+
+/// Encoded audio ready to upload.
+#[derive(Debug, Clone)]
+pub struct EncodedAudio {
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+    /// File extension for multipart uploads, without the dot.
+    pub extension: &'static str,
+}
+
+impl AudioFormat {
+    /// Parse the `audio_format` config value.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "wav" => Ok(AudioFormat::Wav),
+            "flac" => Ok(AudioFormat::Flac),
+            #[cfg(feature = "opus")]
+            "opus" => Ok(AudioFormat::Opus),
+            #[cfg(not(feature = "opus"))]
+            "opus" => bail!(
+                "This build has no Opus encoder. \
+                 Rebuild with `cargo install --path . --features opus` or use audio_format = \"flac\""
+            ),
+            other => bail!(
+                "Unknown audio_format '{}'. Available: {}",
+                other,
+                FORMATS.join(", ")
+            ),
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Opus => "audio/ogg",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Opus => "ogg",
+        }
+    }
+}
+
+/// Encode PCM i16 mono 16kHz samples in the given format.
+pub fn encode(samples: &[i16], format: AudioFormat) -> Result<EncodedAudio> {
+    let bytes = match format {
+        AudioFormat::Wav => encode_wav(samples),
+        AudioFormat::Flac => encode_flac(samples)?,
+        #[cfg(feature = "opus")]
+        AudioFormat::Opus => opus_ogg::encode(samples)?,
+        #[cfg(not(feature = "opus"))]
+        AudioFormat::Opus => bail!("Opus support not compiled in"),
+    };
+    Ok(EncodedAudio {
+        bytes,
+        mime_type: format.mime_type(),
+        extension: format.extension(),
+    })
+}
+
+/// Encode PCM i16 mono 16kHz samples as a WAV file in memory.
+pub fn encode_wav(samples: &[i16]) -> Vec<u8> {
+    let num_channels: u16 = 1;
+    let sample_rate: u32 = SAMPLE_RATE;
+    let bits_per_sample: u16 = 16;
+    let byte_rate = sample_rate * (num_channels as u32) * (bits_per_sample as u32 / 8);
+    let block_align = num_channels * (bits_per_sample / 8);
+    let data_size = (samples.len() * 2) as u32;
+    let file_size = 36 + data_size; // RIFF header is 44 bytes, file_size = total - 8
+
+    let mut buf = Vec::with_capacity(44 + data_size as usize);
+
+    // RIFF header
+    buf.extend_from_slice(b"RIFF");
+    buf.extend_from_slice(&file_size.to_le_bytes());
+    buf.extend_from_slice(b"WAVE");
+
+    // fmt sub-chunk
+    buf.extend_from_slice(b"fmt ");
+    buf.extend_from_slice(&16u32.to_le_bytes()); // sub-chunk size (PCM = 16)
+    buf.extend_from_slice(&1u16.to_le_bytes()); // audio format (PCM = 1)
+    buf.extend_from_slice(&num_channels.to_le_bytes());
+    buf.extend_from_slice(&sample_rate.to_le_bytes());
+    buf.extend_from_slice(&byte_rate.to_le_bytes());
+    buf.extend_from_slice(&block_align.to_le_bytes());
+    buf.extend_from_slice(&bits_per_sample.to_le_bytes());
+
+    // data sub-chunk
+    buf.extend_from_slice(b"data");
+    buf.extend_from_slice(&data_size.to_le_bytes());
+
+    // PCM samples (little-endian i16)
+    for &sample in samples {
+        buf.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    buf
+}
+
+/// Encode PCM i16 mono 16kHz samples as a FLAC stream (pure Rust, lossless).
+fn encode_flac(samples: &[i16]) -> Result<Vec<u8>> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
+    let pcm: Vec<i32> = samples.iter().map(|&s| s as i32).collect();
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| anyhow!("Invalid FLAC encoder config: {e:?}"))?;
+    let source = flacenc::source::MemSource::from_samples(&pcm, 1, 16, SAMPLE_RATE as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| anyhow!("FLAC encoding failed: {e:?}"))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| anyhow!("FLAC encoding failed: {e:?}"))?;
+    Ok(sink.as_slice().to_vec())
+}
+
+/// Ogg/Opus encoding via libopus (RFC 7845 framing).
+#[cfg(feature = "opus")]
+mod opus_ogg {
+    use anyhow::{Context, Result};
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+    use opus::{Application, Bitrate, Channels, Encoder};
+
+    use super::SAMPLE_RATE;
+
+    /// 20 ms frames.
+    const FRAME: usize = SAMPLE_RATE as usize / 50;
+    /// Ogg/Opus granule positions are always counted at 48 kHz.
+    const GRANULE_SCALE: u64 = 48_000 / SAMPLE_RATE as u64;
+    /// Plenty for speech; Gemini and Whisper are robust well below this.
+    const BITRATE: i32 = 24_000;
+    const SERIAL: u32 = 0x6774_7970; // "gtyp"
+
+    pub fn encode(samples: &[i16]) -> Result<Vec<u8>> {
+        let mut encoder = Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Voip)
+            .context("Failed to create Opus encoder")?;
+        encoder
+            .set_bitrate(Bitrate::Bits(BITRATE))
+            .context("Failed to set Opus bitrate")?;
+        let pre_skip = encoder.get_lookahead().context("Opus lookahead")? as u64 * GRANULE_SCALE;
+
+        let mut writer = PacketWriter::new(Vec::new());
+        writer.write_packet(
+            opus_head(pre_skip as u16),
+            SERIAL,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+        writer.write_packet(opus_tags(), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+        let frames: Vec<&[i16]> = samples.chunks(FRAME).collect();
+        let mut out = vec![0u8; 4000];
+        let mut frame_buf = vec![0i16; FRAME];
+        for (i, frame) in frames.iter().enumerate() {
+            // The last frame is zero-padded; the granule position trims it.
+            frame_buf[..frame.len()].copy_from_slice(frame);
+            frame_buf[frame.len()..].fill(0);
+            let len = encoder
+                .encode(&frame_buf, &mut out)
+                .context("Opus encoding failed")?;
+
+            let last = i + 1 == frames.len();
+            let decoded = if last { samples.len() } else { (i + 1) * FRAME };
+            let info = if last {
+                PacketWriteEndInfo::EndStream
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            let granule = pre_skip + decoded as u64 * GRANULE_SCALE;
+            writer.write_packet(out[..len].to_vec(), SERIAL, info, granule)?;
+        }
+
+        Ok(writer.into_inner())
+    }
+
+    fn opus_head(pre_skip: u16) -> Vec<u8> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1); // version
+        head.push(1); // channels
+        head.extend_from_slice(&pre_skip.to_le_bytes());
+        head.extend_from_slice(&SAMPLE_RATE.to_le_bytes()); // original input rate
+        head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+        head.push(0); // mapping family: mono/stereo
+        head
+    }
+
+    fn opus_tags() -> Vec<u8> {
+        let vendor = b"g-type";
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor);
+        tags.extend_from_slice(&0u32.to_le_bytes()); // no user comments
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| ((i as f64 * 0.05).sin() * 8000.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_wav_header() {
+        let samples: Vec<i16> = vec![0; 1600]; // 100ms at 16kHz
+        let wav = encode_wav(&samples);
+
+        // Check RIFF header
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(&wav[36..40], b"data");
+
+        // Data size should be samples * 2 bytes
+        let data_size = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]);
+        assert_eq!(data_size, 3200); // 1600 samples * 2 bytes
+    }
+
+    #[test]
+    fn test_encode_wav_total_size() {
+        let samples: Vec<i16> = vec![100, -100, 0, i16::MAX, i16::MIN];
+        let wav = encode_wav(&samples);
+        assert_eq!(wav.len(), 44 + samples.len() * 2); // 44 header + data
+    }
+
+    #[test]
+    fn test_encode_wav_empty() {
+        let samples: Vec<i16> = vec![];
+        let wav = encode_wav(&samples);
+        // Header only, 0 data bytes
+        assert_eq!(wav.len(), 44);
+        let data_size = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]);
+        assert_eq!(data_size, 0);
+    }
+
+    #[test]
+    fn test_encode_flac_smaller_than_wav() {
+        let samples = tone(16_000);
+        let flac = encode(&samples, AudioFormat::Flac).unwrap();
+        assert_eq!(&flac.bytes[0..4], b"fLaC");
+        assert_eq!(flac.mime_type, "audio/flac");
+        assert!(flac.bytes.len() < encode_wav(&samples).len() / 2);
+    }
+
+    #[test]
+    fn test_format_from_name() {
+        assert_eq!(AudioFormat::from_name("wav").unwrap(), AudioFormat::Wav);
+        assert_eq!(AudioFormat::from_name("FLAC").unwrap(), AudioFormat::Flac);
+        assert_eq!(AudioFormat::Opus.mime_type(), "audio/ogg");
+        let err = AudioFormat::from_name("mp3").unwrap_err();
+        assert!(err.to_string().contains("wav, flac, opus"));
+    }
+
+    #[cfg(not(feature = "opus"))]
+    #[test]
+    fn test_opus_without_feature() {
+        let err = AudioFormat::from_name("opus").unwrap_err();
+        assert!(err.to_string().contains("--features opus"));
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn test_encode_opus_ogg() {
+        let samples = tone(16_000);
+        let ogg = encode(&samples, AudioFormat::Opus).unwrap();
+        assert_eq!(&ogg.bytes[0..4], b"OggS");
+        assert!(ogg.bytes.windows(8).any(|w| w == b"OpusHead"));
+        assert!(ogg.bytes.len() < encode_wav(&samples).len() / 8);
+    }
+}
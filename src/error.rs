@@ -0,0 +1,229 @@
+// error.rs — Typed transcription failures.
+// Backends return these (wrapped in anyhow) instead of smuggling error text
+// into the transcription; `app` decides how to surface them — error beep,
+// log line, desktop notification — and never types them into the focused app.
+
+use serde_json::Value;
+use std::fmt;
+
+/// Why a transcription failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscribeError {
+    /// Too many requests right now (HTTP 429); retrying later will work.
+    RateLimited { message: String },
+    /// Missing, invalid or unauthorized API key (HTTP 401/403).
+    Auth { message: String },
+    /// Daily quota or billing limit reached.
+    Quota { message: String },
+    /// The provider refused to answer (safety filters, recitation, ...).
+    SafetyBlocked { reason: String },
+    /// The reply hit the output token limit and is incomplete.
+    Truncated,
+    /// Connection, DNS or TLS failure — no usable response.
+    Network { message: String },
+    /// No complete response within the request timeout.
+    Timeout,
+    /// Any other non-success response.
+    Server { status: u16, message: String },
+}
+
+impl TranscribeError {
+    /// Classify a failed HTTP response from its status and body.
+    /// Understands both Google (`error.status`) and OpenAI (`error.code`) bodies.
+    pub fn from_http(status: u16, body: &str) -> Self {
+        let parsed: Option<Value> = serde_json::from_str(body).ok();
+        let error = parsed.as_ref().and_then(|v| v.get("error"));
+        let message = error
+            .and_then(|e| e.get("message"))
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| body.chars().take(200).collect());
+        let code = error
+            .and_then(|e| e.get("code"))
+            .and_then(|c| c.as_str())
+            .unwrap_or_default();
+
+        let lower = message.to_lowercase();
+        match status {
+            401 | 403 => TranscribeError::Auth { message },
+            400 if lower.contains("api key") => TranscribeError::Auth { message },
+            402 => TranscribeError::Quota { message },
+            429 if code == "insufficient_quota"
+                || lower.contains("current quota")
+                || lower.contains("billing") =>
+            {
+                TranscribeError::Quota { message }
+            }
+            429 => TranscribeError::RateLimited { message },
+            _ => TranscribeError::Server { status, message },
+        }
+    }
+
+    /// Classify an `error` object embedded in a 200 response or SSE event.
+    pub fn from_api_error(error: &Value) -> Self {
+        let status = error.get("code").and_then(|c| c.as_u64()).unwrap_or(500) as u16;
+        Self::from_http(status, &serde_json::json!({ "error": error }).to_string())
+    }
+
+    /// Map a Gemini `finishReason` to an error. `None` means the text is usable.
+    pub fn from_finish_reason(reason: &str) -> Option<Self> {
+        match reason {
+            "MAX_TOKENS" => Some(TranscribeError::Truncated),
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => {
+                Some(TranscribeError::SafetyBlocked {
+                    reason: reason.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Worth retrying the same request elsewhere (e.g. on a fallback model):
+    /// rate limits, server-side failures and timeouts.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TranscribeError::RateLimited { .. } | TranscribeError::Timeout => true,
+            TranscribeError::Server { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    pub fn network(err: impl fmt::Display) -> Self {
+        TranscribeError::Network {
+            message: err.to_string(),
+        }
+    }
+
+    /// Classify a failed HTTP exchange: `Timeout` if the request timeout
+    /// fired, `Network` otherwise.
+    pub fn request(timed_out: bool, err: impl fmt::Display) -> Self {
+        if timed_out {
+            TranscribeError::Timeout
+        } else {
+            Self::network(err)
+        }
+    }
+
+    /// Short text for a desktop notification.
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            TranscribeError::RateLimited { .. } => {
+                "Too many requests. Wait a few seconds and try again."
+            }
+            TranscribeError::Auth { .. } => {
+                "API key rejected. Run `g-type setup` to enter a valid key."
+            }
+            TranscribeError::Quota { .. } => "API quota exhausted. Check your plan and billing.",
+            TranscribeError::SafetyBlocked { .. } => {
+                "The provider refused to transcribe this audio."
+            }
+            TranscribeError::Truncated => "Transcription was cut off at the output token limit.",
+            TranscribeError::Network { .. } => "Network error. Check your connection.",
+            TranscribeError::Timeout => "The transcription service did not answer in time.",
+            TranscribeError::Server { .. } => "The transcription service returned an error.",
+        }
+    }
+}
+
+impl fmt::Display for TranscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscribeError::RateLimited { message } => write!(f, "rate limited: {message}"),
+            TranscribeError::Auth { message } => write!(f, "authentication failed: {message}"),
+            TranscribeError::Quota { message } => write!(f, "quota exhausted: {message}"),
+            TranscribeError::SafetyBlocked { reason } => {
+                write!(f, "blocked by provider ({reason})")
+            }
+            TranscribeError::Truncated => write!(f, "output truncated at max tokens"),
+            TranscribeError::Network { message } => write!(f, "network error: {message}"),
+            TranscribeError::Timeout => write!(f, "request timed out"),
+            TranscribeError::Server { status, message } => {
+                write!(f, "server error (HTTP {status}): {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranscribeError {}
+
+/// Find the `TranscribeError` behind an anyhow error, through any context.
+pub fn find(err: &anyhow::Error) -> Option<&TranscribeError> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<TranscribeError>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_http_gemini_bodies() {
+        let rate = r#"{"error":{"code":429,"message":"Resource has been exhausted (e.g. check quota).","status":"RESOURCE_EXHAUSTED"}}"#;
+        assert!(matches!(
+            TranscribeError::from_http(429, rate),
+            TranscribeError::RateLimited { .. }
+        ));
+
+        let quota = r#"{"error":{"code":429,"message":"You exceeded your current quota, please check your plan and billing details."}}"#;
+        assert!(matches!(
+            TranscribeError::from_http(429, quota),
+            TranscribeError::Quota { .. }
+        ));
+
+        let key = r#"{"error":{"code":400,"message":"API key not valid. Please pass a valid API key.","status":"INVALID_ARGUMENT"}}"#;
+        assert_eq!(
+            TranscribeError::from_http(400, key),
+            TranscribeError::Auth {
+                message: "API key not valid. Please pass a valid API key.".into()
+            }
+        );
+
+        assert!(matches!(
+            TranscribeError::from_http(503, "upstream unavailable"),
+            TranscribeError::Server { status: 503, .. }
+        ));
+    }
+
+    #[test]
+    fn test_from_http_openai_quota() {
+        let body = r#"{"error":{"message":"You exceeded your quota.","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert!(matches!(
+            TranscribeError::from_http(429, body),
+            TranscribeError::Quota { .. }
+        ));
+    }
+
+    #[test]
+    fn test_from_finish_reason() {
+        assert_eq!(TranscribeError::from_finish_reason("STOP"), None);
+        assert_eq!(
+            TranscribeError::from_finish_reason("MAX_TOKENS"),
+            Some(TranscribeError::Truncated)
+        );
+        assert!(matches!(
+            TranscribeError::from_finish_reason("SAFETY"),
+            Some(TranscribeError::SafetyBlocked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(TranscribeError::from_http(429, "slow down").is_transient());
+        assert!(TranscribeError::from_http(503, "overloaded").is_transient());
+        assert!(TranscribeError::Timeout.is_transient());
+        assert!(!TranscribeError::from_http(400, "bad request").is_transient());
+        assert!(!TranscribeError::from_http(403, "denied").is_transient());
+        assert!(!TranscribeError::Truncated.is_transient());
+    }
+
+    #[test]
+    fn test_find_through_context() {
+        let err: anyhow::Result<()> =
+            Err(TranscribeError::from_api_error(&json!({"code": 403, "message": "denied"})).into());
+        let err = err.context("Segment 2 failed").unwrap_err();
+        assert!(matches!(find(&err), Some(TranscribeError::Auth { .. })));
+        assert!(find(&anyhow::anyhow!("plain")).is_none());
+    }
+}
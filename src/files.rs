@@ -0,0 +1,374 @@
+// files.rs — Gemini Files API client for recordings too large to inline.
+// Uses the resumable upload protocol (start → upload+finalize), waits for the
+// file to become ACTIVE, and hands back a reference usable as `fileData`.
+// Uploaded audio is deleted again once the transcription is done.
+
+use anyhow::{bail, Context, Result};
+use reqwest_middleware::ClientWithMiddleware;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::TranscribeError;
+use crate::network::send_error;
+
+/// How long to wait for the server to finish processing an upload.
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const ACTIVE_POLL_ATTEMPTS: u32 = 20;
+
+/// A file stored by the Files API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadedFile {
+    /// Resource name, e.g. `files/abc-123` (used for GET/DELETE).
+    pub name: String,
+    /// URI referenced from `fileData.fileUri`.
+    pub uri: String,
+    pub mime_type: String,
+}
+
+/// Upload `bytes` and wait until the file can be used in a request.
+///
+/// `base` is the API origin, e.g. `https://generativelanguage.googleapis.com`.
+/// Each request gets `timeout`, sized for sending the whole recording.
+pub async fn upload(
+    client: &ClientWithMiddleware,
+    base: &str,
+    api_key: &str,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    timeout: Duration,
+) -> Result<UploadedFile> {
+    let size = bytes.len();
+
+    // Step 1: open a resumable session; the upload URL comes back in a header.
+    let start = client
+        .post(format!("{base}/upload/v1beta/files"))
+        .header("x-goog-api-key", api_key)
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header("X-Goog-Upload-Header-Content-Length", size.to_string())
+        .header("X-Goog-Upload-Header-Content-Type", mime_type)
+        .json(&json!({ "file": { "display_name": "g-type-dictation" } }))
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(send_error)
+        .context("Failed to start Files API upload")?;
+
+    let status = start.status();
+    if !status.is_success() {
+        let body = start.text().await.unwrap_or_default();
+        return Err(TranscribeError::from_http(status.as_u16(), &body))
+            .context("Files API upload start failed");
+    }
+    let upload_url = start
+        .headers()
+        .get("x-goog-upload-url")
+        .and_then(|v| v.to_str().ok())
+        .context("Files API did not return an upload URL")?
+        .to_string();
+
+    // Step 2: send the bytes and finalize in one request.
+    let finish = client
+        .post(&upload_url)
+        .header("X-Goog-Upload-Offset", "0")
+        .header("X-Goog-Upload-Command", "upload, finalize")
+        .body(bytes)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(send_error)
+        .context("Failed to upload audio to Files API")?;
+
+    let status = finish.status();
+    let body = finish
+        .text()
+        .await
+        .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+        .context("Failed to read Files API response")?;
+    if !status.is_success() {
+        return Err(TranscribeError::from_http(status.as_u16(), &body))
+            .context("Files API upload failed");
+    }
+    let parsed: Value =
+        serde_json::from_str(&body).context("Failed to parse Files API response")?;
+    let file = parse_file(&parsed["file"])?;
+    debug!(name = %file.name, size, "Audio uploaded to Files API");
+
+    // Step 3: audio is usually ACTIVE right away, but may still be PROCESSING.
+    // An upload that never becomes usable is already stored: delete it.
+    let state = parsed["file"]["state"].as_str().unwrap_or("ACTIVE");
+    let name = file.name.clone();
+    match wait_active(client, base, api_key, file, state, timeout).await {
+        Ok(file) => Ok(file),
+        Err(e) => {
+            if let Err(e) = delete(client, base, api_key, &name, timeout).await {
+                warn!(%e, name, "Failed to delete unusable upload");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Poll `file` until it leaves PROCESSING; fails unless it ends up ACTIVE.
+async fn wait_active(
+    client: &ClientWithMiddleware,
+    base: &str,
+    api_key: &str,
+    mut file: UploadedFile,
+    state: &str,
+    timeout: Duration,
+) -> Result<UploadedFile> {
+    let mut state = state.to_string();
+    let mut attempts = 0;
+    while state == "PROCESSING" {
+        attempts += 1;
+        if attempts > ACTIVE_POLL_ATTEMPTS {
+            return Err(TranscribeError::Timeout).with_context(|| {
+                format!("Uploaded file {} did not become ACTIVE in time", file.name)
+            });
+        }
+        tokio::time::sleep(ACTIVE_POLL_INTERVAL).await;
+        let current = get(client, base, api_key, &file.name, timeout).await?;
+        state = current["state"].as_str().unwrap_or("ACTIVE").to_string();
+        file = parse_file(&current)?;
+    }
+    if state == "FAILED" {
+        return Err(TranscribeError::Server {
+            status: 500,
+            message: format!("Files API failed to process {}", file.name),
+        }
+        .into());
+    }
+
+    Ok(file)
+}
+
+/// Delete an uploaded file. Files expire on their own after 48 h; deleting
+/// right away keeps dictated audio off the server.
+pub async fn delete(
+    client: &ClientWithMiddleware,
+    base: &str,
+    api_key: &str,
+    name: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let response = client
+        .delete(format!("{base}/v1beta/{name}"))
+        .header("x-goog-api-key", api_key)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(send_error)
+        .context("Files API delete request failed")?;
+    if !response.status().is_success() {
+        bail!("Files API delete failed ({})", response.status());
+    }
+    Ok(())
+}
+
+/// The `fileData` part referencing an uploaded file.
+pub fn file_part(file: &UploadedFile) -> Value {
+    json!({
+        "fileData": {
+            "mimeType": file.mime_type,
+            "fileUri": file.uri
+        }
+    })
+}
+
+async fn get(
+    client: &ClientWithMiddleware,
+    base: &str,
+    api_key: &str,
+    name: &str,
+    timeout: Duration,
+) -> Result<Value> {
+    let response = client
+        .get(format!("{base}/v1beta/{name}"))
+        .header("x-goog-api-key", api_key)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(send_error)
+        .context("Files API status request failed")?;
+    if !response.status().is_success() {
+        bail!("Files API status request failed ({})", response.status());
+    }
+    response
+        .json()
+        .await
+        .context("Failed to parse Files API status")
+}
+
+fn parse_file(file: &Value) -> Result<UploadedFile> {
+    let field = |key: &str| {
+        file.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .with_context(|| format!("Files API response missing `{key}`"))
+    };
+    Ok(UploadedFile {
+        name: field("name")?,
+        uri: field("uri")?,
+        mime_type: field("mimeType")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{StubResponse, StubServer};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn client() -> ClientWithMiddleware {
+        reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build()
+    }
+
+    fn file_json(state: &str) -> Value {
+        json!({
+            "name": "files/abc-123",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc-123",
+            "mimeType": "audio/wav",
+            "state": state
+        })
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload() {
+        let polls = Arc::new(AtomicU32::new(0));
+        let polls_seen = polls.clone();
+        let server = StubServer::start(move |req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/upload/v1beta/files") => {
+                let url = format!("http://{}/session/1", req.header("host").unwrap());
+                StubResponse::json(200, json!({})).with_header("X-Goog-Upload-URL", &url)
+            }
+            ("POST", "/session/1") => {
+                StubResponse::json(200, json!({ "file": file_json("PROCESSING") }))
+            }
+            ("GET", "/v1beta/files/abc-123") => {
+                polls_seen.fetch_add(1, Ordering::SeqCst);
+                StubResponse::json(200, file_json("ACTIVE"))
+            }
+            _ => StubResponse::text(404, "not found"),
+        })
+        .await;
+
+        let file = upload(
+            &client(),
+            &server.url,
+            "AIzaSyTest",
+            vec![7u8; 1000],
+            "audio/wav",
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.name, "files/abc-123");
+        assert_eq!(polls.load(Ordering::SeqCst), 1);
+
+        let requests = server.requests();
+        let start = &requests[0];
+        assert_eq!(start.header("x-goog-upload-protocol"), Some("resumable"));
+        assert_eq!(
+            start.header("x-goog-upload-header-content-length"),
+            Some("1000")
+        );
+        assert_eq!(start.header("x-goog-api-key"), Some("AIzaSyTest"));
+        let data = &requests[1];
+        assert_eq!(
+            data.header("x-goog-upload-command"),
+            Some("upload, finalize")
+        );
+        assert_eq!(data.body.len(), 1000);
+
+        let part = file_part(&file);
+        assert_eq!(part["fileData"]["fileUri"], file.uri);
+        assert_eq!(part["fileData"]["mimeType"], "audio/wav");
+    }
+
+    #[tokio::test]
+    async fn test_upload_start_rejected() {
+        let server = StubServer::start(|_| StubResponse::text(403, "denied")).await;
+        let err = upload(
+            &client(),
+            &server.url,
+            "bad",
+            vec![0u8; 10],
+            "audio/wav",
+            TIMEOUT,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_is_deleted() {
+        let server = StubServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/upload/v1beta/files") => {
+                let url = format!("http://{}/session/1", req.header("host").unwrap());
+                StubResponse::json(200, json!({})).with_header("X-Goog-Upload-URL", &url)
+            }
+            ("POST", "/session/1") => {
+                StubResponse::json(200, json!({ "file": file_json("PROCESSING") }))
+            }
+            ("GET", "/v1beta/files/abc-123") => StubResponse::json(200, file_json("FAILED")),
+            ("DELETE", "/v1beta/files/abc-123") => StubResponse::json(200, json!({})),
+            _ => StubResponse::text(404, "not found"),
+        })
+        .await;
+
+        let err = upload(
+            &client(),
+            &server.url,
+            "AIzaSyTest",
+            vec![7u8; 10],
+            "audio/wav",
+            TIMEOUT,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::Server { status: 500, .. })
+        ));
+        let requests = server.requests();
+        let last = requests.last().unwrap();
+        assert_eq!(
+            (last.method.as_str(), last.path.as_str()),
+            ("DELETE", "/v1beta/files/abc-123")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_timeout() {
+        // Accepts connections but never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+
+        let err = upload(
+            &client(),
+            &url,
+            "AIzaSyTest",
+            vec![7u8; 10],
+            "audio/wav",
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Timeout));
+    }
+}
@@ -0,0 +1,47 @@
+// focus.rs — Name of the application that has keyboard focus.
+// Fills the `{app}` prompt variable and scopes `{previous_text}` to the app
+// it was typed into. Uses `xdotool` (Linux/X11) and `osascript` (macOS); on
+// Wayland, Windows, or if the tool is missing, the app is simply unknown.
+
+use tracing::debug;
+
+/// The focused application, e.g. `code` or `Slack`, if it can be determined.
+/// Runs a short-lived helper process, so call it off the async runtime.
+pub fn focused_app() -> Option<String> {
+    let mut cmd = command()?;
+    let output = match cmd.output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            debug!(status = %output.status, "Focused app lookup failed");
+            return None;
+        }
+        Err(e) => {
+            debug!(%e, "Focused app lookup unavailable");
+            return None;
+        }
+    };
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(target_os = "linux")]
+fn command() -> Option<std::process::Command> {
+    let mut cmd = std::process::Command::new("xdotool");
+    cmd.args(["getactivewindow", "getwindowclassname"]);
+    Some(cmd)
+}
+
+#[cfg(target_os = "macos")]
+fn command() -> Option<std::process::Command> {
+    let mut cmd = std::process::Command::new("osascript");
+    cmd.args([
+        "-e",
+        "tell application \"System Events\" to get name of first application process whose frontmost is true",
+    ]);
+    Some(cmd)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn command() -> Option<std::process::Command> {
+    None
+}
@@ -0,0 +1,153 @@
+// glossary.rs — `g-type glossary`: manage the custom vocabulary.
+// Terms live in config.toml, either in the top-level `glossary` list or in a
+// `[profiles.NAME]` section, and are woven into the transcription prompt
+// (see `prompt::transcription_prompt`) so Gemini spells them as written.
+
+use anyhow::{bail, Result};
+
+use crate::config::Config;
+
+const USAGE: &str = "Usage: g-type glossary [list | add TERM... | remove TERM...] [--profile NAME]";
+
+/// Handle `g-type glossary ...` (`args` excludes the `glossary` word).
+pub fn run_command(args: &[String]) -> Result<()> {
+    let (profile, args) = split_profile_flag(args)?;
+    let action = args.first().map(String::as_str);
+    let terms = args.get(1..).unwrap_or_default();
+
+    match action {
+        None | Some("list") => {
+            let config = crate::config::load()?;
+            print_list(&config);
+        }
+        Some("add") | Some("remove") if terms.is_empty() => bail!("{USAGE}"),
+        Some("add") => {
+            let added = crate::config::edit(|cfg| {
+                let list = match &profile {
+                    Some(name) => match cfg.profiles.get_mut(name) {
+                        Some(p) => &mut p.glossary,
+                        None => bail!("No profile named '{name}'"),
+                    },
+                    None => &mut cfg.glossary,
+                };
+                Ok(terms.iter().filter(|t| add(list, t)).count())
+            })?;
+            println!("  Added {added} term(s) to {}.", describe(&profile));
+        }
+        Some("remove") => {
+            let removed = crate::config::edit(|cfg| {
+                let list = match &profile {
+                    Some(name) => match cfg.profiles.get_mut(name) {
+                        Some(p) => &mut p.glossary,
+                        None => bail!("No profile named '{name}'"),
+                    },
+                    None => &mut cfg.glossary,
+                };
+                Ok(terms.iter().filter(|t| remove(list, t)).count())
+            })?;
+            println!("  Removed {removed} term(s) from {}.", describe(&profile));
+        }
+        Some(other) => bail!("Unknown glossary command '{other}'. {USAGE}"),
+    }
+    Ok(())
+}
+
+/// Add `term` unless it is blank or already listed (ignoring case).
+/// Returns true if the list changed.
+pub fn add(list: &mut Vec<String>, term: &str) -> bool {
+    let term = term.trim();
+    if term.is_empty() || list.iter().any(|t| t.eq_ignore_ascii_case(term)) {
+        return false;
+    }
+    list.push(term.to_string());
+    true
+}
+
+/// Remove `term` (ignoring case). Returns true if it was listed.
+pub fn remove(list: &mut Vec<String>, term: &str) -> bool {
+    let before = list.len();
+    list.retain(|t| !t.eq_ignore_ascii_case(term.trim()));
+    list.len() != before
+}
+
+/// Take `--profile NAME` out of the arguments.
+fn split_profile_flag(args: &[String]) -> Result<(Option<String>, Vec<String>)> {
+    let mut profile = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--profile" {
+            match iter.next() {
+                Some(name) => profile = Some(name.clone()),
+                None => bail!("--profile needs a name. {USAGE}"),
+            }
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((profile, rest))
+}
+
+fn describe(profile: &Option<String>) -> String {
+    match profile {
+        Some(name) => format!("profile '{name}'"),
+        None => "the glossary".into(),
+    }
+}
+
+fn print_list(config: &Config) {
+    println!();
+    println!("  \x1b[1m📖 Glossary\x1b[0m");
+    print_terms(&config.glossary);
+    for (name, profile) in &config.profiles {
+        let active = if *name == config.profile {
+            " (active)"
+        } else {
+            ""
+        };
+        println!();
+        println!("  \x1b[1mProfile {name}{active}\x1b[0m");
+        print_terms(&profile.glossary);
+    }
+    println!();
+}
+
+fn print_terms(terms: &[String]) {
+    if terms.is_empty() {
+        println!("     \x1b[2m(empty)\x1b[0m");
+    }
+    for term in terms {
+        println!("  • {term}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_remove() {
+        let mut list = vec!["Kubernetes".to_string()];
+        assert!(add(&mut list, " GitHub "));
+        assert!(!add(&mut list, "github"));
+        assert!(!add(&mut list, "  "));
+        assert_eq!(list, ["Kubernetes", "GitHub"]);
+
+        assert!(remove(&mut list, "KUBERNETES"));
+        assert!(!remove(&mut list, "Kubernetes"));
+        assert_eq!(list, ["GitHub"]);
+    }
+
+    #[test]
+    fn test_split_profile_flag() {
+        let args: Vec<String> = ["add", "--profile", "work", "Acme Cloud"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let (profile, rest) = split_profile_flag(&args).unwrap();
+        assert_eq!(profile.as_deref(), Some("work"));
+        assert_eq!(rest, ["add", "Acme Cloud"]);
+
+        assert!(split_profile_flag(&["--profile".to_string()]).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 // injector.rs — Text injection via keystroke emulation with clipboard fallback.
 // Always tries to type character by character using enigo for maximum compatibility.
 // If the text is extremely long or injection fails, it falls back to clipboard injection.
+// Also reads and replaces the focused app's selection for the edit hotkey.
 
 use anyhow::{Context, Result};
 use arboard::Clipboard;
@@ -15,6 +16,157 @@ const KEYSTROKE_DELAY_MS: u64 = 3;
 const PASTE_SETTLE_MS: u64 = 80;
 /// Threshold above which we consider text "too long" for keystrokes and use clipboard.
 const LONG_TEXT_THRESHOLD: usize = 500;
+/// Delay before typing to let the OS settle after hotkey release (ms).
+const SETTLE_MS: u64 = 80;
+/// How often and how long to wait for the copied selection to reach the clipboard.
+const COPY_POLL_MS: u64 = 30;
+const COPY_POLLS: u32 = 10;
+
+/// Destination for transcribed text. The daemon types into the focused app
+/// with `Keystrokes`; tests substitute a recorder.
+pub trait Injector: Send + Sync {
+    /// Type the first fragment of a dictation.
+    fn inject(&self, text: &str) -> Result<()>;
+    /// Type a follow-up fragment of streamed text.
+    fn inject_delta(&self, text: &str) -> Result<()>;
+    /// Press a key combination (spoken commands such as "select all").
+    fn press(&self, shortcut: &Shortcut) -> Result<()>;
+    /// The focused app's selected text; empty if nothing is selected.
+    fn selected_text(&self) -> Result<String>;
+    /// Replace the selection with `text`.
+    fn replace_selection(&self, text: &str) -> Result<()>;
+}
+
+/// Keystroke emulation with clipboard fallback.
+pub struct Keystrokes;
+
+impl Injector for Keystrokes {
+    fn inject(&self, text: &str) -> Result<()> {
+        inject(text)
+    }
+
+    fn inject_delta(&self, text: &str) -> Result<()> {
+        inject_delta(text)
+    }
+
+    fn press(&self, shortcut: &Shortcut) -> Result<()> {
+        press(shortcut)
+    }
+
+    fn selected_text(&self) -> Result<String> {
+        copy_selection()
+    }
+
+    fn replace_selection(&self, text: &str) -> Result<()> {
+        inject_clipboard(text)
+    }
+}
+
+/// A key combination: modifiers held while one key is clicked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    pub modifiers: Vec<Key>,
+    pub key: Key,
+    /// As written in config, e.g. "ctrl+z", for logs and listings.
+    pub label: String,
+}
+
+impl Shortcut {
+    /// Parse "ctrl+shift+z", "enter", ... or one of the named editing
+    /// actions `delete_word`, `select_all`, `undo` and `copy`, which map to
+    /// the platform's usual shortcut.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim().to_lowercase();
+        let primary = if cfg!(target_os = "macos") {
+            Key::Meta
+        } else {
+            Key::Control
+        };
+        let (modifiers, key) = match spec.as_str() {
+            "delete_word" if cfg!(target_os = "macos") => (vec![Key::Alt], Key::Backspace),
+            "delete_word" => (vec![Key::Control], Key::Backspace),
+            "select_all" => (vec![primary], Key::Unicode('a')),
+            "undo" => (vec![primary], Key::Unicode('z')),
+            "copy" => (vec![primary], Key::Unicode('c')),
+            _ => {
+                let parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+                let (key, modifiers) = parts.split_last().expect("split yields one part");
+                let modifiers = modifiers
+                    .iter()
+                    .map(|m| match *m {
+                        "ctrl" | "control" => Ok(Key::Control),
+                        "shift" => Ok(Key::Shift),
+                        "alt" | "option" => Ok(Key::Alt),
+                        "meta" | "super" | "cmd" | "win" => Ok(Key::Meta),
+                        other => anyhow::bail!("Unknown modifier '{other}' in '{spec}'"),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                (
+                    modifiers,
+                    parse_key(key).with_context(|| format!("in '{spec}'"))?,
+                )
+            }
+        };
+        Ok(Self {
+            modifiers,
+            key,
+            label: spec,
+        })
+    }
+}
+
+fn parse_key(name: &str) -> Result<Key> {
+    let key = match name {
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "space" => Key::Space,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "escape" | "esc" => Key::Escape,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c),
+                _ => anyhow::bail!("Unknown key '{name}'"),
+            }
+        }
+    };
+    Ok(key)
+}
+
+/// Press a key combination in the focused application.
+pub fn press(shortcut: &Shortcut) -> Result<()> {
+    let mut enigo = Enigo::new(&Settings::default())
+        .map_err(|e| anyhow::anyhow!("Failed to initialize enigo: {:?}", e))?;
+
+    // Let the app catch up with text typed just before.
+    thread::sleep(Duration::from_millis(SETTLE_MS));
+
+    for modifier in &shortcut.modifiers {
+        enigo
+            .key(*modifier, Direction::Press)
+            .map_err(|e| anyhow::anyhow!("Failed to press {:?}: {:?}", modifier, e))?;
+    }
+    let clicked = enigo.key(shortcut.key, Direction::Click);
+    // Always release the modifiers, even if the click failed.
+    for modifier in shortcut.modifiers.iter().rev() {
+        if let Err(e) = enigo.key(*modifier, Direction::Release) {
+            error!(?e, "Failed to release {:?}", modifier);
+        }
+    }
+    clicked.map_err(|e| anyhow::anyhow!("Failed to press '{}': {:?}", shortcut.label, e))?;
+
+    debug!(shortcut = %shortcut.label, "Shortcut sent");
+    Ok(())
+}
 
 /// Inject text into the currently focused application.
 ///
@@ -23,6 +175,18 @@ const LONG_TEXT_THRESHOLD: usize = 500;
 /// 2. Otherwise, try keystroke injection.
 /// 3. If keystroke injection fails, fallback to clipboard injection.
 pub fn inject(text: &str) -> Result<()> {
+    inject_with_settle(text, SETTLE_MS)
+}
+
+/// Inject a follow-up fragment of streamed text.
+///
+/// Same strategy as `inject`, minus the settle delay — the OS already
+/// settled before the first fragment was typed.
+pub fn inject_delta(text: &str) -> Result<()> {
+    inject_with_settle(text, 0)
+}
+
+fn inject_with_settle(text: &str, settle_ms: u64) -> Result<()> {
     if text.is_empty() {
         debug!("Empty text, nothing to inject");
         return Ok(());
@@ -36,7 +200,7 @@ pub fn inject(text: &str) -> Result<()> {
         return inject_clipboard(text);
     }
 
-    match inject_keystrokes(text) {
+    match inject_keystrokes(text, settle_ms) {
         Ok(_) => Ok(()),
         Err(e) => {
             warn!(%e, "Keystroke injection failed, falling back to clipboard");
@@ -46,12 +210,12 @@ pub fn inject(text: &str) -> Result<()> {
 }
 
 /// Type text character by character using enigo.
-fn inject_keystrokes(text: &str) -> Result<()> {
+fn inject_keystrokes(text: &str, settle_ms: u64) -> Result<()> {
     let mut enigo = Enigo::new(&Settings::default())
         .map_err(|e| anyhow::anyhow!("Failed to initialize enigo: {:?}", e))?;
 
     // Small delay before starting to let the OS settle after hotkey release
-    thread::sleep(Duration::from_millis(80));
+    thread::sleep(Duration::from_millis(settle_ms));
 
     let total = text.chars().count();
     let log_every = if total > 200 { total / 10 } else { usize::MAX };
@@ -74,13 +238,26 @@ fn inject_keystrokes(text: &str) -> Result<()> {
 }
 
 /// Inject text via clipboard: backup current clipboard, set new text, paste, restore.
-fn inject_clipboard(text: &str) -> Result<()> {
+/// Pasting over a selection replaces it.
+pub fn inject_clipboard(text: &str) -> Result<()> {
     debug!(len = text.len(), "Using clipboard injection");
 
     let mut clipboard = Clipboard::new().context("Failed to access system clipboard")?;
+    paste_text(&mut clipboard, text, paste_shortcut)?;
 
+    debug!("Clipboard injection complete");
+    Ok(())
+}
+
+/// Put `text` on the clipboard, `paste` it and put the previous contents
+/// back, whatever their format.
+fn paste_text(
+    clipboard: &mut Clipboard,
+    text: &str,
+    paste: impl FnOnce() -> Result<()>,
+) -> Result<()> {
     // Step 1: Backup current clipboard contents
-    let backup = clipboard.get_text().ok();
+    let backup = ClipboardBackup::save(clipboard);
     if backup.is_some() {
         debug!("Clipboard backup saved");
     }
@@ -90,36 +267,132 @@ fn inject_clipboard(text: &str) -> Result<()> {
         .set_text(text.to_string())
         .context("Failed to set clipboard text")?;
 
-    // Step 3: Simulate paste shortcut
-    paste_shortcut()?;
-
-    // Step 4: Wait for paste to settle
-    thread::sleep(Duration::from_millis(PASTE_SETTLE_MS));
+    // Step 3: Simulate paste shortcut, then wait for the paste to settle
+    let pasted = paste();
+    if pasted.is_ok() {
+        thread::sleep(Duration::from_millis(PASTE_SETTLE_MS));
+    }
 
-    // Step 5: Restore original clipboard
+    // Step 4: Restore original clipboard
     match backup {
-        Some(original) => {
-            // Re-acquire clipboard (it may have been released)
-            match Clipboard::new() {
-                Ok(mut cb) => {
-                    if let Err(e) = cb.set_text(original) {
-                        warn!(%e, "Failed to restore clipboard (non-fatal)");
-                    } else {
-                        debug!("Clipboard restored");
-                    }
-                }
-                Err(e) => {
-                    warn!(%e, "Failed to re-acquire clipboard for restore");
-                }
+        Some(original) => match original.restore(clipboard) {
+            Ok(()) => debug!("Clipboard restored"),
+            Err(e) => warn!(%e, "Failed to restore clipboard (non-fatal)"),
+        },
+        None => debug!("No previous clipboard content to restore"),
+    }
+    pasted
+}
+
+/// Copy the focused app's selection with the copy shortcut and read it from
+/// the clipboard, which is then put back as it was. Empty if nothing is
+/// selected.
+pub fn copy_selection() -> Result<String> {
+    let mut clipboard = Clipboard::new().context("Failed to access system clipboard")?;
+    let backup = ClipboardBackup::save(&mut clipboard);
+
+    // Cleared first, so an empty selection is not mistaken for the old
+    // contents. Contents that could not be saved are left alone: they are
+    // not text, so any text read after the copy is the selection.
+    if backup.is_some() {
+        clipboard.clear().context("Failed to clear clipboard")?;
+    }
+    press(&Shortcut::parse("copy")?)?;
+
+    let mut selection = String::new();
+    for _ in 0..COPY_POLLS {
+        thread::sleep(Duration::from_millis(COPY_POLL_MS));
+        if let Ok(text) = clipboard.get_text() {
+            if !text.is_empty() {
+                selection = text;
+                break;
             }
         }
-        None => {
-            debug!("No previous clipboard content to restore");
+    }
+
+    if let Some(original) = backup {
+        if let Err(e) = original.restore(&mut clipboard) {
+            warn!(%e, "Failed to restore clipboard (non-fatal)");
         }
     }
+    debug!(len = selection.len(), "Selection copied");
+    Ok(selection)
+}
 
-    debug!("Clipboard injection complete");
-    Ok(())
+/// Clipboard contents saved while the clipboard is borrowed to paste text or
+/// copy the selection.
+enum ClipboardBackup {
+    Files(Vec<std::path::PathBuf>),
+    Image(arboard::ImageData<'static>),
+    /// HTML with its plain-text alternative.
+    Html(String, Option<String>),
+    Text(String),
+}
+
+impl ClipboardBackup {
+    /// The clipboard in the richest format that can be put back, or `None`
+    /// if it is empty or holds something else.
+    fn save(clipboard: &mut Clipboard) -> Option<Self> {
+        let text = clipboard.get_text().ok();
+        if let Ok(files) = clipboard.get().file_list() {
+            if !files.is_empty() {
+                return Some(Self::Files(files));
+            }
+        }
+        if let Ok(image) = clipboard.get_image() {
+            return Some(Self::Image(image));
+        }
+        if let Ok(html) = clipboard.get().html() {
+            return Some(Self::Html(html, text));
+        }
+        text.map(Self::Text)
+    }
+
+    fn restore(self, clipboard: &mut Clipboard) -> Result<(), arboard::Error> {
+        match self {
+            Self::Files(files) => clipboard.set().file_list(&files),
+            Self::Image(image) => clipboard.set_image(image),
+            Self::Html(html, text) => clipboard.set().html(html, text),
+            Self::Text(text) => clipboard.set_text(text),
+        }
+    }
+}
+
+/// Put text on the clipboard without pasting it — for results that arrive
+/// after the original focus is gone. With `hold`, the call blocks on Linux
+/// until another app takes the clipboard over: a process about to exit would
+/// otherwise take the text with it.
+pub fn copy_to_clipboard(text: &str, hold: bool) -> Result<()> {
+    let mut clipboard = Clipboard::new().context("Failed to access system clipboard")?;
+
+    // On X11/Wayland the text is only available while its owner is alive, so
+    // keep the clipboard until another app takes ownership.
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        if hold {
+            return clipboard
+                .set()
+                .wait()
+                .text(text.to_string())
+                .context("Failed to set clipboard text");
+        }
+        let text = text.to_string();
+        thread::spawn(move || {
+            if let Err(e) = clipboard.set().wait().text(text) {
+                warn!(%e, "Failed to set clipboard text");
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = hold;
+        clipboard
+            .set_text(text.to_string())
+            .context("Failed to set clipboard text")
+    }
 }
 
 /// Send the OS-appropriate paste shortcut (CTRL+V on Linux/Windows, CMD+V on macOS).
@@ -170,6 +443,53 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_paste_restores_image_clipboard() {
+        // Needs a clipboard; headless CI has none.
+        let Ok(mut clipboard) = Clipboard::new() else {
+            return;
+        };
+        let image = arboard::ImageData {
+            width: 1,
+            height: 1,
+            bytes: vec![255, 0, 0, 255].into(),
+        };
+        if clipboard.set_image(image.clone()).is_err() {
+            return;
+        }
+
+        let mut pasted = None;
+        paste_text(&mut clipboard, "ciao", || {
+            pasted = Some(Clipboard::new()?.get_text()?);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(pasted.as_deref(), Some("ciao"));
+        assert_eq!(clipboard.get_image().unwrap().bytes, image.bytes);
+    }
+
+    #[test]
+    fn test_empty_delta() {
+        assert!(inject_delta("").is_ok());
+    }
+
+    #[test]
+    fn test_parse_shortcut() {
+        let undo = Shortcut::parse("Ctrl+Shift+Z").unwrap();
+        assert_eq!(undo.modifiers, [Key::Control, Key::Shift]);
+        assert_eq!(undo.key, Key::Unicode('z'));
+        assert_eq!(undo.label, "ctrl+shift+z");
+        assert_eq!(Shortcut::parse("enter").unwrap().key, Key::Return);
+        assert_eq!(
+            Shortcut::parse("select_all").unwrap().key,
+            Key::Unicode('a')
+        );
+
+        assert!(Shortcut::parse("hyper+a").is_err());
+        assert!(Shortcut::parse("ctrl+banana").is_err());
+        assert!(Shortcut::parse("").is_err());
+    }
+
     #[test]
     fn test_long_text_threshold() {
         // Verify the constant is sane
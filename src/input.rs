@@ -1,6 +1,7 @@
 // input.rs — Global keyboard hook using rdev.
 // Runs on a dedicated OS thread (rdev::listen is blocking).
-// Detects a configurable hotkey combo and sends signals via tokio mpsc.
+// Detects configurable hotkey combos and sends signals via tokio mpsc; each
+// Start carries the index of the hotkey that was pressed.
 
 use anyhow::{Context, Result};
 use rdev::{Event, EventType, Key};
@@ -14,8 +15,8 @@ use tracing::{debug, error, info};
 /// Signals sent from the input thread to the main event loop.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputSignal {
-    /// Hotkey pressed — start recording.
-    Start,
+    /// Hotkey pressed — start recording. Index into the listener's hotkeys.
+    Start(usize),
     /// Hotkey released — stop recording.
     Stop,
 }
@@ -193,26 +194,26 @@ fn str_to_rdev_key(name: &str) -> Result<Key> {
 struct HookState {
     /// Currently held modifier keys.
     held_modifiers: HashSet<Modifier>,
-    /// Whether the trigger key is currently held.
-    trigger_held: bool,
-    /// Whether we are currently recording.
-    recording: bool,
+    /// Currently held trigger keys (of any hotkey).
+    held_triggers: HashSet<Key>,
+    /// Index of the hotkey we are recording for, if any.
+    recording: Option<usize>,
     /// Last trigger time for debouncing.
     last_trigger: Instant,
-    /// The hotkey definition.
-    hotkey: Hotkey,
+    /// The hotkey definitions.
+    hotkeys: Vec<Hotkey>,
     /// Channel sender.
     tx: InputTx,
 }
 
 impl HookState {
-    fn new(tx: InputTx, hotkey: Hotkey) -> Self {
+    fn new(tx: InputTx, hotkeys: Vec<Hotkey>) -> Self {
         Self {
             held_modifiers: HashSet::new(),
-            trigger_held: false,
-            recording: false,
+            held_triggers: HashSet::new(),
+            recording: None,
             last_trigger: Instant::now() - std::time::Duration::from_secs(10),
-            hotkey,
+            hotkeys,
             tx,
         }
     }
@@ -223,8 +224,8 @@ impl HookState {
                 if let Some(m) = key_to_modifier(key) {
                     self.held_modifiers.insert(m);
                 }
-                if key == self.hotkey.trigger {
-                    self.trigger_held = true;
+                if self.hotkeys.iter().any(|h| h.trigger == key) {
+                    self.held_triggers.insert(key);
                 }
                 self.check_combo();
             }
@@ -232,51 +233,60 @@ impl HookState {
                 if let Some(m) = key_to_modifier(key) {
                     self.held_modifiers.remove(&m);
                 }
-                if key == self.hotkey.trigger {
-                    self.trigger_held = false;
-                }
+                self.held_triggers.remove(&key);
                 self.check_release();
             }
             _ => {}
         }
     }
 
+    /// All of the hotkey's keys are down.
+    fn is_held(&self, hotkey: &Hotkey) -> bool {
+        self.held_triggers.contains(&hotkey.trigger)
+            && hotkey
+                .modifiers
+                .iter()
+                .all(|m| self.held_modifiers.contains(m))
+    }
+
     fn check_combo(&mut self) {
-        // All required modifiers must be held AND the trigger key
-        let all_mods = self
-            .hotkey
-            .modifiers
+        if self.recording.is_some() {
+            return;
+        }
+        // With overlapping combos (ctrl+space, ctrl+shift+space) the one
+        // with the most modifiers wins.
+        let Some((index, hotkey)) = self
+            .hotkeys
             .iter()
-            .all(|m| self.held_modifiers.contains(m));
-        if all_mods && self.trigger_held && !self.recording {
-            let now = Instant::now();
-            if now.duration_since(self.last_trigger).as_millis() < DEBOUNCE_MS as u128 {
-                debug!(hotkey = %self.hotkey.label, "Hotkey debounced");
-                return;
-            }
-            self.last_trigger = now;
-            self.recording = true;
-            info!(hotkey = %self.hotkey.label, "Hotkey pressed — START recording");
-            if self.tx.blocking_send(InputSignal::Start).is_err() {
-                error!("Input channel closed, cannot send Start signal");
-            }
+            .enumerate()
+            .filter(|(_, h)| self.is_held(h))
+            .max_by_key(|(_, h)| h.modifiers.len())
+        else {
+            return;
+        };
+        let now = Instant::now();
+        if now.duration_since(self.last_trigger).as_millis() < DEBOUNCE_MS as u128 {
+            debug!(hotkey = %hotkey.label, "Hotkey debounced");
+            return;
+        }
+        info!(hotkey = %hotkey.label, "Hotkey pressed — START recording");
+        self.last_trigger = now;
+        self.recording = Some(index);
+        if self.tx.blocking_send(InputSignal::Start(index)).is_err() {
+            error!("Input channel closed, cannot send Start signal");
         }
     }
 
     fn check_release(&mut self) {
-        if self.recording {
-            // Stop when trigger is released OR any required modifier is released
-            let all_mods = self
-                .hotkey
-                .modifiers
-                .iter()
-                .all(|m| self.held_modifiers.contains(m));
-            if !self.trigger_held || !all_mods {
-                self.recording = false;
-                debug!(hotkey = %self.hotkey.label, "Hotkey released");
-                if self.tx.blocking_send(InputSignal::Stop).is_err() {
-                    error!("Input channel closed, cannot send Stop signal");
-                }
+        // Stop when trigger is released OR any required modifier is released
+        let Some(index) = self.recording else {
+            return;
+        };
+        if !self.is_held(&self.hotkeys[index]) {
+            self.recording = None;
+            debug!(hotkey = %self.hotkeys[index].label, "Hotkey released");
+            if self.tx.blocking_send(InputSignal::Stop).is_err() {
+                error!("Input channel closed, cannot send Stop signal");
             }
         }
     }
@@ -299,18 +309,23 @@ fn key_to_modifier(key: Key) -> Option<Modifier> {
 /// or the process exits.
 ///
 /// `tx` — channel for sending Start/Stop signals to the async event loop.
-/// `hotkey` — the parsed hotkey combo to listen for.
+/// `hotkeys` — the parsed hotkey combos to listen for; `Start` carries the
+/// index of the one pressed.
 pub fn spawn_listener(
     tx: InputTx,
     shutdown: Arc<AtomicBool>,
-    hotkey: Hotkey,
+    hotkeys: Vec<Hotkey>,
 ) -> Result<std::thread::JoinHandle<()>> {
-    let label = hotkey.label.clone();
+    let label = hotkeys
+        .iter()
+        .map(|h| h.label.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
     let handle = std::thread::Builder::new()
         .name("g-type-input".into())
         .spawn(move || {
-            debug!(hotkey = %label, "Global keyboard listener started");
-            let state = Arc::new(std::sync::Mutex::new(HookState::new(tx, hotkey)));
+            debug!(hotkeys = %label, "Global keyboard listener started");
+            let state = Arc::new(std::sync::Mutex::new(HookState::new(tx, hotkeys)));
 
             let callback = move |event: Event| {
                 if shutdown.load(Ordering::Relaxed) {
@@ -385,7 +400,7 @@ mod tests {
 
             let hotkey = parse_hotkey("ctrl+shift+space").unwrap();
             let (tx, mut rx) = mpsc::channel(16);
-            let mut state = HookState::new(tx, hotkey);
+            let mut state = HookState::new(tx, vec![hotkey]);
 
             // Press Ctrl
             state.handle_event(&Event {
@@ -394,7 +409,7 @@ mod tests {
                 event_type: EventType::KeyPress(Key::ControlLeft),
             });
             assert!(state.held_modifiers.contains(&Modifier::Ctrl));
-            assert!(!state.recording);
+            assert!(state.recording.is_none());
 
             // Press Shift
             state.handle_event(&Event {
@@ -402,7 +417,7 @@ mod tests {
                 name: None,
                 event_type: EventType::KeyPress(Key::ShiftLeft),
             });
-            assert!(!state.recording);
+            assert!(state.recording.is_none());
 
             // Press Space
             state.handle_event(&Event {
@@ -410,10 +425,10 @@ mod tests {
                 name: None,
                 event_type: EventType::KeyPress(Key::Space),
             });
-            assert!(state.recording);
+            assert_eq!(state.recording, Some(0));
 
             let signal = rt.block_on(async { rx.recv().await });
-            assert_eq!(signal, Some(InputSignal::Start));
+            assert_eq!(signal, Some(InputSignal::Start(0)));
 
             // Release Space
             state.handle_event(&Event {
@@ -421,7 +436,7 @@ mod tests {
                 name: None,
                 event_type: EventType::KeyRelease(Key::Space),
             });
-            assert!(!state.recording);
+            assert!(state.recording.is_none());
 
             let signal = rt.block_on(async { rx.recv().await });
             assert_eq!(signal, Some(InputSignal::Stop));
@@ -0,0 +1,505 @@
+// live.rs — Gemini Live API realtime mode (BidiGenerateContent over WebSocket).
+// The session opens on hotkey press and each 100 ms chunk from the Downsampler
+// is pushed while the user is still speaking, so by the time the hotkey is
+// released the audio is already on the server and only the reply remains.
+//
+// Voice activity detection is disabled: we send explicit activityStart /
+// activityEnd so the model answers exactly once, after release.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, warn};
+
+use crate::audio::AudioChunk;
+use crate::config::Config;
+use crate::streaming::DeltaTrimmer;
+use crate::tracking::{Latency, TokenUsage};
+use crate::transcriber::{self, TranscribeContext, Transcription};
+
+/// What the session task hands back once the turn is complete.
+#[derive(Debug, Default)]
+struct SessionOutcome {
+    /// Model reply (follows the transcription prompt).
+    model_text: String,
+    /// Raw input transcription, used if the model reply is empty.
+    input_text: String,
+    usage: TokenUsage,
+}
+
+/// A live transcription session bound to one recording.
+pub struct LiveSession {
+    audio_tx: mpsc::UnboundedSender<AudioChunk>,
+    text_rx: mpsc::UnboundedReceiver<String>,
+    handle: JoinHandle<Result<SessionOutcome>>,
+    model: String,
+    reply_timeout: Duration,
+}
+
+impl LiveSession {
+    /// Open the session in the background. Audio pushed before the socket is
+    /// ready is buffered in the channel, so nothing is lost and recording
+    /// never waits on the network.
+    pub fn start(config: &Config, ctx: &TranscribeContext) -> Self {
+        let (audio_tx, audio_rx) = mpsc::unbounded_channel();
+        let (text_tx, text_rx) = mpsc::unbounded_channel();
+
+        let url = config.live.url.clone();
+        let api_key = config.api_key.clone();
+        let setup = setup_message(&config.live.model, &ctx.prompt);
+        let handle = tokio::spawn(run_session(url, api_key, setup, audio_rx, text_tx));
+
+        Self {
+            audio_tx,
+            text_rx,
+            handle,
+            model: config.live.model.clone(),
+            reply_timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        }
+    }
+
+    /// Abandon the session (e.g. nothing was recorded).
+    pub fn cancel(self) {
+        self.handle.abort();
+    }
+
+    /// Sender for the audio collector. Safe to use from non-tokio threads.
+    pub fn audio_sender(&self) -> mpsc::UnboundedSender<AudioChunk> {
+        self.audio_tx.clone()
+    }
+
+    /// End the audio stream and wait for the reply, forwarding text to
+    /// `deltas` as it arrives. A failure before any text was typed (connect
+    /// error, timeout) can safely fall back to the batch path; one after
+    /// carries the typed text, see `transcriber::partial`.
+    pub async fn finish(self, deltas: &mpsc::UnboundedSender<String>) -> Result<Transcription> {
+        let LiveSession {
+            audio_tx,
+            mut text_rx,
+            handle,
+            model,
+            reply_timeout,
+        } = self;
+        // All senders dropped → the session task sends activityEnd.
+        drop(audio_tx);
+
+        let mut trimmer = DeltaTrimmer::default();
+        let mut typed = String::new();
+        let result = tokio::time::timeout(reply_timeout, async {
+            while let Some(delta) = text_rx.recv().await {
+                let ready = trimmer.push(&delta);
+                if !ready.is_empty() {
+                    typed.push_str(&ready);
+                    let _ = deltas.send(ready);
+                }
+            }
+            handle.await.context("Live session task panicked")?
+        })
+        .await
+        .context("Timed out waiting for Gemini Live reply")
+        .and_then(|r| r);
+
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            // Part of the reply is already typed: the caller keeps it rather
+            // than re-transcribe and type it twice. Usage arrives with the
+            // last message, so none is known.
+            Err(e) if !typed.is_empty() => {
+                warn!(%e, "Gemini Live reply cut short");
+                let sent = Transcription {
+                    text: typed,
+                    model,
+                    ..Transcription::default()
+                };
+                return Err(transcriber::with_partial(
+                    e.context("Gemini Live reply cut short"),
+                    sent,
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let text = if outcome.model_text.trim().is_empty() {
+            outcome.input_text.trim().to_string()
+        } else {
+            outcome.model_text.trim().to_string()
+        };
+        if typed.is_empty() && !text.is_empty() {
+            let _ = deltas.send(text.clone());
+        }
+
+        Ok(Transcription {
+            text,
+            usage: outcome.usage,
+            model,
+            detected_language: None,
+            // Audio was streamed while recording; nothing left to encode or upload.
+            latency: Latency::default(),
+        })
+    }
+}
+
+/// Build the initial `setup` message.
+fn setup_message(model: &str, prompt: &str) -> Value {
+    let model = if model.starts_with("models/") {
+        model.to_string()
+    } else {
+        format!("models/{model}")
+    };
+    json!({
+        "setup": {
+            "model": model,
+            "generationConfig": {
+                "responseModalities": ["TEXT"],
+                "temperature": 0.0
+            },
+            "systemInstruction": {
+                "parts": [{ "text": prompt }]
+            },
+            "realtimeInputConfig": {
+                "automaticActivityDetection": { "disabled": true }
+            },
+            "inputAudioTranscription": {}
+        }
+    })
+}
+
+/// Wrap one PCM chunk as a `realtimeInput` message.
+fn audio_message(chunk: &[i16]) -> Value {
+    let mut bytes = Vec::with_capacity(chunk.len() * 2);
+    for s in chunk {
+        bytes.extend_from_slice(&s.to_le_bytes());
+    }
+    json!({
+        "realtimeInput": {
+            "audio": {
+                "data": BASE64.encode(&bytes),
+                "mimeType": "audio/pcm;rate=16000"
+            }
+        }
+    })
+}
+
+/// Decode a server frame (the Live API sends JSON in text or binary frames).
+fn parse_frame(msg: Message) -> Option<Value> {
+    match msg {
+        Message::Text(t) => serde_json::from_str(t.as_str()).ok(),
+        Message::Binary(b) => serde_json::from_slice(&b).ok(),
+        _ => None,
+    }
+}
+
+/// Apply one server message to the outcome. Returns true on `turnComplete`.
+fn handle_server_message(
+    msg: &Value,
+    outcome: &mut SessionOutcome,
+    text_tx: &mpsc::UnboundedSender<String>,
+) -> bool {
+    if let Some(meta) = msg.get("usageMetadata") {
+        let field = |name: &str| meta.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+        outcome.usage = TokenUsage {
+            prompt_tokens: field("promptTokenCount"),
+            candidates_tokens: field("responseTokenCount"),
+            thoughts_tokens: field("thoughtsTokenCount"),
+            total_tokens: field("totalTokenCount"),
+        };
+    }
+
+    let Some(content) = msg.get("serverContent") else {
+        return false;
+    };
+    if let Some(t) = content
+        .pointer("/inputTranscription/text")
+        .and_then(|t| t.as_str())
+    {
+        outcome.input_text.push_str(t);
+    }
+    if let Some(parts) = content
+        .pointer("/modelTurn/parts")
+        .and_then(|p| p.as_array())
+    {
+        for part in parts {
+            if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                outcome.model_text.push_str(t);
+                let _ = text_tx.send(t.to_string());
+            }
+        }
+    }
+    content
+        .get("turnComplete")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Session task: connect, stream audio until the channel closes, then read
+/// the reply until `turnComplete`.
+async fn run_session(
+    url: String,
+    api_key: String,
+    setup: Value,
+    mut audio_rx: mpsc::UnboundedReceiver<AudioChunk>,
+    text_tx: mpsc::UnboundedSender<String>,
+) -> Result<SessionOutcome> {
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .context("Invalid Gemini Live URL")?;
+    // API key goes in a header, never in the URL (same rule as the REST path).
+    if !api_key.is_empty() {
+        request.headers_mut().insert(
+            "x-goog-api-key",
+            api_key.parse().context("Invalid API key header")?,
+        );
+    }
+
+    let (ws, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .context("Failed to connect to Gemini Live")?;
+    let (mut sink, mut stream) = ws.split();
+    debug!("Gemini Live socket open");
+
+    send_json(&mut sink, &setup).await?;
+    loop {
+        let msg = stream
+            .next()
+            .await
+            .context("Gemini Live closed during setup")?
+            .context("Gemini Live socket error")?;
+        if let Some(v) = parse_frame(msg) {
+            if v.get("setupComplete").is_some() {
+                break;
+            }
+            if let Some(err) = v.get("error") {
+                bail!("Gemini Live setup rejected: {err}");
+            }
+        }
+    }
+    send_json(&mut sink, &json!({"realtimeInput": {"activityStart": {}}})).await?;
+
+    let mut outcome = SessionOutcome::default();
+    let mut chunks_sent = 0usize;
+
+    // Phase 1: push audio while recording; pick up early input transcription.
+    loop {
+        tokio::select! {
+            chunk = audio_rx.recv() => match chunk {
+                Some(chunk) => {
+                    send_json(&mut sink, &audio_message(&chunk)).await?;
+                    chunks_sent += 1;
+                }
+                None => break,
+            },
+            msg = stream.next() => match msg {
+                Some(Ok(m)) => {
+                    if let Some(v) = parse_frame(m) {
+                        handle_server_message(&v, &mut outcome, &text_tx);
+                    }
+                }
+                Some(Err(e)) => return Err(e).context("Gemini Live socket error"),
+                None => bail!("Gemini Live closed the connection while recording"),
+            },
+        }
+    }
+
+    debug!(chunks_sent, "Audio stream ended, waiting for Live reply");
+    send_json(&mut sink, &json!({"realtimeInput": {"activityEnd": {}}})).await?;
+
+    // Phase 2: read the reply.
+    while let Some(msg) = stream.next().await {
+        let msg = msg.context("Gemini Live socket error")?;
+        if let Some(v) = parse_frame(msg) {
+            if handle_server_message(&v, &mut outcome, &text_tx) {
+                let _ = sink.send(Message::Close(None)).await;
+                return Ok(outcome);
+            }
+        }
+    }
+
+    bail!("Gemini Live closed before turnComplete")
+}
+
+async fn send_json<S>(sink: &mut S, value: &Value) -> Result<()>
+where
+    S: SinkExt<Message> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    sink.send(Message::Text(value.to_string().into()))
+        .await
+        .context("Failed to send to Gemini Live")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::WsStub;
+
+    #[test]
+    fn test_setup_message() {
+        let msg = setup_message("gemini-live-2.5-flash-preview", "Transcribe.");
+        assert_eq!(
+            msg["setup"]["model"],
+            "models/gemini-live-2.5-flash-preview"
+        );
+        assert_eq!(
+            msg["setup"]["realtimeInputConfig"]["automaticActivityDetection"]["disabled"],
+            true
+        );
+        assert_eq!(
+            msg["setup"]["systemInstruction"]["parts"][0]["text"],
+            "Transcribe."
+        );
+    }
+
+    #[test]
+    fn test_audio_message_pcm_le() {
+        let msg = audio_message(&[1, -1]);
+        let data = msg["realtimeInput"]["audio"]["data"].as_str().unwrap();
+        assert_eq!(BASE64.decode(data).unwrap(), vec![1, 0, 0xff, 0xff]);
+        assert_eq!(
+            msg["realtimeInput"]["audio"]["mimeType"],
+            "audio/pcm;rate=16000"
+        );
+    }
+
+    /// Live API stand-in: acknowledges setup, answers after activityEnd.
+    fn stub_handler(msg: &Value) -> Option<Vec<Value>> {
+        if msg.get("setup").is_some() {
+            Some(vec![json!({"setupComplete": {}})])
+        } else if msg.pointer("/realtimeInput/activityEnd").is_some() {
+            Some(vec![
+                json!({"serverContent": {"inputTranscription": {"text": "ciao mondo"}}}),
+                json!({"serverContent": {"modelTurn": {"parts": [{"text": "Ciao "}]}}}),
+                json!({"serverContent": {"modelTurn": {"parts": [{"text": "mondo."}]}}}),
+                json!({"serverContent": {"turnComplete": true},
+                       "usageMetadata": {"promptTokenCount": 75, "responseTokenCount": 4}}),
+            ])
+        } else {
+            Some(Vec::new())
+        }
+    }
+
+    fn live_config(url: &str) -> Config {
+        let mut cfg = Config {
+            api_key: "AIzaSyTest".into(),
+            ..Config::default()
+        };
+        cfg.live.enabled = true;
+        cfg.live.url = url.into();
+        cfg
+    }
+
+    fn ctx() -> TranscribeContext {
+        TranscribeContext {
+            language: "it".into(),
+            prompt: "Transcribe.".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_live_session_against_stub() {
+        let stub = WsStub::start(stub_handler).await;
+        let url = stub.url.clone();
+        let session = LiveSession::start(&live_config(&url), &ctx());
+
+        // Chunks pushed before the socket is up must be buffered, not lost.
+        let tx = session.audio_sender();
+        for _ in 0..5 {
+            tx.send(vec![0i16; 1600]).unwrap();
+        }
+        drop(tx);
+
+        let (delta_tx, mut delta_rx) = mpsc::unbounded_channel();
+        let result = session.finish(&delta_tx).await.unwrap();
+        drop(delta_tx);
+
+        assert_eq!(result.text, "Ciao mondo.");
+        assert_eq!(result.usage.prompt_tokens, 75);
+        assert_eq!(result.usage.candidates_tokens, 4);
+        let received = stub.messages();
+        let audio = received
+            .iter()
+            .filter(|m| m.pointer("/realtimeInput/audio").is_some())
+            .count();
+        assert_eq!(audio, 5);
+        assert!(received[1]
+            .pointer("/realtimeInput/activityStart")
+            .is_some());
+
+        let mut typed = String::new();
+        while let Some(d) = delta_rx.recv().await {
+            typed.push_str(&d);
+        }
+        assert_eq!(typed, "Ciao mondo.");
+    }
+
+    #[tokio::test]
+    async fn test_live_reply_cut_short_keeps_typed_text() {
+        // Starts the reply, then never finishes the turn.
+        let stub = WsStub::start(|msg| {
+            if msg.get("setup").is_some() {
+                Some(vec![json!({"setupComplete": {}})])
+            } else if msg.pointer("/realtimeInput/activityEnd").is_some() {
+                Some(vec![
+                    json!({"serverContent": {"modelTurn": {"parts": [{"text": "Ciao mondo, "}]}}}),
+                ])
+            } else {
+                Some(Vec::new())
+            }
+        })
+        .await;
+        let config = Config {
+            timeout_secs: 1,
+            ..live_config(&stub.url)
+        };
+        let session = LiveSession::start(&config, &ctx());
+        session.audio_sender().send(vec![0i16; 1600]).unwrap();
+
+        let (delta_tx, _rx) = mpsc::unbounded_channel();
+        let err = session.finish(&delta_tx).await.unwrap_err();
+        let sent = transcriber::partial(&err).unwrap();
+        assert_eq!(sent.text, "Ciao mondo,");
+        assert_eq!(sent.model, config.live.model);
+    }
+
+    #[tokio::test]
+    async fn test_live_closed_mid_reply_keeps_typed_text() {
+        // Starts the reply, then closes the socket without `turnComplete`.
+        let stub = WsStub::start(|msg| {
+            if msg.get("setup").is_some() {
+                Some(vec![json!({"setupComplete": {}})])
+            } else if msg.pointer("/realtimeInput/activityEnd").is_some() {
+                Some(vec![
+                    json!({"serverContent": {"modelTurn": {"parts": [{"text": "Ciao mondo, "}]}}}),
+                    Value::Null,
+                ])
+            } else {
+                Some(Vec::new())
+            }
+        })
+        .await;
+        let config = live_config(&stub.url);
+        let session = LiveSession::start(&config, &ctx());
+        session.audio_sender().send(vec![0i16; 1600]).unwrap();
+
+        let (delta_tx, _rx) = mpsc::unbounded_channel();
+        let err = session.finish(&delta_tx).await.unwrap_err();
+        assert!(format!("{err:#}").contains("closed before turnComplete"));
+        let sent = transcriber::partial(&err).unwrap();
+        assert_eq!(sent.text, "Ciao mondo,");
+    }
+
+    #[tokio::test]
+    async fn test_live_session_connect_failure() {
+        // Nothing listens on port 1.
+        let session = LiveSession::start(&live_config("ws://127.0.0.1:1"), &ctx());
+        let (delta_tx, _rx) = mpsc::unbounded_channel();
+        assert!(session.finish(&delta_tx).await.is_err());
+    }
+}
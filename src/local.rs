@@ -0,0 +1,142 @@
+// local.rs — Fully offline on-device transcription via whisper.cpp (whisper-rs).
+// Compiled only with `--features local-whisper`. The ggml model is loaded once
+// at startup; inference runs on tokio's blocking pool so the FSM stays responsive.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::debug;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::config::Config;
+use crate::tracking::{Latency, TokenUsage};
+use crate::transcriber::{TranscribeContext, Transcriber, Transcription};
+
+/// On-device Whisper transcriber. No network, no per-token cost.
+pub struct LocalTranscriber {
+    ctx: Arc<WhisperContext>,
+    threads: i32,
+    /// `local/<model file stem>` — recorded in usage history at zero cost.
+    model: String,
+}
+
+impl LocalTranscriber {
+    pub fn new(config: &Config) -> Result<Self> {
+        let model_file = config.local.model_file();
+        let path = model_file.as_path();
+        if config.local.model_path.is_empty() {
+            bail!("backend = \"local\" requires [local] model_path (a ggml Whisper model file)");
+        }
+        if !path.exists() {
+            bail!("Local Whisper model not found at {}", path.display());
+        }
+
+        let ctx = WhisperContext::new_with_params(path, WhisperContextParameters::default())
+            .map_err(|e| anyhow::anyhow!("Failed to load Whisper model: {e}"))
+            .with_context(|| format!("Model file: {}", path.display()))?;
+
+        let threads = match config.local.threads {
+            0 => std::thread::available_parallelism()
+                .map(|n| n.get() as i32)
+                .unwrap_or(4),
+            n => n as i32,
+        };
+
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "whisper".into());
+
+        debug!(model = %path.display(), threads, "Local Whisper model loaded");
+
+        Ok(Self {
+            ctx: Arc::new(ctx),
+            threads,
+            model: format!("local/{stem}"),
+        })
+    }
+}
+
+#[async_trait]
+impl Transcriber for LocalTranscriber {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn transcribe(&self, samples: &[i16], ctx: &TranscribeContext) -> Result<Transcription> {
+        if samples.is_empty() {
+            bail!("No audio samples to transcribe");
+        }
+
+        let audio = samples_to_f32(samples);
+        let whisper = self.ctx.clone();
+        let threads = self.threads;
+        let language = match ctx.language.as_str() {
+            "" => "auto".to_string(),
+            code => code.to_string(),
+        };
+
+        let text = tokio::task::spawn_blocking(move || -> Result<String> {
+            let mut state = whisper
+                .create_state()
+                .map_err(|e| anyhow::anyhow!("Failed to create Whisper state: {e}"))?;
+
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_n_threads(threads);
+            params.set_language(Some(&language));
+            params.set_print_special(false);
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_timestamps(false);
+
+            state
+                .full(params, &audio)
+                .map_err(|e| anyhow::anyhow!("Whisper inference failed: {e}"))?;
+
+            let mut text = String::new();
+            for segment in state.as_iter() {
+                if let Ok(s) = segment.to_str_lossy() {
+                    text.push_str(&s);
+                }
+            }
+            Ok(text.trim().to_string())
+        })
+        .await
+        .context("Whisper inference task panicked")??;
+
+        Ok(Transcription {
+            text,
+            usage: TokenUsage::default(),
+            model: self.model.clone(),
+            detected_language: None,
+            latency: Latency::default(),
+        })
+    }
+}
+
+/// Convert PCM i16 samples to the normalized f32 range whisper.cpp expects.
+fn samples_to_f32(samples: &[i16]) -> Vec<f32> {
+    samples.iter().map(|&s| s as f32 / 32768.0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_to_f32_range() {
+        let out = samples_to_f32(&[0, i16::MAX, i16::MIN]);
+        assert_eq!(out[0], 0.0);
+        assert!(out[1] > 0.99 && out[1] < 1.0);
+        assert_eq!(out[2], -1.0);
+    }
+
+    #[test]
+    fn test_missing_model_path() {
+        let cfg = Config {
+            backend: "local".into(),
+            ..Config::default()
+        };
+        assert!(LocalTranscriber::new(&cfg).is_err());
+    }
+}
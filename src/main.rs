@@ -4,16 +4,41 @@
 //   g-type setup    → interactive config wizard
 //   g-type set-key  → update API key without full setup
 //   g-type config   → print config file path
+//   g-type queue    → list, retry or drop dictations that failed to transcribe
+//   g-type glossary → list, add or remove custom vocabulary terms
+//   g-type prompt   → preview the rendered transcription prompt
+//   g-type commands → list the spoken commands for a language
 
 mod app;
 mod audio;
 mod audio_feedback;
+mod chunking;
+mod code;
+mod commands;
 mod config;
+mod encoder;
+mod error;
+mod files;
+mod focus;
+mod glossary;
 mod injector;
 mod input;
+mod live;
+#[cfg(feature = "local-whisper")]
+mod local;
 mod network;
+mod notify;
+mod openai;
+mod prompt;
+mod rewrite;
+mod spool;
+mod streaming;
+#[cfg(test)]
+mod test_support;
 mod tracking;
+mod transcriber;
 mod upgrade;
+mod vertex;
 
 use anyhow::Result;
 use tracing::{debug, error, info};
@@ -27,7 +52,11 @@ fn print_usage() {
     eprintln!("  setup         Run interactive setup wizard");
     eprintln!("  set-key       Update your Gemini API key");
     eprintln!("  config        Show config file location");
-    eprintln!("  stats         Show cost & usage statistics");
+    eprintln!("  stats         Show cost & usage statistics (--latency: p50/p95 per model)");
+    eprintln!("  queue         List failed dictations (queue retry [ID] | queue drop ID|all)");
+    eprintln!("  glossary      Custom vocabulary (glossary add|remove TERM... [--profile NAME])");
+    eprintln!("  prompt        Show the transcription prompt (prompt preview [NAME] [--app APP])");
+    eprintln!("  commands      List spoken commands (commands [LANG])");
     eprintln!("  upgrade       Self-update to latest release");
     eprintln!("  version       Show current version");
     eprintln!("  test-audio    Test microphone capture (3 seconds)");
@@ -66,6 +95,13 @@ async fn main() -> Result<()> {
             }
             return Ok(());
         }
+        Some("stats") if args.get(2).is_some_and(|a| a == "--latency") => {
+            if let Err(e) = tracking::print_latency() {
+                eprintln!("\n❌ Failed to load stats: {e}\n");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
         Some("stats") => {
             // Load config for currency preference (fallback to USD if no config).
             let currency = config::config_path()
@@ -81,6 +117,34 @@ async fn main() -> Result<()> {
             }
             return Ok(());
         }
+        Some("queue") => {
+            if let Err(e) = spool::run_command(&args[2..]).await {
+                eprintln!("\n❌ {e:#}\n");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("glossary") => {
+            if let Err(e) = glossary::run_command(&args[2..]) {
+                eprintln!("\n❌ {e:#}\n");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("prompt") => {
+            if let Err(e) = prompt::run_command(&args[2..]) {
+                eprintln!("\n❌ {e:#}\n");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("commands") => {
+            if let Err(e) = commands::run_command(&args[2..]) {
+                eprintln!("\n❌ {e:#}\n");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
         Some("upgrade") | Some("update") => {
             if let Err(e) = upgrade::run_upgrade() {
                 eprintln!("\n❌ Upgrade failed: {e}\n");
@@ -197,6 +261,7 @@ async fn main() -> Result<()> {
     };
 
     debug!(
+        backend = %cfg.backend,
         model = %cfg.model,
         hotkey = %cfg.hotkey,
         "Configuration loaded"
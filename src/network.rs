@@ -1,213 +1,944 @@
 // network.rs — HTTP client for Gemini generateContent REST API.
-// Records audio → encodes as WAV base64 → sends to Gemini → returns transcription text.
-// No WebSocket, no streaming — simple and reliable.
+// Records audio → encodes (WAV/FLAC/Opus, see encoder.rs) as base64 → sends to Gemini → returns transcription text.
+// Optionally streams the reply over SSE (see streaming.rs) for progressive typing.
+// Recordings too large to inline are uploaded via the Files API (see files.rs).
+// `model` may list several models: on 429, 5xx or a timeout the request is
+// repeated on the next one at once, and only the last model is retried with
+// backoff (see `Endpoint`).
+// The same client talks to Vertex AI (`backend = "vertex"`) with OAuth tokens
+// from a service account (see vertex.rs) instead of an API key.
 
 use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use reqwest::Client;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_retry::{
+    default_on_request_failure, policies::ExponentialBackoff, DefaultRetryableStrategy, RetryError,
+    RetryTransientMiddleware, Retryable, RetryableStrategy,
+};
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, error, warn};
 
-use crate::config::Config;
-use crate::tracking::TokenUsage;
+use crate::config::{Config, GenerationConfig};
+use crate::encoder::{self, AudioFormat};
+use crate::error::TranscribeError;
+use crate::files::{self, UploadedFile};
+use crate::streaming;
+use crate::tracking::{Latency, TokenUsage};
+use crate::transcriber::{TranscribeContext, Transcriber, Transcription};
+use crate::vertex::{TokenProvider, VertexTarget};
 
-/// HTTP client singleton with retry middleware.
-fn http_client() -> Result<ClientWithMiddleware> {
+/// Deadlines and retry budget from config.
+///
+/// Each HTTP attempt gets `attempt()`; the whole dictation — upload, retries,
+/// model fallbacks, segments — must finish within `overall()` (enforced by
+/// `transcriber::with_deadline`). Both grow with the length of the recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+    pub connect: Duration,
+    attempt: Duration,
+    overall: Duration,
+    /// Added to both deadlines per minute of audio.
+    per_audio_minute: Duration,
+    /// Retries of a transient failure (429, 5xx, connection reset) per request.
+    pub max_retries: u32,
+}
+
+impl Timeouts {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            connect: Duration::from_secs(config.connect_timeout_secs.max(1)),
+            attempt: Duration::from_secs(config.timeout_secs.max(1)),
+            overall: Duration::from_secs(config.overall_timeout_secs.max(1)),
+            per_audio_minute: Duration::from_secs(config.timeout_per_audio_minute_secs),
+            max_retries: config.max_retries,
+        }
+    }
+
+    /// Timeout for a single HTTP attempt carrying `samples` of audio.
+    pub fn attempt(&self, samples: usize) -> Duration {
+        self.attempt + self.scaled(samples)
+    }
+
+    /// Deadline for transcribing `samples` of audio end to end.
+    pub fn overall(&self, samples: usize) -> Duration {
+        self.overall + self.scaled(samples)
+    }
+
+    fn scaled(&self, samples: usize) -> Duration {
+        self.per_audio_minute
+            .mul_f64(samples as f64 / (SAMPLE_RATE as f64 * 60.0))
+    }
+}
+
+/// Classify a failed send as `Timeout` or `Network`.
+pub(crate) fn send_error(err: reqwest_middleware::Error) -> TranscribeError {
+    TranscribeError::request(is_timeout(&err), err)
+}
+
+/// Whether the request timeout fired. The retry middleware wraps the last
+/// error in a `RetryError` inside `Error::Middleware`, so unwrap that first.
+fn is_timeout(err: &reqwest_middleware::Error) -> bool {
+    match err {
+        reqwest_middleware::Error::Reqwest(e) => e.is_timeout(),
+        reqwest_middleware::Error::Middleware(e) => match e.downcast_ref::<RetryError>() {
+            Some(RetryError::WithRetries { err, .. } | RetryError::Error(err)) => is_timeout(err),
+            None => false,
+        },
+    }
+}
+
+/// HTTP client with a connect timeout and retry middleware. Built once per
+/// backend; per-attempt timeouts are set on each request.
+pub fn http_client(timeouts: &Timeouts) -> Result<ClientWithMiddleware> {
+    client_with_retries(timeouts, DefaultRetryableStrategy)
+}
+
+/// Like `http_client`, but 429, 5xx and timeouts are returned at once so the
+/// next model can be tried; only connection failures are retried.
+fn fallback_client(timeouts: &Timeouts) -> Result<ClientWithMiddleware> {
+    client_with_retries(timeouts, ConnectionFailures)
+}
+
+/// Retries a request only if it never reached the server.
+struct ConnectionFailures;
+
+impl RetryableStrategy for ConnectionFailures {
+    fn handle(
+        &self,
+        res: &Result<reqwest::Response, reqwest_middleware::Error>,
+    ) -> Option<Retryable> {
+        match res {
+            Ok(_) => None,
+            Err(e) if is_timeout(e) => Some(Retryable::Fatal),
+            Err(e) => default_on_request_failure(e),
+        }
+    }
+}
+
+fn client_with_retries<R>(timeouts: &Timeouts, strategy: R) -> Result<ClientWithMiddleware>
+where
+    R: RetryableStrategy + Send + Sync + 'static,
+{
     let reqwest_client = Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
+        .connect_timeout(timeouts.connect)
         .build()
         .context("Failed to build HTTP client")?;
 
-    // Retry policy: up to 3 retries with exponential backoff
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+    // Retry policy: exponential backoff within the configured budget
+    let retry_policy = ExponentialBackoff::builder()
+        .retry_bounds(Duration::from_millis(500), Duration::from_secs(8))
+        .build_with_max_retries(timeouts.max_retries);
 
     Ok(ClientBuilder::new(reqwest_client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+            retry_policy,
+            strategy,
+        ))
         .build())
 }
 
-/// Transcribe audio samples using Gemini REST API.
-///
-/// - `config`: App config with API key and model.
-/// - `samples`: All recorded PCM i16 16kHz mono samples.
-///
-/// Returns the transcription text and token usage metadata.
-pub async fn transcribe(config: &Config, samples: &[i16]) -> Result<(String, TokenUsage)> {
-    if samples.is_empty() {
-        bail!("No audio samples to transcribe");
+const SAMPLE_RATE: usize = 16_000;
+
+/// How requests are authenticated.
+enum Auth {
+    /// Gemini API key (`x-goog-api-key` header).
+    ApiKey(String),
+    /// Vertex AI service account (`Authorization: Bearer`).
+    Vertex(TokenProvider),
+}
+
+/// One model of the fallback chain.
+struct Endpoint {
+    model: String,
+    url: String,
+    stream_url: String,
+}
+
+/// Gemini `generateContent` backend — the default transcriber.
+pub struct GeminiTranscriber {
+    /// Shared by every request of this backend.
+    client: ClientWithMiddleware,
+    /// Used instead of `client` while another model is left to fall back to.
+    fallback_client: ClientWithMiddleware,
+    timeouts: Timeouts,
+    auth: Auth,
+    /// Tried in order; later entries only when earlier ones fail transiently.
+    endpoints: Vec<Endpoint>,
+    /// API origin for the Files API.
+    api_base: String,
+    /// Use `streamGenerateContent` (SSE) and forward partial text.
+    streaming: bool,
+    /// Request a JSON reply matching `response_schema` (disables streaming).
+    structured: bool,
+    /// Sampling, thinking and safety settings.
+    generation: GenerationConfig,
+    /// Base64 payloads larger than this go through the Files API.
+    upload_threshold: usize,
+    format: AudioFormat,
+}
+
+impl GeminiTranscriber {
+    pub fn new(config: &Config) -> Result<Self> {
+        let endpoints = config
+            .model
+            .all()
+            .iter()
+            .map(|model| Endpoint {
+                model: model.clone(),
+                url: config.api_url(model),
+                stream_url: config.stream_url(model),
+            })
+            .collect();
+        Self::with_endpoints(config, Auth::ApiKey(config.api_key.clone()), endpoints)
     }
 
-    let duration_secs = samples.len() as f64 / 16_000.0;
-    debug!(
-        samples = samples.len(),
-        duration_secs = format!("{:.1}", duration_secs),
-        "Sending audio to Gemini API"
-    );
+    /// Gemini on Vertex AI, authenticated with a service-account key.
+    pub fn vertex(config: &Config) -> Result<Self> {
+        let target = VertexTarget::from_config(config)?;
+        let endpoints = config
+            .model
+            .all()
+            .iter()
+            .map(|model| Endpoint {
+                model: model.clone(),
+                url: target.url(model),
+                stream_url: target.stream_url(model),
+            })
+            .collect();
+        Self::with_endpoints(config, Auth::Vertex(target.tokens), endpoints)
+    }
+
+    fn with_endpoints(config: &Config, auth: Auth, endpoints: Vec<Endpoint>) -> Result<Self> {
+        if endpoints.is_empty() {
+            bail!("`model` must name at least one Gemini model");
+        }
+        check_generation(&config.generation)?;
+        let timeouts = Timeouts::from_config(config);
+        Ok(Self {
+            client: http_client(&timeouts)?,
+            fallback_client: fallback_client(&timeouts)?,
+            timeouts,
+            auth,
+            endpoints,
+            api_base: config.api_base().to_string(),
+            streaming: config.streaming,
+            structured: config.structured_output,
+            generation: config.generation.clone(),
+            upload_threshold: config.upload_threshold_bytes(),
+            format: AudioFormat::from_name(&config.audio_format)?,
+        })
+    }
 
-    // Step 1: Encode PCM samples as WAV in memory
-    let wav_bytes = encode_wav(samples);
-    let wav_b64 = BASE64.encode(&wav_bytes);
+    /// Encode the samples and build the request body. Long recordings are
+    /// uploaded first; the returned guard deletes the file again.
+    /// Also returns the time spent encoding and uploading to the Files API.
+    async fn prepare_body(
+        &self,
+        samples: &[i16],
+        ctx: &TranscribeContext,
+    ) -> Result<(Value, Option<Upload>, Latency)> {
+        if samples.is_empty() {
+            bail!("No audio samples to transcribe");
+        }
 
-    debug!(
-        wav_size = wav_bytes.len(),
-        b64_size = wav_b64.len(),
-        "Audio encoded as WAV"
-    );
+        let duration_secs = samples.len() as f64 / 16_000.0;
+        debug!(
+            samples = samples.len(),
+            duration_secs = format!("{:.1}", duration_secs),
+            "Sending audio to Gemini API"
+        );
 
-    // Step 2: Build the API request
-    let url = config.api_url();
-    let body = build_request_body(&wav_b64, &config.language);
-
-    debug!(model = %config.model, "Sending request to Gemini API");
-
-    // Step 3: Send HTTP POST (with auto-retry for 429/503)
-    // API key is sent via header, never in URL (security best practice).
-    let client = http_client()?;
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("x-goog-api-key", &config.api_key)
-        .json(&body)
-        .send()
-        .await
-        .context("HTTP request to Gemini API failed")?;
+        // Step 1: Encode PCM samples in the configured format
+        let started = Instant::now();
+        let audio = encoder::encode(samples, self.format)?;
+        let mut latency = Latency {
+            encode_ms: started.elapsed().as_millis() as u64,
+            ..Latency::default()
+        };
+        let b64_size = base64_len(audio.bytes.len());
 
-    let status = response.status();
-    let response_text = response
-        .text()
-        .await
-        .context("Failed to read API response body")?;
-
-    debug!(status = %status, body_len = response_text.len(), "API response received");
-
-    if !status.is_success() {
-        error!(status = %status, body = %truncate_str(&response_text, 500), "Gemini API error");
-
-        // Return a user-friendly error message that will be injected
-        let error_usage = TokenUsage::default();
-        if status.as_u16() == 429 {
-            return Ok((
-                "[Errore: Troppe richieste (429). Attendi qualche secondo e riprova]".to_string(),
-                error_usage,
-            ));
-        } else if status.as_u16() == 403 {
-            return Ok((
-                "[Errore: API Key non valida o permessi insufficienti (403)]".to_string(),
-                error_usage,
-            ));
+        debug!(
+            size = audio.bytes.len(),
+            b64_size,
+            mime_type = audio.mime_type,
+            "Audio encoded"
+        );
+
+        // Step 2: Build the API request — inline, or by reference if too large
+        debug!(model = %self.endpoints[0].model, language = %ctx.language, "Sending request to Gemini API");
+        if b64_size > self.upload_threshold {
+            match &self.auth {
+                Auth::ApiKey(api_key) => {
+                    debug!(
+                        threshold = self.upload_threshold,
+                        "Payload above inline limit, uploading via Files API"
+                    );
+                    let started = Instant::now();
+                    let file = files::upload(
+                        &self.client,
+                        &self.api_base,
+                        api_key,
+                        audio.bytes,
+                        audio.mime_type,
+                        self.timeouts.attempt(samples.len()),
+                    )
+                    .await
+                    .context("Failed to upload long recording")?;
+                    latency.upload_ms = started.elapsed().as_millis() as u64;
+                    let body = self.request_body(files::file_part(&file), &ctx.prompt);
+                    let upload = Upload {
+                        client: self.client.clone(),
+                        base: self.api_base.clone(),
+                        api_key: api_key.clone(),
+                        timeout: self.timeouts.attempt(0),
+                        file: Some(file),
+                    };
+                    return Ok((body, Some(upload), latency));
+                }
+                // Vertex AI has no Files API; it accepts larger inline requests.
+                Auth::Vertex(_) => warn!(
+                    b64_size,
+                    "Payload above upload threshold, sending inline (no Files API on Vertex AI)"
+                ),
+            }
+        }
+
+        let audio_b64 = BASE64.encode(&audio.bytes);
+        Ok((
+            self.request_body(inline_audio(&audio_b64, audio.mime_type), &ctx.prompt),
+            None,
+            latency,
+        ))
+    }
+
+    fn request_body(&self, audio: Value, prompt: &str) -> Value {
+        let mut body = build_request_body(audio, prompt);
+        if let Some(safety) = safety_settings(&self.generation.safety_threshold) {
+            body["safetySettings"] = safety;
+        }
+        body
+    }
+
+    /// `generationConfig` for one model of the chain; set right before each
+    /// attempt because thinking options differ between model families.
+    fn generation_config(&self, model: &str) -> Value {
+        let mut generation = json!({
+            "temperature": self.generation.temperature,
+            "maxOutputTokens": self.generation.max_output_tokens
+        });
+        if let Some(thinking) = thinking_config(&self.generation, model) {
+            generation["thinkingConfig"] = thinking;
+        }
+        if self.structured {
+            generation["responseMimeType"] = json!("application/json");
+            generation["responseSchema"] = response_schema();
+        }
+        generation
+    }
+
+    /// Send the request to `endpoints[index]` (with auto-retry for 429/503 on
+    /// the last model). Credentials are sent via header, never in URL
+    /// (security best practice).
+    async fn post(
+        &self,
+        index: usize,
+        url: &str,
+        body: &Value,
+        attempt: Duration,
+    ) -> Result<reqwest::Response> {
+        let client = if index + 1 < self.endpoints.len() {
+            &self.fallback_client
         } else {
-            return Ok((format!("[Errore API Gemini: {}]", status), error_usage));
+            &self.client
+        };
+        let request = client
+            .post(url)
+            .timeout(attempt)
+            .header("Content-Type", "application/json")
+            .json(body);
+        let request = match &self.auth {
+            Auth::ApiKey(api_key) => request.header("x-goog-api-key", api_key),
+            Auth::Vertex(tokens) => request.bearer_auth(tokens.access_token().await?),
+        };
+        request
+            .send()
+            .await
+            .map_err(send_error)
+            .context("HTTP request to Gemini API failed")
+    }
+
+    /// Whether a failure on `endpoints[index]` should be retried on the next
+    /// model: only for transient errors, and only if there is one left.
+    fn should_fall_back(&self, err: &anyhow::Error, index: usize) -> bool {
+        let Some(next) = self.endpoints.get(index + 1) else {
+            return false;
+        };
+        if !crate::error::find(err).is_some_and(TranscribeError::is_transient) {
+            return false;
+        }
+        warn!(
+            failed = %self.endpoints[index].model,
+            next = %next.model,
+            "{err:#} — falling back to next model"
+        );
+        true
+    }
+}
+
+/// Classify a failed HTTP call.
+fn http_error(status: reqwest::StatusCode, body: &str) -> anyhow::Error {
+    error!(status = %status, body = %truncate_str(body, 500), "Gemini API error");
+    TranscribeError::from_http(status.as_u16(), body).into()
+}
+
+#[async_trait]
+impl Transcriber for GeminiTranscriber {
+    fn name(&self) -> &'static str {
+        match self.auth {
+            Auth::ApiKey(_) => "gemini",
+            Auth::Vertex(_) => "vertex",
         }
     }
 
-    // Step 4: Parse the response
-    let parsed: Value =
-        serde_json::from_str(&response_text).context("Failed to parse Gemini API JSON response")?;
+    /// Transcribe audio samples using Gemini REST API.
+    ///
+    /// - `samples`: All recorded PCM i16 16kHz mono samples.
+    /// - `ctx`: Prompt and language for this dictation.
+    ///
+    /// Returns the transcription text and token usage metadata.
+    async fn transcribe(&self, samples: &[i16], ctx: &TranscribeContext) -> Result<Transcription> {
+        let (mut body, uploaded, latency) = self.prepare_body(samples, ctx).await?;
+        let outcome = self
+            .generate(&mut body, self.timeouts.attempt(samples.len()))
+            .await;
+        if let Some(upload) = uploaded {
+            upload.delete().await;
+        }
+        outcome.map(|t| with_prepare_latency(t, latency))
+    }
 
-    // Extract token usage from usageMetadata
-    let usage = extract_usage(&parsed);
+    /// Same as `transcribe`, but via `streamGenerateContent` so partial text
+    /// reaches `deltas` while the model is still generating.
+    async fn transcribe_streaming(
+        &self,
+        samples: &[i16],
+        ctx: &TranscribeContext,
+        deltas: &UnboundedSender<String>,
+    ) -> Result<Transcription> {
+        // A JSON reply is only usable once complete, so it is never streamed.
+        if !self.streaming || self.structured {
+            let result = self.transcribe(samples, ctx).await?;
+            if !result.text.is_empty() {
+                let _ = deltas.send(result.text.clone());
+            }
+            return Ok(result);
+        }
 
-    let transcription = extract_text(&parsed)?;
+        let (mut body, uploaded, latency) = self.prepare_body(samples, ctx).await?;
+        let outcome = self
+            .stream(&mut body, deltas, self.timeouts.attempt(samples.len()))
+            .await;
+        if let Some(upload) = uploaded {
+            upload.delete().await;
+        }
+        outcome.map(|t| with_prepare_latency(t, latency))
+    }
 
-    debug!(
-        text_len = transcription.len(),
-        text_preview = %truncate_str(&transcription, 80),
-        prompt_tokens = usage.prompt_tokens,
-        output_tokens = usage.candidates_tokens,
-        "Transcription received"
-    );
+    async fn warm_up(&self) {
+        // Vertex tokens take a round trip of their own; fetch one now too.
+        if let Auth::Vertex(tokens) = &self.auth {
+            if let Err(e) = tokens.access_token().await {
+                debug!(error = %format!("{e:#}"), "Access token prefetch failed");
+            }
+        }
+        warm_up_origin(&self.endpoints[0].url, |origin| {
+            self.client
+                .head(origin)
+                .timeout(self.timeouts.attempt(0))
+                .send()
+        })
+        .await;
+    }
+}
+
+/// A recording uploaded to the Files API. Deleted with `delete` once the
+/// request is done; if the request is cancelled instead (the dictation
+/// deadline drops the future), dropping the guard deletes it in the background.
+struct Upload {
+    client: ClientWithMiddleware,
+    base: String,
+    api_key: String,
+    timeout: Duration,
+    file: Option<UploadedFile>,
+}
+
+impl Upload {
+    async fn delete(mut self) {
+        if let Some(file) = self.file.take() {
+            delete_upload(&self.client, &self.base, &self.api_key, &file, self.timeout).await;
+        }
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        let Some(file) = self.file.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!(name = %file.name, "Uploaded audio left on the server (expires in 48 h)");
+            return;
+        };
+        let (client, base, api_key, timeout) = (
+            self.client.clone(),
+            self.base.clone(),
+            self.api_key.clone(),
+            self.timeout,
+        );
+        runtime.spawn(async move { delete_upload(&client, &base, &api_key, &file, timeout).await });
+    }
+}
+
+/// Failures are logged, never surfaced.
+async fn delete_upload(
+    client: &ClientWithMiddleware,
+    base: &str,
+    api_key: &str,
+    file: &UploadedFile,
+    timeout: Duration,
+) {
+    if let Err(e) = files::delete(client, base, api_key, &file.name, timeout).await {
+        warn!(%e, name = %file.name, "Failed to delete uploaded audio");
+    }
+}
+
+/// Add the encode and Files API upload time from `prepare_body`.
+fn with_prepare_latency(mut transcription: Transcription, prepare: Latency) -> Transcription {
+    transcription.latency.encode_ms += prepare.encode_ms;
+    transcription.latency.upload_ms += prepare.upload_ms;
+    transcription
+}
+
+/// Scheme, host and port of `url`, e.g. `https://aiplatform.googleapis.com`.
+pub fn origin(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(url.origin().ascii_serialization()).filter(|o| o != "null")
+}
+
+/// `Transcriber::warm_up` for HTTP backends: `head` sends a HEAD request to
+/// the origin of `url` with the client whose pool should keep the connection.
+/// Any answer (usually 404) leaves an open connection behind.
+pub async fn warm_up_origin<F, E>(url: &str, head: impl FnOnce(String) -> F)
+where
+    F: std::future::Future<Output = Result<reqwest::Response, E>>,
+    E: std::fmt::Display,
+{
+    let Some(origin) = origin(url) else {
+        return;
+    };
+    match head(origin.clone()).await {
+        Ok(response) => debug!(%origin, status = %response.status(), "Connection warmed up"),
+        Err(e) => debug!(%origin, %e, "Connection warm-up failed"),
+    }
+}
+
+impl GeminiTranscriber {
+    /// Send `text` with an instruction and no audio (rewrite modes). With
+    /// streaming on, the reply reaches `deltas` while it is generated.
+    pub async fn complete_text(
+        &self,
+        instruction: &str,
+        text: &str,
+        deltas: &UnboundedSender<String>,
+        attempt: Duration,
+    ) -> Result<Transcription> {
+        let mut body = self.request_body(json!({ "text": text }), instruction);
+        if self.streaming && !self.structured {
+            return self.stream(&mut body, deltas, attempt).await;
+        }
+        let result = self.generate(&mut body, attempt).await?;
+        if !result.text.is_empty() {
+            let _ = deltas.send(result.text.clone());
+        }
+        Ok(result)
+    }
+
+    /// `generateContent` on each model in turn until one answers.
+    async fn generate(&self, body: &mut Value, attempt: Duration) -> Result<Transcription> {
+        let mut index = 0;
+        loop {
+            let endpoint = &self.endpoints[index];
+            body["generationConfig"] = self.generation_config(&endpoint.model);
+            match self.generate_with(index, body, attempt).await {
+                Err(e) if self.should_fall_back(&e, index) => index += 1,
+                outcome => return outcome,
+            }
+        }
+    }
+
+    /// POST to one model's `generateContent` and parse the reply.
+    async fn generate_with(
+        &self,
+        index: usize,
+        body: &Value,
+        attempt: Duration,
+    ) -> Result<Transcription> {
+        let endpoint = &self.endpoints[index];
+        // Step 3: Send HTTP POST
+        let sent = Instant::now();
+        let response = self.post(index, &endpoint.url, body, attempt).await?;
+        let upload_ms = sent.elapsed().as_millis() as u64;
+
+        let status = response.status();
+        let response_text = response
+            .text()
+            .await
+            .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+            .context("Failed to read API response body")?;
+
+        debug!(status = %status, body_len = response_text.len(), "API response received");
+
+        if !status.is_success() {
+            return Err(http_error(status, &response_text));
+        }
+
+        // Step 4: Parse the response
+        let parsed: Value = serde_json::from_str(&response_text)
+            .context("Failed to parse Gemini API JSON response")?;
+
+        // Extract token usage from usageMetadata
+        let usage = extract_usage(&parsed);
+
+        let text = extract_text(&parsed)?;
+        let (text, detected_language) = if self.structured {
+            parse_structured_reply(&text)?.into_parts()
+        } else {
+            (text, None)
+        };
+
+        debug!(
+            text_len = text.len(),
+            text_preview = %truncate_str(&text, 80),
+            prompt_tokens = usage.prompt_tokens,
+            output_tokens = usage.candidates_tokens,
+            thinking_tokens = usage.thoughts_tokens,
+            "Transcription received"
+        );
+
+        Ok(Transcription {
+            text,
+            usage,
+            model: endpoint.model.clone(),
+            detected_language,
+            latency: Latency {
+                upload_ms,
+                ..Latency::default()
+            },
+        })
+    }
+
+    /// POST to `streamGenerateContent` and read the SSE reply. Falls back to
+    /// the next model only before the stream starts — once text may have been
+    /// typed, a retry would duplicate it.
+    async fn stream(
+        &self,
+        body: &mut Value,
+        deltas: &UnboundedSender<String>,
+        attempt: Duration,
+    ) -> Result<Transcription> {
+        let mut index = 0;
+        let (response, endpoint, upload_ms) = loop {
+            let endpoint = &self.endpoints[index];
+            body["generationConfig"] = self.generation_config(&endpoint.model);
+            let sent = Instant::now();
+            match self.open_stream(index, body, attempt).await {
+                Ok(response) => break (response, endpoint, sent.elapsed().as_millis() as u64),
+                Err(e) if self.should_fall_back(&e, index) => index += 1,
+                Err(e) => return Err(e),
+            }
+        };
+
+        let (text, usage) =
+            streaming::read_stream(response, deltas, extract_usage, &endpoint.model).await?;
+
+        debug!(
+            text_len = text.len(),
+            prompt_tokens = usage.prompt_tokens,
+            output_tokens = usage.candidates_tokens,
+            thinking_tokens = usage.thoughts_tokens,
+            "Streamed transcription complete"
+        );
+
+        Ok(Transcription {
+            text,
+            usage,
+            model: endpoint.model.clone(),
+            detected_language: None,
+            latency: Latency {
+                upload_ms,
+                ..Latency::default()
+            },
+        })
+    }
 
-    Ok((transcription, usage))
+    async fn open_stream(
+        &self,
+        index: usize,
+        body: &Value,
+        attempt: Duration,
+    ) -> Result<reqwest::Response> {
+        let url = &self.endpoints[index].stream_url;
+        let response = self.post(index, url, body, attempt).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let response_text = response.text().await.unwrap_or_default();
+            return Err(http_error(status, &response_text));
+        }
+        Ok(response)
+    }
+}
+
+/// Size of the base64 encoding of `n` bytes (with padding).
+fn base64_len(n: usize) -> usize {
+    n.div_ceil(3) * 4
+}
+
+/// The `inlineData` part carrying base64 audio.
+fn inline_audio(audio_b64: &str, mime_type: &str) -> Value {
+    json!({
+        "inlineData": {
+            "mimeType": mime_type,
+            "data": audio_b64
+        }
+    })
 }
 
-/// Build the JSON body for Gemini generateContent with inline audio.
-fn build_request_body(wav_b64: &str, language: &str) -> Value {
-    let prompt = crate::config::transcription_prompt(language);
+/// Build the JSON body for Gemini generateContent. `audio` is either
+/// `inline_audio(..)` or `files::file_part(..)`. `generationConfig` depends
+/// on the model and is added per attempt.
+fn build_request_body(audio: Value, prompt: &str) -> Value {
     json!({
         "contents": [{
             "parts": [
                 {
                     "text": prompt
                 },
-                {
-                    "inlineData": {
-                        "mimeType": "audio/wav",
-                        "data": wav_b64
-                    }
-                }
+                audio
             ]
-        }],
-        "generationConfig": {
-            "temperature": 0.0,
-            "maxOutputTokens": 4096
+        }]
+    })
+}
+
+/// Values accepted by `generation.thinking_level`.
+const THINKING_LEVELS: &[&str] = &["minimal", "low", "medium", "high"];
+
+/// Values accepted by `generation.safety_threshold`.
+const SAFETY_THRESHOLDS: &[&str] = &[
+    "OFF",
+    "BLOCK_NONE",
+    "BLOCK_ONLY_HIGH",
+    "BLOCK_MEDIUM_AND_ABOVE",
+    "BLOCK_LOW_AND_ABOVE",
+];
+
+const HARM_CATEGORIES: &[&str] = &[
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+];
+
+/// Reject values the API would only refuse at the first dictation.
+fn check_generation(generation: &GenerationConfig) -> Result<()> {
+    if let Some(level) = &generation.thinking_level {
+        if !THINKING_LEVELS.contains(&level.as_str()) {
+            bail!(
+                "Unknown generation.thinking_level '{}'. Available: {}",
+                level,
+                THINKING_LEVELS.join(", ")
+            );
+        }
+    }
+    let threshold = &generation.safety_threshold;
+    if !threshold.is_empty() && !SAFETY_THRESHOLDS.contains(&threshold.as_str()) {
+        bail!(
+            "Unknown generation.safety_threshold '{}'. Available: {}",
+            threshold,
+            SAFETY_THRESHOLDS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// `thinkingConfig` for `model`. 2.5 models take a token budget — flash and
+/// flash-lite can switch thinking off, pro needs at least 128 — and 3.x
+/// models a level. Older models reject the field, so they get none.
+fn thinking_config(generation: &GenerationConfig, model: &str) -> Option<Value> {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    if name.starts_with("gemini-2.5") {
+        let minimum = if name.contains("pro") { 128 } else { 0 };
+        let budget = generation.thinking_budget.unwrap_or(minimum);
+        Some(json!({ "thinkingBudget": budget }))
+    } else if name.starts_with("gemini-3") {
+        let level = generation.thinking_level.as_deref().unwrap_or("low");
+        Some(json!({ "thinkingLevel": level }))
+    } else {
+        None
+    }
+}
+
+/// The same threshold for every harm category; `None` keeps the defaults.
+fn safety_settings(threshold: &str) -> Option<Value> {
+    if threshold.is_empty() {
+        return None;
+    }
+    let settings: Vec<Value> = HARM_CATEGORIES
+        .iter()
+        .map(|category| json!({ "category": category, "threshold": threshold }))
+        .collect();
+    Some(Value::Array(settings))
+}
+
+/// `responseSchema` for `structured_output`: the model fills these fields
+/// instead of answering in free text.
+fn response_schema() -> Value {
+    json!({
+        "type": "OBJECT",
+        "properties": {
+            "text": { "type": "STRING" },
+            "detected_language": { "type": "STRING" },
+            "is_silence": { "type": "BOOLEAN" },
+            "notes": { "type": "STRING" }
+        },
+        "required": ["text", "is_silence"],
+        "propertyOrdering": ["text", "detected_language", "is_silence", "notes"]
+    })
+}
+
+/// A reply shaped by `response_schema`.
+#[derive(Debug, Deserialize)]
+struct StructuredReply {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    detected_language: Option<String>,
+    #[serde(default)]
+    is_silence: bool,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+impl StructuredReply {
+    /// The text to type (empty for silence) and the detected language.
+    fn into_parts(self) -> (String, Option<String>) {
+        if let Some(notes) = self.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            debug!(notes, "Model notes on the recording");
         }
+        let text = if self.is_silence {
+            String::new()
+        } else {
+            self.text.trim().to_string()
+        };
+        let language = self
+            .detected_language
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty());
+        (text, language)
+    }
+}
+
+/// Parse the JSON text returned in `structured_output` mode. An empty reply
+/// counts as silence.
+fn parse_structured_reply(raw: &str) -> Result<StructuredReply> {
+    if raw.is_empty() {
+        return Ok(StructuredReply {
+            text: String::new(),
+            detected_language: None,
+            is_silence: true,
+            notes: None,
+        });
+    }
+    serde_json::from_str(raw).with_context(|| {
+        format!(
+            "Gemini returned malformed structured output: {}",
+            truncate_str(raw, 200)
+        )
     })
 }
 
 /// Extract token usage from the Gemini response's `usageMetadata` field.
 fn extract_usage(response: &Value) -> TokenUsage {
-    if let Some(meta) = response.get("usageMetadata") {
-        TokenUsage {
-            prompt_tokens: meta
-                .get("promptTokenCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            candidates_tokens: meta
-                .get("candidatesTokenCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            total_tokens: meta
-                .get("totalTokenCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-        }
-    } else {
-        TokenUsage::default()
+    let Some(meta) = response.get("usageMetadata") else {
+        return TokenUsage::default();
+    };
+    let field = |name: &str| meta.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+    TokenUsage {
+        prompt_tokens: field("promptTokenCount"),
+        candidates_tokens: field("candidatesTokenCount"),
+        thoughts_tokens: field("thoughtsTokenCount"),
+        total_tokens: field("totalTokenCount"),
     }
 }
 
 /// Extract text from Gemini generateContent response.
+///
+/// Blocked, truncated and error responses become a `TranscribeError`; an
+/// empty string means the model heard nothing (silence).
 fn extract_text(response: &Value) -> Result<String> {
+    // Check for error in response
+    if let Some(error) = response.get("error") {
+        let err = TranscribeError::from_api_error(error);
+        error!(%err, "Gemini API error");
+        return Err(err.into());
+    }
+
+    // The prompt itself was blocked: no candidates at all.
+    if let Some(reason) = response
+        .pointer("/promptFeedback/blockReason")
+        .and_then(|r| r.as_str())
+    {
+        return Err(TranscribeError::SafetyBlocked {
+            reason: reason.to_string(),
+        }
+        .into());
+    }
+
     // Standard response format:
     // { "candidates": [{ "content": { "parts": [{ "text": "..." }] } }] }
-    if let Some(candidates) = response.get("candidates").and_then(|c| c.as_array()) {
-        if let Some(first) = candidates.first() {
-            if let Some(content) = first.get("content") {
-                if let Some(parts) = content.get("parts").and_then(|p| p.as_array()) {
-                    let mut text = String::new();
-                    for part in parts {
-                        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
-                            text.push_str(t);
-                        }
-                    }
-                    if !text.is_empty() {
-                        return Ok(text.trim().to_string());
-                    }
-                }
+    if let Some(first) = response
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .and_then(|c| c.first())
+    {
+        // Check for safety/block reasons
+        if let Some(reason) = first.get("finishReason").and_then(|r| r.as_str()) {
+            if let Some(err) = TranscribeError::from_finish_reason(reason) {
+                return Err(err.into());
             }
+            if reason != "STOP" {
+                warn!(reason, "Gemini response had non-STOP finish reason");
+            }
+        }
 
-            // Check for safety/block reasons
-            if let Some(reason) = first.get("finishReason").and_then(|r| r.as_str()) {
-                if reason != "STOP" {
-                    warn!(reason, "Gemini response had non-STOP finish reason");
+        if let Some(parts) = first.pointer("/content/parts").and_then(|p| p.as_array()) {
+            let mut text = String::new();
+            for part in parts {
+                if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                    text.push_str(t);
                 }
             }
+            if !text.is_empty() {
+                return Ok(text.trim().to_string());
+            }
         }
     }
 
-    // Check for error in response
-    if let Some(error) = response.get("error") {
-        let msg = error
-            .get("message")
-            .and_then(|m| m.as_str())
-            .unwrap_or("Unknown API error");
-        error!("Gemini API error: {}", msg);
-        return Ok(format!("[Errore API Gemini: {}]", msg));
-    }
-
     warn!(
         response = %truncate_str(&response.to_string(), 300),
         "Could not extract text from Gemini response"
@@ -215,93 +946,101 @@ fn extract_text(response: &Value) -> Result<String> {
     Ok(String::new())
 }
 
-/// Encode PCM i16 mono 16kHz samples as a WAV file in memory.
-pub fn encode_wav(samples: &[i16]) -> Vec<u8> {
-    let num_channels: u16 = 1;
-    let sample_rate: u32 = 16_000;
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * (num_channels as u32) * (bits_per_sample as u32 / 8);
-    let block_align = num_channels * (bits_per_sample / 8);
-    let data_size = (samples.len() * 2) as u32;
-    let file_size = 36 + data_size; // RIFF header is 44 bytes, file_size = total - 8
-
-    let mut buf = Vec::with_capacity(44 + data_size as usize);
-
-    // RIFF header
-    buf.extend_from_slice(b"RIFF");
-    buf.extend_from_slice(&file_size.to_le_bytes());
-    buf.extend_from_slice(b"WAVE");
-
-    // fmt sub-chunk
-    buf.extend_from_slice(b"fmt ");
-    buf.extend_from_slice(&16u32.to_le_bytes()); // sub-chunk size (PCM = 16)
-    buf.extend_from_slice(&1u16.to_le_bytes()); // audio format (PCM = 1)
-    buf.extend_from_slice(&num_channels.to_le_bytes());
-    buf.extend_from_slice(&sample_rate.to_le_bytes());
-    buf.extend_from_slice(&byte_rate.to_le_bytes());
-    buf.extend_from_slice(&block_align.to_le_bytes());
-    buf.extend_from_slice(&bits_per_sample.to_le_bytes());
-
-    // data sub-chunk
-    buf.extend_from_slice(b"data");
-    buf.extend_from_slice(&data_size.to_le_bytes());
-
-    // PCM samples (little-endian i16)
-    for &sample in samples {
-        buf.extend_from_slice(&sample.to_le_bytes());
-    }
-
-    buf
-}
-
-/// Truncate a string for display.
-fn truncate_str(s: &str, max: usize) -> String {
+/// Truncate a string to at most `max` bytes for display, never splitting a
+/// character.
+pub fn truncate_str(s: &str, max: usize) -> String {
     if s.len() <= max {
-        s.to_string()
-    } else {
-        format!("{}…", &s[..max])
+        return s.to_string();
     }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &s[..end])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_encode_wav_header() {
-        let samples: Vec<i16> = vec![0; 1600]; // 100ms at 16kHz
-        let wav = encode_wav(&samples);
-
-        // Check RIFF header
-        assert_eq!(&wav[0..4], b"RIFF");
-        assert_eq!(&wav[8..12], b"WAVE");
-        assert_eq!(&wav[12..16], b"fmt ");
-        assert_eq!(&wav[36..40], b"data");
-
-        // Data size should be samples * 2 bytes
-        let data_size = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]);
-        assert_eq!(data_size, 3200); // 1600 samples * 2 bytes
-    }
-
-    #[test]
-    fn test_encode_wav_total_size() {
-        let samples: Vec<i16> = vec![100, -100, 0, i16::MAX, i16::MIN];
-        let wav = encode_wav(&samples);
-        assert_eq!(wav.len(), 44 + samples.len() * 2); // 44 header + data
-    }
+    use crate::test_support::{RecordedRequest, StubResponse, StubServer};
 
     #[test]
     fn test_build_request_body() {
-        let body = build_request_body("dGVzdA==", "auto");
+        let body = build_request_body(inline_audio("dGVzdA==", "audio/flac"), "Transcribe.");
         assert_eq!(
             body["contents"][0]["parts"][1]["inlineData"]["mimeType"],
-            "audio/wav"
+            "audio/flac"
         );
         assert_eq!(
             body["contents"][0]["parts"][1]["inlineData"]["data"],
             "dGVzdA=="
         );
-        assert_eq!(body["generationConfig"]["temperature"], 0.0);
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "Transcribe.");
+        assert!(body.get("safetySettings").is_none());
+    }
+
+    #[test]
+    fn test_thinking_config_per_model() {
+        let defaults = GenerationConfig::default();
+        assert_eq!(
+            thinking_config(&defaults, "models/gemini-2.5-flash"),
+            Some(json!({"thinkingBudget": 0}))
+        );
+        assert_eq!(
+            thinking_config(&defaults, "gemini-2.5-pro"),
+            Some(json!({"thinkingBudget": 128}))
+        );
+        assert_eq!(
+            thinking_config(&defaults, "models/gemini-3-pro-preview"),
+            Some(json!({"thinkingLevel": "low"}))
+        );
+        assert_eq!(thinking_config(&defaults, "models/gemini-2.0-flash"), None);
+
+        let custom = GenerationConfig {
+            thinking_budget: Some(-1),
+            thinking_level: Some("high".into()),
+            ..GenerationConfig::default()
+        };
+        assert_eq!(
+            thinking_config(&custom, "models/gemini-2.5-flash"),
+            Some(json!({"thinkingBudget": -1}))
+        );
+        assert_eq!(
+            thinking_config(&custom, "models/gemini-3-flash"),
+            Some(json!({"thinkingLevel": "high"}))
+        );
+    }
+
+    #[test]
+    fn test_check_generation() {
+        assert!(check_generation(&GenerationConfig::default()).is_ok());
+        let bad_level = GenerationConfig {
+            thinking_level: Some("extreme".into()),
+            ..GenerationConfig::default()
+        };
+        assert!(check_generation(&bad_level).is_err());
+        let bad_safety = GenerationConfig {
+            safety_threshold: "block_all".into(),
+            ..GenerationConfig::default()
+        };
+        assert!(check_generation(&bad_safety).is_err());
+
+        let safety = safety_settings("BLOCK_ONLY_HIGH").unwrap();
+        assert_eq!(safety.as_array().unwrap().len(), HARM_CATEGORIES.len());
+        assert_eq!(safety[0]["threshold"], "BLOCK_ONLY_HIGH");
+    }
+
+    #[test]
+    fn test_extract_usage_thoughts() {
+        let response = json!({"usageMetadata": {
+            "promptTokenCount": 120,
+            "candidatesTokenCount": 8,
+            "thoughtsTokenCount": 300,
+            "totalTokenCount": 428
+        }});
+        let usage = extract_usage(&response);
+        assert_eq!(usage.candidates_tokens, 8);
+        assert_eq!(usage.thoughts_tokens, 300);
     }
 
     #[test]
@@ -339,25 +1078,223 @@ mod tests {
                 "code": 403
             }
         });
-        let result = extract_text(&response);
-        assert!(result.is_ok());
-        assert!(result.unwrap().contains("API key invalid"));
+        let err = extract_text(&response).unwrap_err();
+        assert_eq!(
+            crate::error::find(&err),
+            Some(&TranscribeError::Auth {
+                message: "API key invalid".into()
+            })
+        );
     }
 
     #[test]
-    fn test_truncate_str() {
-        assert_eq!(truncate_str("hello", 10), "hello");
-        assert_eq!(truncate_str("hello world", 5), "hello\u{2026}");
+    fn test_base64_len() {
+        assert_eq!(base64_len(0), 0);
+        assert_eq!(base64_len(4), BASE64.encode([0u8; 4]).len());
+        assert_eq!(base64_len(3000), BASE64.encode(vec![0u8; 3000]).len());
+    }
+
+    fn ctx() -> TranscribeContext {
+        TranscribeContext {
+            language: "it".into(),
+            prompt: "Trascrivi.".into(),
+        }
+    }
+
+    /// Files API stand-in: accepts an upload as `files/f1`, answers
+    /// `/generate` with `generate` and accepts the file's deletion.
+    async fn files_api_stub(
+        generate: impl Fn(&RecordedRequest) -> StubResponse + Send + Sync + 'static,
+    ) -> StubServer {
+        StubServer::start(move |req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/upload/v1beta/files") => {
+                let url = format!("http://{}/session", req.header("host").unwrap());
+                StubResponse::json(200, json!({})).with_header("X-Goog-Upload-URL", &url)
+            }
+            ("POST", "/session") => StubResponse::json(
+                200,
+                json!({"file": {"name": "files/f1", "uri": "https://files/f1",
+                                "mimeType": "audio/wav", "state": "ACTIVE"}}),
+            ),
+            ("POST", "/generate") => generate(req),
+            ("DELETE", "/v1beta/files/f1") => StubResponse::json(200, json!({})),
+            _ => StubResponse::text(404, "not found"),
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_long_recording_uses_files_api() {
+        let server = files_api_stub(|_| {
+            StubResponse::json(
+                200,
+                json!({"candidates": [{"content": {"parts": [{"text": "nota lunga"}]}}]}),
+            )
+        })
+        .await;
+
+        let gemini = GeminiTranscriber {
+            upload_threshold: 1024,
+            ..test_transcriber(&server.url, Timeouts::from_config(&Config::default()))
+        };
+        let ctx = ctx();
+        let result = gemini.transcribe(&[0i16; 16_000], &ctx).await.unwrap();
+        assert_eq!(result.text, "nota lunga");
+
+        let requests = server.requests();
+        let paths: Vec<_> = requests
+            .iter()
+            .map(|r| format!("{} {}", r.method, r.path))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "POST /upload/v1beta/files",
+                "POST /session",
+                "POST /generate",
+                "DELETE /v1beta/files/f1"
+            ]
+        );
+        let body: Value = serde_json::from_slice(&requests[2].body).unwrap();
+        let audio = &body["contents"][0]["parts"][1];
+        assert_eq!(audio["fileData"]["fileUri"], "https://files/f1");
+        assert!(audio.get("inlineData").is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancelled_request_deletes_upload() {
+        let server = files_api_stub(|_| {
+            // Outlives the deadline below.
+            std::thread::sleep(Duration::from_millis(500));
+            StubResponse::text(500, "too late")
+        })
+        .await;
+
+        let gemini = GeminiTranscriber {
+            upload_threshold: 1024,
+            ..test_transcriber(&server.url, Timeouts::from_config(&Config::default()))
+        };
+        let ctx = ctx();
+        let samples = [0i16; 16_000];
+        let cancelled = crate::transcriber::with_deadline(
+            Duration::from_millis(200),
+            gemini.transcribe(&samples, &ctx),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        for _ in 0..50 {
+            if server
+                .requests()
+                .iter()
+                .any(|r| r.method == "DELETE" && r.path == "/v1beta/files/f1")
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("uploaded file was not deleted");
+    }
+
+    #[tokio::test]
+    async fn test_default_config_chunks_long_recordings() {
+        let server = StubServer::start(|req| {
+            if req.path.contains(":generateContent") {
+                StubResponse::json(
+                    200,
+                    json!({"candidates": [{"content": {"parts": [{"text": "nota"}]}}]}),
+                )
+            } else {
+                StubResponse::text(404, "not found")
+            }
+        })
+        .await;
+
+        let cfg = Config {
+            api_key: "AIzaSyTest".into(),
+            api_base: server.url.clone(),
+            ..Config::default()
+        };
+        let transcriber = crate::transcriber::from_config(&cfg).unwrap();
+        // Over the inline limit as WAV, but every segment fits inline.
+        let samples = vec![0i16; cfg.upload_threshold_bytes() * 3 / 8 + SAMPLE_RATE];
+        let segment = cfg.chunking.max_segment_secs as usize * SAMPLE_RATE;
+        let ctx = ctx();
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        transcriber
+            .transcribe_streaming(&samples, &ctx, &tx)
+            .await
+            .unwrap();
+
+        // A request per segment, all inline; nothing uploaded.
+        let requests = server.requests();
+        assert!(requests.len() >= samples.len() / segment);
+        for r in &requests {
+            assert!(r.path.contains(":generateContent"), "{}", r.path);
+            assert!(r.body_text().contains("inlineData"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_structured_output() {
+        let reply = json!({
+            "text": " Buongiorno a tutti ",
+            "detected_language": "IT",
+            "is_silence": false,
+            "notes": ""
+        });
+        let server = StubServer::start(move |_| {
+            StubResponse::json(
+                200,
+                json!({"candidates": [{"content": {"parts": [{"text": reply.to_string()}]}}]}),
+            )
+        })
+        .await;
+
+        let gemini = GeminiTranscriber {
+            streaming: true,
+            structured: true,
+            ..test_transcriber(&server.url, Timeouts::from_config(&Config::default()))
+        };
+        let ctx = TranscribeContext {
+            language: "auto".into(),
+            prompt: "Trascrivi.".into(),
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = gemini
+            .transcribe_streaming(&[0i16; 1600], &ctx, &tx)
+            .await
+            .unwrap();
+        assert_eq!(result.text, "Buongiorno a tutti");
+        assert_eq!(result.detected_language.as_deref(), Some("it"));
+        assert_eq!(rx.recv().await.unwrap(), "Buongiorno a tutti");
+
+        // Structured replies are never streamed.
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/generate");
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let generation = &body["generationConfig"];
+        assert_eq!(generation["responseMimeType"], "application/json");
+        assert_eq!(
+            generation["responseSchema"]["properties"]["is_silence"]["type"],
+            "BOOLEAN"
+        );
+    }
+
+    #[test]
+    fn test_parse_structured_reply() {
+        let silence = parse_structured_reply(r#"{"text":"[rumore]","is_silence":true}"#).unwrap();
+        assert_eq!(silence.into_parts(), (String::new(), None));
+        assert!(parse_structured_reply("").unwrap().is_silence);
+        assert!(parse_structured_reply("not json").is_err());
     }
 
     #[test]
-    fn test_encode_wav_empty() {
-        let samples: Vec<i16> = vec![];
-        let wav = encode_wav(&samples);
-        // Header only, 0 data bytes
-        assert_eq!(wav.len(), 44);
-        let data_size = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]);
-        assert_eq!(data_size, 0);
+    fn test_truncate_str() {
+        assert_eq!(truncate_str("hello", 10), "hello");
+        assert_eq!(truncate_str("hello world", 5), "hello\u{2026}");
+        // "è" is two bytes; the cut falls inside it.
+        assert_eq!(truncate_str("perchè no", 6), "perch\u{2026}");
     }
 
     #[test]
@@ -394,7 +1331,224 @@ mod tests {
                 "finishReason": "SAFETY"
             }]
         });
-        let text = extract_text(&response).unwrap();
-        assert!(text.is_empty());
+        let err = extract_text(&response).unwrap_err();
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::SafetyBlocked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extract_text_truncated() {
+        let response = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "ciao mon"}]},
+                "finishReason": "MAX_TOKENS"
+            }]
+        });
+        let err = extract_text(&response).unwrap_err();
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Truncated));
+    }
+
+    #[tokio::test]
+    async fn test_http_error_is_not_injected() {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                403,
+                json!({"error": {"code": 403, "message": "Permission denied"}}),
+            )
+        })
+        .await;
+        let gemini = GeminiTranscriber {
+            streaming: true,
+            ..test_transcriber(&server.url, Timeouts::from_config(&Config::default()))
+        };
+        let ctx = TranscribeContext {
+            language: "auto".into(),
+            prompt: "Transcribe.".into(),
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let err = gemini
+            .transcribe_streaming(&[0i16; 1600], &ctx, &tx)
+            .await
+            .unwrap_err();
+        drop(tx);
+
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::Auth { .. })
+        ));
+        assert!(rx.recv().await.is_none(), "nothing may be typed on error");
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_model() {
+        let server = StubServer::start(|req| match req.path.as_str() {
+            "/pro/generate" | "/pro/stream" => StubResponse::json(
+                429,
+                json!({"error": {"code": 429, "message": "Resource has been exhausted"}}),
+            ),
+            "/flash/generate" => StubResponse::json(
+                200,
+                json!({"candidates": [{"content": {"parts": [{"text": "salvata"}]}}]}),
+            ),
+            "/flash/stream" => StubResponse::text(
+                200,
+                "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"salvata\"}]}}]}\n\n",
+            ),
+            _ => StubResponse::text(404, "not found"),
+        })
+        .await;
+        let endpoint = |name: &str| Endpoint {
+            model: format!("models/gemini-2.5-{name}"),
+            url: format!("{}/{name}/generate", server.url),
+            stream_url: format!("{}/{name}/stream", server.url),
+        };
+        // The default retry budget is spent on the last model only, so the
+        // 429 moves on to flash without a retry on pro.
+        let gemini = GeminiTranscriber {
+            endpoints: vec![endpoint("pro"), endpoint("flash")],
+            streaming: true,
+            ..test_transcriber(&server.url, Timeouts::from_config(&Config::default()))
+        };
+        let mut body = build_request_body(inline_audio("AAAA", "audio/wav"), "Transcribe.");
+        let attempt = Duration::from_secs(5);
+
+        let result = gemini.generate(&mut body, attempt).await.unwrap();
+        assert_eq!(result.text, "salvata");
+        assert_eq!(result.model, "models/gemini-2.5-flash");
+
+        // One request per model, each with its own thinking setting.
+        let budgets: Vec<Value> = server
+            .requests()
+            .iter()
+            .map(|r| {
+                let body: Value = serde_json::from_slice(&r.body).unwrap();
+                body["generationConfig"]["thinkingConfig"]["thinkingBudget"].clone()
+            })
+            .collect();
+        assert_eq!(budgets, [json!(128), json!(0)]);
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = gemini.stream(&mut body, &tx, attempt).await.unwrap();
+        assert_eq!(result.model, "models/gemini-2.5-flash");
+
+        // Non-transient errors are not retried on another model.
+        let denied = GeminiTranscriber {
+            endpoints: vec![endpoint("missing"), endpoint("flash")],
+            ..gemini
+        };
+        let err = denied.generate(&mut body, attempt).await.unwrap_err();
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::Server { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_connects_to_origin() {
+        let server = StubServer::start(|_| StubResponse::text(404, "")).await;
+        let gemini = GeminiTranscriber {
+            endpoints: vec![Endpoint {
+                model: "models/gemini-2.0-flash".into(),
+                url: format!(
+                    "{}/v1beta/models/gemini-2.0-flash:generateContent",
+                    server.url
+                ),
+                stream_url: format!("{}/stream", server.url),
+            }],
+            ..test_transcriber(&server.url, no_retries())
+        };
+        gemini.warm_up().await;
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            (requests[0].method.as_str(), requests[0].path.as_str()),
+            ("HEAD", "/")
+        );
+        assert!(requests[0].header("x-goog-api-key").is_none());
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(
+            origin("https://generativelanguage.googleapis.com/v1beta/models/x:generateContent")
+                .as_deref(),
+            Some("https://generativelanguage.googleapis.com")
+        );
+        assert_eq!(
+            origin("http://localhost:8000/v1").as_deref(),
+            Some("http://localhost:8000")
+        );
+        assert_eq!(origin("not a url"), None);
+    }
+
+    /// A non-streaming transcriber for one model served from `base`;
+    /// tests override the fields they exercise.
+    fn test_transcriber(base: &str, timeouts: Timeouts) -> GeminiTranscriber {
+        GeminiTranscriber {
+            client: http_client(&timeouts).unwrap(),
+            fallback_client: fallback_client(&timeouts).unwrap(),
+            timeouts,
+            auth: Auth::ApiKey("AIzaSyTest".into()),
+            endpoints: vec![Endpoint {
+                model: "models/gemini-2.0-flash".into(),
+                url: format!("{base}/generate"),
+                stream_url: format!("{base}/stream"),
+            }],
+            api_base: base.to_string(),
+            streaming: false,
+            structured: false,
+            generation: GenerationConfig::default(),
+            upload_threshold: usize::MAX,
+            format: AudioFormat::Wav,
+        }
+    }
+
+    fn no_retries() -> Timeouts {
+        Timeouts {
+            max_retries: 0,
+            ..Timeouts::from_config(&Config::default())
+        }
+    }
+
+    #[test]
+    fn test_timeouts_scale_with_audio() {
+        let cfg = Config {
+            timeout_secs: 10,
+            overall_timeout_secs: 60,
+            timeout_per_audio_minute_secs: 30,
+            ..Config::default()
+        };
+        let timeouts = Timeouts::from_config(&cfg);
+        assert_eq!(timeouts.attempt(0), Duration::from_secs(10));
+        assert_eq!(timeouts.attempt(16_000 * 120), Duration::from_secs(70));
+        assert_eq!(timeouts.overall(16_000 * 30), Duration::from_secs(75));
+    }
+
+    #[tokio::test]
+    async fn test_attempt_timeout() {
+        // Accepts connections but never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+
+        let gemini = test_transcriber(&url, no_retries());
+        let mut body = build_request_body(inline_audio("AAAA", "audio/wav"), "Transcribe.");
+
+        let started = std::time::Instant::now();
+        let err = gemini
+            .generate(&mut body, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Timeout));
     }
 }
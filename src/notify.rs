@@ -0,0 +1,69 @@
+// notify.rs — Desktop notifications for failures the user would otherwise miss.
+// The daemon runs in the background and must never type error text into the
+// focused app, so a transient OS notification is the only visible signal
+// besides the error beep. Uses `notify-send` (Linux) and `osascript` (macOS);
+// on other platforms, or if the tool is missing, it is a silent no-op.
+
+use tracing::debug;
+
+const TITLE: &str = "G-Type";
+
+/// Show a notification without blocking the caller.
+pub fn show(body: &str) {
+    let Some(mut cmd) = command(body) else {
+        debug!("Desktop notifications not supported on this platform");
+        return;
+    };
+    // Reap the child on a throwaway thread so it never becomes a zombie.
+    std::thread::spawn(move || {
+        if let Err(e) = cmd.status() {
+            debug!(%e, "Failed to show desktop notification");
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn command(body: &str) -> Option<std::process::Command> {
+    let mut cmd = std::process::Command::new("notify-send");
+    cmd.args(["--app-name", TITLE, TITLE, body]);
+    Some(cmd)
+}
+
+#[cfg(target_os = "macos")]
+fn command(body: &str) -> Option<std::process::Command> {
+    let mut cmd = std::process::Command::new("osascript");
+    cmd.args([
+        "-e",
+        &format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(TITLE)
+        ),
+    ]);
+    Some(cmd)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn command(_body: &str) -> Option<std::process::Command> {
+    None
+}
+
+/// Quote a string as an AppleScript literal.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applescript_string_escapes() {
+        assert_eq!(applescript_string("ok"), "\"ok\"");
+        assert_eq!(
+            applescript_string(r#"say "hi" \ bye"#),
+            r#""say \"hi\" \\ bye""#
+        );
+    }
+}
@@ -0,0 +1,282 @@
+// openai.rs — Backend for OpenAI-compatible `/v1/audio/transcriptions` endpoints.
+// Works with OpenAI, Groq, faster-whisper-server, LocalAI and anything else
+// that speaks the same multipart API. Audio is uploaded in the configured
+// `audio_format` (see encoder.rs).
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Instant;
+use tracing::{debug, error};
+
+use crate::config::{Config, OpenAiConfig};
+use crate::encoder::{self, AudioFormat};
+use crate::error::TranscribeError;
+use crate::network::{truncate_str, warm_up_origin, Timeouts};
+use crate::tracking::{Latency, TokenUsage};
+use crate::transcriber::{TranscribeContext, Transcriber, Transcription};
+
+/// Transcriber for OpenAI-compatible speech-to-text servers.
+pub struct OpenAiTranscriber {
+    settings: OpenAiConfig,
+    client: Client,
+    timeouts: Timeouts,
+    format: AudioFormat,
+}
+
+impl OpenAiTranscriber {
+    pub fn new(config: &Config) -> Result<Self> {
+        // No retry middleware: multipart bodies cannot be replayed.
+        let timeouts = Timeouts::from_config(config);
+        let client = Client::builder()
+            .connect_timeout(timeouts.connect)
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            settings: config.openai.clone(),
+            client,
+            timeouts,
+            format: AudioFormat::from_name(&config.audio_format)?,
+        })
+    }
+
+    /// Full URL of the transcription endpoint.
+    fn endpoint(&self) -> String {
+        format!(
+            "{}/audio/transcriptions",
+            self.settings.base_url.trim_end_matches('/')
+        )
+    }
+}
+
+#[async_trait]
+impl Transcriber for OpenAiTranscriber {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn warm_up(&self) {
+        warm_up_origin(&self.settings.base_url, |origin| {
+            self.client
+                .head(origin)
+                .timeout(self.timeouts.attempt(0))
+                .send()
+        })
+        .await;
+    }
+
+    async fn transcribe(&self, samples: &[i16], ctx: &TranscribeContext) -> Result<Transcription> {
+        if samples.is_empty() {
+            bail!("No audio samples to transcribe");
+        }
+
+        let started = Instant::now();
+        let audio = encoder::encode(samples, self.format)?;
+        let encode_ms = started.elapsed().as_millis() as u64;
+        debug!(
+            size = audio.bytes.len(),
+            mime_type = audio.mime_type,
+            model = %self.settings.model,
+            "Uploading audio to OpenAI-compatible endpoint"
+        );
+
+        let file = Part::bytes(audio.bytes)
+            .file_name(format!("audio.{}", audio.extension))
+            .mime_str(audio.mime_type)
+            .context("Invalid audio MIME type")?;
+        let mut form = Form::new()
+            .part("file", file)
+            .text("model", self.settings.model.clone())
+            .text("response_format", "json");
+        if ctx.language != "auto" && !ctx.language.is_empty() {
+            form = form.text("language", ctx.language.clone());
+        }
+
+        let mut request = self
+            .client
+            .post(self.endpoint())
+            .timeout(self.timeouts.attempt(samples.len()))
+            .multipart(form);
+        // Local servers usually run without auth — only send a token if configured.
+        if !self.settings.api_key.is_empty() {
+            request = request.bearer_auth(&self.settings.api_key);
+        }
+
+        let sent = Instant::now();
+        let response = request
+            .send()
+            .await
+            .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+            .context("HTTP request to transcription endpoint failed")?;
+        let upload_ms = sent.elapsed().as_millis() as u64;
+
+        let status = response.status();
+        let response_text = response
+            .text()
+            .await
+            .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+            .context("Failed to read transcription response body")?;
+
+        if !status.is_success() {
+            error!(status = %status, body = %truncate_str(&response_text, 500), "Transcription endpoint error");
+            return Err(TranscribeError::from_http(status.as_u16(), &response_text).into());
+        }
+
+        let parsed: Value = serde_json::from_str(&response_text)
+            .context("Failed to parse transcription JSON response")?;
+        let text = parsed
+            .get("text")
+            .and_then(|t| t.as_str())
+            .context("Transcription response has no 'text' field")?
+            .trim()
+            .to_string();
+
+        Ok(Transcription {
+            text,
+            usage: extract_usage(&parsed),
+            model: self.settings.model.clone(),
+            detected_language: None,
+            latency: Latency {
+                encode_ms,
+                upload_ms,
+                ..Latency::default()
+            },
+        })
+    }
+}
+
+/// Extract token usage, reported by token-billed models such as
+/// `gpt-4o-transcribe`. Duration-billed models (`whisper-1`) and most
+/// self-hosted servers omit it, yielding zero usage.
+fn extract_usage(response: &Value) -> TokenUsage {
+    let Some(usage) = response.get("usage") else {
+        return TokenUsage::default();
+    };
+    let field = |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+    TokenUsage {
+        prompt_tokens: field("input_tokens"),
+        candidates_tokens: field("output_tokens"),
+        thoughts_tokens: 0,
+        total_tokens: field("total_tokens"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{StubResponse, StubServer};
+    use serde_json::json;
+
+    fn config_for(base_url: &str, api_key: &str) -> Config {
+        Config {
+            backend: "openai".into(),
+            openai: OpenAiConfig {
+                base_url: base_url.into(),
+                model: "whisper-large-v3".into(),
+                api_key: api_key.into(),
+            },
+            ..Config::default()
+        }
+    }
+
+    fn ctx(language: &str) -> TranscribeContext {
+        TranscribeContext {
+            language: language.into(),
+            prompt: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_endpoint_trailing_slash() {
+        let t = OpenAiTranscriber::new(&config_for("http://localhost:8000/v1/", "")).unwrap();
+        assert_eq!(
+            t.endpoint(),
+            "http://localhost:8000/v1/audio/transcriptions"
+        );
+    }
+
+    #[test]
+    fn test_extract_usage_tokens() {
+        let usage = extract_usage(&json!({
+            "text": "hi",
+            "usage": {"type": "tokens", "input_tokens": 12, "output_tokens": 3, "total_tokens": 15}
+        }));
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.candidates_tokens, 3);
+    }
+
+    #[test]
+    fn test_extract_usage_missing() {
+        let usage = extract_usage(&json!({"text": "hi"}));
+        assert_eq!(usage.prompt_tokens, 0);
+        assert_eq!(usage.candidates_tokens, 0);
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_against_stub() {
+        let server =
+            StubServer::start(|_| StubResponse::json(200, json!({"text": "  hello world \n"})))
+                .await;
+        let cfg = Config {
+            audio_format: "flac".into(),
+            ..config_for(&format!("{}/v1", server.url), "sk-test")
+        };
+        let t = OpenAiTranscriber::new(&cfg).unwrap();
+
+        let result = t.transcribe(&[0i16; 1600], &ctx("en")).await.unwrap();
+        assert_eq!(result.text, "hello world");
+        assert_eq!(result.model, "whisper-large-v3");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let req = &requests[0];
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/v1/audio/transcriptions");
+        assert_eq!(req.header("authorization"), Some("Bearer sk-test"));
+        assert!(req
+            .header("content-type")
+            .unwrap()
+            .starts_with("multipart/form-data"));
+        let body = req.body_text();
+        assert!(body.contains("name=\"model\""));
+        assert!(body.contains("whisper-large-v3"));
+        assert!(body.contains("filename=\"audio.flac\""));
+        assert!(body.contains("Content-Type: audio/flac"));
+        assert!(body.contains("fLaC"));
+        assert!(body.contains("name=\"language\""));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_without_key_or_language() {
+        let server = StubServer::start(|_| StubResponse::json(200, json!({"text": "ok"}))).await;
+        let t = OpenAiTranscriber::new(&config_for(&server.url, "")).unwrap();
+
+        t.transcribe(&[1i16; 160], &ctx("auto")).await.unwrap();
+
+        let req = &server.requests()[0];
+        assert!(req.header("authorization").is_none());
+        assert!(!req.body_text().contains("name=\"language\""));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_http_error() {
+        let server = StubServer::start(|_| StubResponse::text(401, "bad token")).await;
+        let t = OpenAiTranscriber::new(&config_for(&server.url, "wrong")).unwrap();
+
+        let err = t.transcribe(&[0i16; 160], &ctx("en")).await.unwrap_err();
+        assert_eq!(
+            crate::error::find(&err),
+            Some(&TranscribeError::Auth {
+                message: "bad token".into()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_empty_samples() {
+        let t = OpenAiTranscriber::new(&config_for("http://127.0.0.1:1", "")).unwrap();
+        assert!(t.transcribe(&[], &ctx("en")).await.is_err());
+    }
+}
@@ -0,0 +1,390 @@
+// prompt.rs — Transcription prompt templates.
+// The instruction sent with the audio comes from a template: the built-in
+// `default`, written in the dictation language, or a user template from the
+// `[prompts]` config table. Templates use `{language}`, `{glossary}`, `{app}`
+// and `{previous_text}`; a line whose variables are all empty is dropped, so
+// one template works with or without a glossary, a known app, and so on.
+
+use anyhow::{bail, Result};
+
+use crate::config::Config;
+
+/// Name of the built-in template.
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+/// How much of the previous dictation is passed as `{previous_text}`.
+const PREVIOUS_TEXT_CHARS: usize = 500;
+
+/// Where the dictation is going, known only when recording starts.
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    /// Application that has keyboard focus (see `focus`).
+    pub app: Option<String>,
+    /// Text dictated just before into the same application.
+    pub previous_text: Option<String>,
+}
+
+/// The built-in template in one language.
+struct Builtin {
+    code: &'static str,
+    intro: &'static str,
+    /// Output rules for free-text replies.
+    plain: &'static str,
+    /// Output rules for `structured_output` replies.
+    structured: &'static str,
+    /// One line per variable.
+    context: &'static str,
+}
+
+/// Languages with a native default template; the others use English.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        code: "en",
+        intro: "Transcribe exactly what is said in this audio, word for word. \
+                Do not add comments, do not answer questions, do not invent punctuation.",
+        plain: "Return ONLY the dictated text. If the audio is silent or unintelligible, \
+                reply with an empty string.",
+        structured: "Put ONLY the dictated text in the `text` field and the ISO 639-1 code of \
+                     the spoken language in `detected_language`. If the audio is silent or \
+                     unintelligible, set `is_silence` to true and leave `text` empty. Use \
+                     `notes` only to flag noisy audio or uncertain words.",
+        context: "The audio is in {language}. Transcribe in that language.\n\
+                  When you hear one of these terms, write it exactly like this: {glossary}.\n\
+                  The text will be typed into {app}.\n\
+                  Text dictated just before, for context only (do not repeat it): {previous_text}",
+    },
+    Builtin {
+        code: "it",
+        intro: "Trascrivi esattamente ciò che viene detto in questo audio, parola per parola. \
+                Non aggiungere commenti, non rispondere a domande, non inventare punteggiatura.",
+        plain: "Restituisci SOLO il testo dettato. Se l'audio è silenzioso o incomprensibile, \
+                rispondi con una stringa vuota.",
+        structured: "Metti SOLO il testo dettato nel campo `text` e il codice ISO 639-1 della \
+                     lingua parlata in `detected_language`. Se l'audio è silenzioso o \
+                     incomprensibile, imposta `is_silence` a true e lascia `text` vuoto. Usa \
+                     `notes` solo per segnalare audio disturbato o parole incerte.",
+        context: "L'audio è in {language}. Trascrivi in quella lingua.\n\
+                  Quando senti uno di questi termini, scrivilo esattamente così: {glossary}.\n\
+                  Il testo verrà digitato in {app}.\n\
+                  Testo dettato subito prima, solo come contesto (non ripeterlo): {previous_text}",
+    },
+    Builtin {
+        code: "es",
+        intro: "Transcribe exactamente lo que se dice en este audio, palabra por palabra. \
+                No añadas comentarios, no respondas preguntas, no inventes puntuación.",
+        plain: "Devuelve SOLO el texto dictado. Si el audio está en silencio o es \
+                ininteligible, responde con una cadena vacía.",
+        structured: "Pon SOLO el texto dictado en el campo `text` y el código ISO 639-1 del \
+                     idioma hablado en `detected_language`. Si el audio está en silencio o es \
+                     ininteligible, pon `is_silence` a true y deja `text` vacío. Usa `notes` \
+                     solo para señalar audio con ruido o palabras dudosas.",
+        context: "El audio está en {language}. Transcribe en ese idioma.\n\
+                  Cuando oigas uno de estos términos, escríbelo exactamente así: {glossary}.\n\
+                  El texto se escribirá en {app}.\n\
+                  Texto dictado justo antes, solo como contexto (no lo repitas): {previous_text}",
+    },
+    Builtin {
+        code: "fr",
+        intro: "Transcris exactement ce qui est dit dans cet audio, mot pour mot. \
+                N'ajoute pas de commentaires, ne réponds pas aux questions, n'invente pas \
+                de ponctuation.",
+        plain: "Renvoie UNIQUEMENT le texte dicté. Si l'audio est silencieux ou \
+                incompréhensible, réponds par une chaîne vide.",
+        structured: "Mets UNIQUEMENT le texte dicté dans le champ `text` et le code ISO 639-1 \
+                     de la langue parlée dans `detected_language`. Si l'audio est silencieux \
+                     ou incompréhensible, mets `is_silence` à true et laisse `text` vide. \
+                     Utilise `notes` seulement pour signaler un audio bruité ou des mots \
+                     incertains.",
+        context: "L'audio est en {language}. Transcris dans cette langue.\n\
+                  Quand tu entends l'un de ces termes, écris-le exactement ainsi : {glossary}.\n\
+                  Le texte sera saisi dans {app}.\n\
+                  Texte dicté juste avant, uniquement pour le contexte (ne le répète pas) : \
+                  {previous_text}",
+    },
+    Builtin {
+        code: "de",
+        intro: "Transkribiere genau, was in dieser Aufnahme gesagt wird, Wort für Wort. \
+                Füge keine Kommentare hinzu, beantworte keine Fragen, erfinde keine \
+                Zeichensetzung.",
+        plain: "Gib NUR den diktierten Text zurück. Wenn die Aufnahme still oder \
+                unverständlich ist, antworte mit einer leeren Zeichenkette.",
+        structured: "Schreibe NUR den diktierten Text in das Feld `text` und den \
+                     ISO-639-1-Code der gesprochenen Sprache in `detected_language`. Wenn die \
+                     Aufnahme still oder unverständlich ist, setze `is_silence` auf true und \
+                     lass `text` leer. Nutze `notes` nur, um verrauschte Aufnahmen oder \
+                     unsichere Wörter zu melden.",
+        context: "Die Aufnahme ist auf {language}. Transkribiere in dieser Sprache.\n\
+                  Wenn du einen dieser Begriffe hörst, schreibe ihn genau so: {glossary}.\n\
+                  Der Text wird in {app} eingegeben.\n\
+                  Unmittelbar davor diktierter Text, nur als Kontext (nicht wiederholen): \
+                  {previous_text}",
+    },
+];
+
+/// The built-in template for a language code, with the output rules for
+/// plain or structured replies.
+pub fn default_template(language: &str, structured: bool) -> String {
+    let builtin = BUILTINS
+        .iter()
+        .find(|b| b.code == language)
+        .unwrap_or(&BUILTINS[0]);
+    let output = if structured {
+        builtin.structured
+    } else {
+        builtin.plain
+    };
+    format!("{} {}\n{}", builtin.intro, output, builtin.context)
+}
+
+/// Name of the template in use: the active profile's, else `prompt`.
+pub fn template_name(config: &Config) -> &str {
+    config
+        .active_profile()
+        .and_then(|p| p.prompt.as_deref())
+        .unwrap_or(&config.prompt)
+}
+
+/// Fail if the configured template does not exist.
+pub fn check(config: &Config) -> Result<()> {
+    let name = template_name(config);
+    if name != DEFAULT_TEMPLATE && !config.prompts.contains_key(name) {
+        bail!("Prompt template '{name}' is not defined. Add it under [prompts] or use \"default\"");
+    }
+    Ok(())
+}
+
+/// The transcription prompt for a dictation. `structured` selects output
+/// rules for JSON replies (see `Config::structured_output`).
+pub fn transcription_prompt(config: &Config, structured: bool, context: &PromptContext) -> String {
+    let name = template_name(config);
+    let template = match config.prompts.get(name) {
+        Some(template) => template.clone(),
+        None => default_template(&config.language, structured),
+    };
+
+    let language = match config.language.as_str() {
+        "auto" | "" => String::new(),
+        code => format!("{} ({code})", crate::config::language_name(code)),
+    };
+    let previous_text = context
+        .previous_text
+        .as_deref()
+        .map(last_chars)
+        .unwrap_or_default();
+    render(
+        &template,
+        &[
+            ("language", &language),
+            ("glossary", &config.glossary_terms().join(", ")),
+            ("app", context.app.as_deref().unwrap_or_default()),
+            ("previous_text", previous_text),
+        ],
+    )
+}
+
+/// Substitute `{name}` placeholders. Lines that use variables, all of them
+/// empty, are dropped; unknown placeholders are left as written.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut lines = Vec::new();
+    for line in template.trim().lines() {
+        let used: Vec<&str> = vars
+            .iter()
+            .filter(|(name, _)| line.contains(&format!("{{{name}}}")))
+            .map(|(_, value)| *value)
+            .collect();
+        if !used.is_empty() && used.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        lines.push(substitute(line, vars));
+    }
+    lines.join("\n")
+}
+
+/// Replace the placeholders of one line in a single pass, so a value that
+/// itself contains `{app}` or the like is inserted as written.
+fn substitute(line: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+        let var = vars.iter().find_map(|(name, value)| {
+            let after = tail.strip_prefix(name)?.strip_prefix('}')?;
+            Some((value, after))
+        });
+        match var {
+            Some((value, after)) => {
+                out.push_str(value.trim());
+                rest = after;
+            }
+            None => {
+                out.push('{');
+                rest = tail;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The tail of `text`, cut at a character boundary.
+fn last_chars(text: &str) -> &str {
+    let skip = text.chars().count().saturating_sub(PREVIOUS_TEXT_CHARS);
+    match text.char_indices().nth(skip) {
+        Some((start, _)) => &text[start..],
+        None => text,
+    }
+}
+
+/// Handle `g-type prompt ...` (`args` excludes the `prompt` word).
+pub fn run_command(args: &[String]) -> Result<()> {
+    let mut context = PromptContext::default();
+    let mut name = None;
+    let mut iter = args.iter().map(String::as_str);
+    match iter.next() {
+        None | Some("preview") => {}
+        Some(other) => bail!("Unknown prompt command '{other}'. Use preview"),
+    }
+    while let Some(arg) = iter.next() {
+        match arg {
+            "--app" => context.app = iter.next().map(str::to_string),
+            "--previous" => context.previous_text = iter.next().map(str::to_string),
+            flag if flag.starts_with("--") => bail!(
+                "Unknown option '{flag}'. Usage: g-type prompt preview [NAME] [--app APP] [--previous TEXT]"
+            ),
+            template => name = Some(template.to_string()),
+        }
+    }
+
+    let mut config = crate::config::load()?;
+    if let Some(name) = name {
+        config.prompt = name;
+        if let Some(profile) = config.profiles.get_mut(&config.profile) {
+            profile.prompt = None;
+        }
+        check(&config)?;
+    }
+    let structured = config.structured_output && !config.live.enabled;
+    let prompt = transcription_prompt(&config, structured, &context);
+
+    println!();
+    println!(
+        "  \x1b[1m📝 Prompt template '{}' ({})\x1b[0m",
+        template_name(&config),
+        config.language
+    );
+    println!();
+    for line in prompt.lines() {
+        println!("  {line}");
+    }
+    println!();
+    if context.app.is_none() {
+        println!("  \x1b[2mPass --app NAME or --previous TEXT to fill in {{app}} and {{previous_text}}.\x1b[0m");
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(language: &str) -> Config {
+        Config {
+            language: language.into(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_default_template_follows_language() {
+        let prompt = transcription_prompt(&config("it"), false, &PromptContext::default());
+        assert!(prompt.starts_with("Trascrivi esattamente"));
+        assert!(prompt.contains("L'audio è in Italiano (it)."));
+        // Empty variables drop their lines.
+        assert!(!prompt.contains('{'));
+        assert_eq!(prompt.lines().count(), 2);
+
+        let prompt = transcription_prompt(&config("ja"), true, &PromptContext::default());
+        assert!(prompt.starts_with("Transcribe exactly"));
+        assert!(prompt.contains("`is_silence`"));
+        assert!(prompt.contains("日本語 (ja)"));
+
+        let prompt = transcription_prompt(&config("auto"), false, &PromptContext::default());
+        assert_eq!(prompt.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_user_template() {
+        let mut cfg = config("en");
+        cfg.glossary = vec!["AcmeDB".into(), "Kubernetes".into()];
+        cfg.prompts.insert(
+            "email".into(),
+            "Transcribe this email in {language}.\nTerms: {glossary}\nApp: {app}\n\
+             Earlier: \"{previous_text}\"\nKeep {braces} as written."
+                .into(),
+        );
+        cfg.prompt = "email".into();
+        check(&cfg).unwrap();
+
+        let context = PromptContext {
+            app: Some("thunderbird".into()),
+            previous_text: None,
+        };
+        assert_eq!(
+            transcription_prompt(&cfg, false, &context),
+            "Transcribe this email in English (en).\nTerms: AcmeDB, Kubernetes\n\
+             App: thunderbird\nKeep {braces} as written."
+        );
+
+        cfg.prompt = "missing".into();
+        assert!(check(&cfg).is_err());
+    }
+
+    #[test]
+    fn test_profile_selects_template() {
+        let raw = r#"
+prompt = "default"
+profile = "code"
+
+[prompts]
+code = "Dictation for {app}."
+
+[profiles.code]
+prompt = "code"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(template_name(&cfg), "code");
+        let context = PromptContext {
+            app: Some("Code".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            transcription_prompt(&cfg, false, &context),
+            "Dictation for Code."
+        );
+    }
+
+    #[test]
+    fn test_values_are_not_expanded_again() {
+        let context = PromptContext {
+            app: Some("term".into()),
+            previous_text: Some("type {app} and {glossary} literally".into()),
+        };
+        let mut cfg = config("en");
+        cfg.prompts
+            .insert("p".into(), "In {app}: {previous_text} {x}".into());
+        cfg.prompt = "p".into();
+        assert_eq!(
+            transcription_prompt(&cfg, false, &context),
+            "In term: type {app} and {glossary} literally {x}"
+        );
+    }
+
+    #[test]
+    fn test_previous_text_is_truncated() {
+        let long = "é".repeat(PREVIOUS_TEXT_CHARS + 10);
+        assert_eq!(last_chars(&long).chars().count(), PREVIOUS_TEXT_CHARS);
+        assert_eq!(last_chars("short"), "short");
+    }
+}
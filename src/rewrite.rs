@@ -0,0 +1,296 @@
+// rewrite.rs — Rewrite modes: a second, text-only Gemini call that turns the
+// raw transcription into what the user wanted to write (fixed grammar, an
+// email, bullet points, ...) before anything is typed. A mode with
+// `translate_to` types the text in another language than the one spoken.
+// Modes are built in or defined under `[modes.NAME]`, picked by `mode` / a
+// profile, or bound to a hotkey of their own. Both calls are tracked as
+// separate line items. The edit hotkey uses the same call to apply a spoken
+// instruction to the selected text.
+
+use anyhow::{bail, Result};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{language_name, Config};
+use crate::network::{GeminiTranscriber, Timeouts};
+use crate::transcriber::Transcription;
+
+/// Modes available without configuration: name and instruction.
+pub const BUILTIN_MODES: &[(&str, &str)] = &[
+    (
+        "grammar",
+        "Fix grammar, spelling and punctuation and drop filler words and false starts. \
+         Otherwise keep the wording as it is.",
+    ),
+    (
+        "email",
+        "Turn it into a clear, polite email with a greeting, short paragraphs and a closing. \
+         Do not make up names for the signature.",
+    ),
+    (
+        "bullets",
+        "Turn it into a concise bullet-point list, one idea per line, each line starting \
+         with \"- \".",
+    ),
+    ("formal", "Rewrite it in a formal, professional tone."),
+];
+
+/// A resolved rewrite mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mode {
+    pub name: String,
+    /// Empty for a mode that only translates.
+    pub instruction: String,
+    /// Language spoken with this mode, overriding `language`.
+    pub source_language: Option<String>,
+    /// Language the text is translated into.
+    pub target_language: Option<String>,
+}
+
+impl Mode {
+    /// Look up `name` in `[modes]`, falling back to the built-in instruction.
+    pub fn resolve(config: &Config, name: &str) -> Result<Self> {
+        let entry = config.modes.get(name);
+        let configured = entry.and_then(|m| m.instruction.as_deref());
+        let builtin = BUILTIN_MODES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, i)| *i);
+        let source_language = entry
+            .and_then(|m| m.language.as_deref())
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && *l != "auto")
+            .map(str::to_string);
+        let target_language = match entry.and_then(|m| m.translate_to.as_deref()) {
+            Some(l) if l.trim().is_empty() || l.trim() == "auto" => {
+                bail!("[modes.{name}] translate_to needs a language code, e.g. \"en\"")
+            }
+            target => target.map(|l| l.trim().to_string()),
+        };
+        let instruction = match configured.or(builtin) {
+            Some(instruction) => instruction.trim().to_string(),
+            None if target_language.is_some() => String::new(),
+            None => bail!(
+                "Rewrite mode '{name}' has no instruction. Set [modes.{name}] instruction \
+                 or use a built-in mode: {}",
+                BUILTIN_MODES
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            instruction,
+            source_language,
+            target_language,
+        })
+    }
+
+    /// The instruction sent ahead of the text.
+    fn prompt(&self) -> String {
+        let (task, keep) = match &self.target_language {
+            None => (
+                "You rewrite dictated text.".to_string(),
+                "Keep the language and meaning of the text and do not add information. \
+                 Reply with ONLY the rewritten text.",
+            ),
+            Some(code) => (
+                format!(
+                    "You translate dictated text into {} ({code}).",
+                    language_name(code)
+                ),
+                "Keep the meaning and tone of the text and do not add information. \
+                 Reply with ONLY the translated text.",
+            ),
+        };
+        match self.instruction.as_str() {
+            "" => format!("{task}\n{keep}"),
+            instruction => format!("{task} {instruction}\n{keep}"),
+        }
+    }
+}
+
+/// Fail if a mode named anywhere in the config cannot be resolved.
+pub fn check(config: &Config) -> Result<()> {
+    let profile_modes = config.profiles.values().filter_map(|p| p.mode.as_deref());
+    let named = config
+        .modes
+        .keys()
+        .map(String::as_str)
+        .chain(config.active_mode())
+        .chain(profile_modes);
+    for name in named {
+        Mode::resolve(config, name)?;
+    }
+    Ok(())
+}
+
+/// Modes bound to a hotkey of their own: `(mode, hotkey)`.
+pub fn hotkeys(config: &Config) -> Vec<(&str, &str)> {
+    config
+        .modes
+        .iter()
+        .filter_map(|(name, m)| Some((name.as_str(), m.hotkey.as_deref()?)))
+        .collect()
+}
+
+/// Runs rewrite calls against Gemini (or Vertex AI with `backend = "vertex"`).
+pub struct Rewriter {
+    gemini: GeminiTranscriber,
+    timeouts: Timeouts,
+}
+
+impl Rewriter {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut rewrite_config = config.clone();
+        if let Some(model) = &config.rewrite_model {
+            rewrite_config.model = model.clone();
+        }
+        rewrite_config.structured_output = false;
+        let gemini = match config.backend.as_str() {
+            "vertex" => GeminiTranscriber::vertex(&rewrite_config)?,
+            _ if config.api_key.is_empty() => {
+                bail!("Rewrite modes run on Gemini. Set `api_key` or remove the modes")
+            }
+            _ => GeminiTranscriber::new(&rewrite_config)?,
+        };
+        Ok(Self {
+            gemini,
+            timeouts: Timeouts::from_config(config),
+        })
+    }
+
+    /// Rewrite `text` from a recording of `audio_samples` samples; longer
+    /// dictations get the same extra time as their transcription. The result
+    /// reaches `deltas` as it is generated.
+    pub async fn rewrite(
+        &self,
+        mode: &Mode,
+        text: &str,
+        audio_samples: usize,
+        deltas: &UnboundedSender<String>,
+    ) -> Result<Transcription> {
+        let attempt = self.timeouts.attempt(audio_samples);
+        self.gemini
+            .complete_text(&mode.prompt(), text, deltas, attempt)
+            .await
+    }
+
+    /// Apply a spoken `instruction` to text selected in an application.
+    pub async fn edit(
+        &self,
+        instruction: &str,
+        selection: &str,
+        audio_samples: usize,
+    ) -> Result<Transcription> {
+        let prompt = format!(
+            "You edit text the user selected in an application, following their spoken \
+             instruction: {instruction}\n\
+             Keep the formatting, such as line breaks, unless the instruction changes it. \
+             Reply with ONLY the edited text, without quotes or explanations."
+        );
+        // The result replaces the selection in one piece, so nothing is streamed.
+        let (deltas, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let attempt = self.timeouts.attempt(audio_samples);
+        self.gemini
+            .complete_text(&prompt, selection, &deltas, attempt)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModeConfig;
+
+    #[test]
+    fn test_resolve_modes() {
+        let mut cfg = Config::default();
+        cfg.modes.insert(
+            "email".into(),
+            ModeConfig {
+                hotkey: Some("ctrl+shift+e".into()),
+                ..Default::default()
+            },
+        );
+        cfg.modes.insert(
+            "haiku".into(),
+            ModeConfig {
+                instruction: Some("Turn it into a haiku.".into()),
+                ..Default::default()
+            },
+        );
+
+        let email = Mode::resolve(&cfg, "email").unwrap();
+        assert!(email.instruction.contains("polite email"));
+        assert_eq!(
+            Mode::resolve(&cfg, "haiku").unwrap().instruction,
+            "Turn it into a haiku."
+        );
+        assert!(Mode::resolve(&cfg, "sonnet").is_err());
+        assert_eq!(hotkeys(&cfg), [("email", "ctrl+shift+e")]);
+        check(&cfg).unwrap();
+
+        cfg.mode = "sonnet".into();
+        assert!(check(&cfg).is_err());
+    }
+
+    #[test]
+    fn test_translation_mode() {
+        let mut cfg = Config::default();
+        cfg.modes.insert(
+            "support".into(),
+            ModeConfig {
+                hotkey: Some("ctrl+alt+t".into()),
+                language: Some("it".into()),
+                translate_to: Some("en".into()),
+                ..Default::default()
+            },
+        );
+        let mode = Mode::resolve(&cfg, "support").unwrap();
+        assert_eq!(mode.instruction, "");
+        assert_eq!(mode.source_language.as_deref(), Some("it"));
+        assert_eq!(mode.target_language.as_deref(), Some("en"));
+        assert!(mode
+            .prompt()
+            .starts_with("You translate dictated text into English (en).\n"));
+
+        // A built-in rewrite can translate as well.
+        cfg.modes.insert(
+            "email".into(),
+            ModeConfig {
+                translate_to: Some("de".into()),
+                ..Default::default()
+            },
+        );
+        let email = Mode::resolve(&cfg, "email").unwrap();
+        assert!(email.prompt().contains("into Deutsch (de). Turn it into"));
+
+        cfg.modes.get_mut("email").unwrap().translate_to = Some("auto".into());
+        assert!(check(&cfg).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_sends_text_only() {
+        use crate::test_support::{GeminiReply, MockGemini};
+
+        let mock = MockGemini::start(GeminiReply::Text("- first\n- second".into())).await;
+        let rewriter = Rewriter::from_config(&mock.config()).unwrap();
+        let mode = Mode::resolve(&Config::default(), "bullets").unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let result = rewriter
+            .rewrite(&mode, "first and second", 16_000, &tx)
+            .await
+            .unwrap();
+        assert_eq!(result.text, "- first\n- second");
+        assert_eq!(rx.recv().await.as_deref(), Some("- first\n- second"));
+
+        let body: serde_json::Value = serde_json::from_slice(&mock.requests()[0].body).unwrap();
+        let parts = &body["contents"][0]["parts"];
+        assert!(parts[0]["text"].as_str().unwrap().contains("bullet-point"));
+        assert_eq!(parts[1]["text"], "first and second");
+        assert!(body["generationConfig"].get("responseSchema").is_none());
+    }
+}
@@ -0,0 +1,1125 @@
+// spool.rs — Offline spool for dictations whose transcription failed.
+// Instead of discarding the recording, `app` saves the audio (WAV) and its
+// request metadata under `<data dir>/spool/`. A background task retries the
+// queue periodically; since the original focus is long gone, recovered text
+// goes to the clipboard or is appended to a file. Only failures that may go
+// away on their own are queued, and a recording that keeps failing is parked
+// instead of being re-sent forever. Code formatting, spoken commands and the
+// rewrite mode of the original dictation are applied to recovered text too.
+// `g-type queue` manages the spool by hand.
+
+use anyhow::{bail, Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::{Config, QueueConfig};
+use crate::encoder;
+use crate::error::{self, TranscribeError};
+use crate::network::Timeouts;
+use crate::rewrite::{Mode, Rewriter};
+use crate::tracking::TranscriptionRecord;
+use crate::transcriber::{self, TranscribeContext, Transcriber, Transcription};
+
+const SAMPLE_RATE: f64 = 16_000.0;
+/// Size of the header written by `encoder::encode_wav`.
+const WAV_HEADER_LEN: usize = 44;
+/// A claim left behind by a process that died mid-retry is taken over after
+/// this long — well past any retry's overall timeout.
+const STALE_CLAIM: Duration = Duration::from_secs(30 * 60);
+
+/// Metadata stored next to each spooled recording.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpoolEntry {
+    /// Time-ordered identifier, also the file stem.
+    pub id: String,
+    /// ISO 8601 timestamp (UTC) of the recording.
+    pub created_at: String,
+    pub duration_secs: f64,
+    /// Request context of the original dictation.
+    pub language: String,
+    pub prompt: String,
+    /// Why the most recent attempt failed.
+    pub last_error: String,
+    /// Transcription attempts so far, including the original one.
+    pub attempts: u32,
+    /// Set once background retries gave up on this entry; only
+    /// `g-type queue retry` sends it again.
+    #[serde(default)]
+    pub parked: bool,
+    /// Dictated in code mode: the result is formatted as code on delivery.
+    #[serde(default)]
+    pub code: bool,
+    /// Rewrite mode the dictation was started with, applied on delivery.
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Spoken commands are applied on delivery; keystroke commands have no
+    /// target any more and are dropped.
+    #[serde(default)]
+    pub spoken_commands: bool,
+}
+
+/// What the original dictation did with its transcription, redone when a
+/// queued entry is recovered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Processing {
+    pub code: bool,
+    pub mode: Option<String>,
+    pub spoken_commands: bool,
+}
+
+/// A directory of `<id>.wav` + `<id>.json` pairs.
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    /// The spool under the platform data directory.
+    pub fn open() -> Result<Self> {
+        let proj = ProjectDirs::from("", "", "g-type")
+            .context("Cannot determine home directory for the spool")?;
+        Ok(Self::at(proj.data_dir().join("spool")))
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Save a failed recording for later.
+    pub fn save(
+        &self,
+        samples: &[i16],
+        ctx: &TranscribeContext,
+        processing: &Processing,
+        err: &anyhow::Error,
+    ) -> Result<SpoolEntry> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Cannot create spool directory {}", self.dir.display()))?;
+
+        let entry = SpoolEntry {
+            id: new_id(),
+            created_at: crate::tracking::chrono_now_utc(),
+            duration_secs: samples.len() as f64 / SAMPLE_RATE,
+            language: ctx.language.clone(),
+            prompt: ctx.prompt.clone(),
+            last_error: format!("{err:#}"),
+            attempts: 1,
+            parked: false,
+            code: processing.code,
+            mode: processing.mode.clone(),
+            spoken_commands: processing.spoken_commands,
+        };
+        let audio = self.dir.join(format!("{}.wav", entry.id));
+        fs::write(&audio, encoder::encode_wav(samples))
+            .with_context(|| format!("Cannot write {}", audio.display()))?;
+        // Metadata last: an entry is only listed once its audio is complete.
+        self.write_meta(&entry)?;
+        debug!(id = %entry.id, "Recording saved to spool");
+        Ok(entry)
+    }
+
+    /// All queued entries, oldest first.
+    pub fn list(&self) -> Result<Vec<SpoolEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        let dir = fs::read_dir(&self.dir)
+            .with_context(|| format!("Cannot read spool directory {}", self.dir.display()))?;
+        for item in dir {
+            let path = item?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let parsed = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| Ok(serde_json::from_str::<SpoolEntry>(&raw)?));
+            match parsed {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!(%e, path = %path.display(), "Skipping unreadable spool entry"),
+            }
+        }
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    /// PCM samples of a queued recording.
+    pub fn load_audio(&self, entry: &SpoolEntry) -> Result<Vec<i16>> {
+        let path = self.dir.join(format!("{}.wav", entry.id));
+        let bytes = fs::read(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        if bytes.len() < WAV_HEADER_LEN || &bytes[0..4] != b"RIFF" {
+            bail!("{} is not a WAV file", path.display());
+        }
+        Ok(bytes[WAV_HEADER_LEN..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect())
+    }
+
+    /// Claim `entry` for a retry, returning it as currently stored. `None`
+    /// if another process is retrying it or it was removed since it was listed.
+    pub fn claim(&self, entry: &SpoolEntry) -> Result<Option<(Claim, SpoolEntry)>> {
+        let path = self.dir.join(format!("{}.lock", entry.id));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let age = fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok());
+                let stale = age.is_some_and(|age| age > STALE_CLAIM);
+                if !stale {
+                    return Ok(None);
+                }
+                warn!(id = %entry.id, "Taking over a stale claim on a queued dictation");
+                fs::write(&path, b"")
+                    .with_context(|| format!("Cannot write {}", path.display()))?;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Cannot create {}", path.display())),
+        }
+        let claim = Claim { path };
+        // Re-read: the entry may have been retried or dropped meanwhile.
+        let meta = self.dir.join(format!("{}.json", entry.id));
+        let Ok(raw) = fs::read_to_string(&meta) else {
+            return Ok(None);
+        };
+        let entry = serde_json::from_str(&raw)
+            .with_context(|| format!("Cannot parse {}", meta.display()))?;
+        Ok(Some((claim, entry)))
+    }
+
+    /// Delete an entry. Unknown IDs are an error.
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let meta = self.dir.join(format!("{id}.json"));
+        if !meta.exists() {
+            bail!("No queued dictation with ID {id}");
+        }
+        fs::remove_file(&meta).with_context(|| format!("Cannot delete {}", meta.display()))?;
+        let _ = fs::remove_file(self.dir.join(format!("{id}.wav")));
+        Ok(())
+    }
+
+    /// Store a failed attempt on `entry`, unless it was dropped with
+    /// `g-type queue drop` meanwhile. Only logged if that fails: the entry
+    /// is then retried as it was.
+    fn save_failure(&self, entry: &SpoolEntry) {
+        if !self.dir.join(format!("{}.json", entry.id)).exists() {
+            return;
+        }
+        if let Err(e) = self.write_meta(entry) {
+            warn!(id = %entry.id, error = %format!("{e:#}"), "Cannot update queued dictation");
+        }
+    }
+
+    fn write_meta(&self, entry: &SpoolEntry) -> Result<()> {
+        let path = self.dir.join(format!("{}.json", entry.id));
+        let json =
+            serde_json::to_string_pretty(entry).context("Failed to serialize spool entry")?;
+        fs::write(&path, json).with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+/// Exclusive hold on a queued entry while it is retried, so the daemon and
+/// `g-type queue retry` never send (and deliver) the same recording twice.
+/// Released when dropped.
+pub struct Claim {
+    path: PathBuf,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Milliseconds since the epoch, zero-padded so IDs sort chronologically,
+/// then the process ID and a counter so recordings saved in the same
+/// millisecond never share an ID.
+fn new_id() -> String {
+    static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed) % 10_000;
+    format!("{millis:013}-{}-{sequence:04}", std::process::id())
+}
+
+/// Whether a failed dictation is worth queueing: only failures that may go
+/// away on their own (offline, timeout, rate limit, quota, 5xx). A refusal,
+/// a rejected key or a truncated reply would be repeated — and billed —
+/// on every retry.
+pub fn should_spool(err: &anyhow::Error) -> bool {
+    match error::find(err) {
+        Some(e @ TranscribeError::Server { .. }) => e.is_transient(),
+        Some(e) => matches!(
+            e,
+            TranscribeError::Network { .. }
+                | TranscribeError::Timeout
+                | TranscribeError::RateLimited { .. }
+                | TranscribeError::Quota { .. }
+        ),
+        None => false,
+    }
+}
+
+/// Whether a retry failure means the rest of the queue would fail too
+/// (offline, throttled, bad credentials) rather than just this recording.
+fn blocks_queue(err: &anyhow::Error) -> bool {
+    !matches!(
+        error::find(err),
+        Some(TranscribeError::SafetyBlocked { .. } | TranscribeError::Truncated)
+    )
+}
+
+/// Where recovered text goes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    /// With `hold`, delivery waits until another app owns the clipboard
+    /// (see `injector::copy_to_clipboard`) — for `g-type queue retry`, which
+    /// exits right after.
+    Clipboard { hold: bool },
+    /// Appended, one line per dictation.
+    File(PathBuf),
+}
+
+impl Delivery {
+    pub fn from_config(queue: &QueueConfig) -> Result<Self> {
+        match queue.deliver.as_str() {
+            "clipboard" => Ok(Delivery::Clipboard { hold: false }),
+            "file" if !queue.output_file.is_empty() => {
+                Ok(Delivery::File(PathBuf::from(&queue.output_file)))
+            }
+            "file" => {
+                let proj = ProjectDirs::from("", "", "g-type")
+                    .context("Cannot determine home directory for queue output")?;
+                Ok(Delivery::File(
+                    proj.data_dir().join("queued-dictations.txt"),
+                ))
+            }
+            other => bail!("Unknown queue.deliver '{other}'. Available: clipboard, file"),
+        }
+    }
+
+    /// The same delivery for a short-lived process: clipboard text is held
+    /// until another app takes it over.
+    pub fn held(self) -> Self {
+        match self {
+            Delivery::Clipboard { .. } => Delivery::Clipboard { hold: true },
+            file => file,
+        }
+    }
+
+    /// Hand over recovered dictations. The clipboard receives them all at
+    /// once, separated by blank lines, so none overwrites another.
+    pub async fn deliver(&self, recovered: &[Recovered]) -> Result<()> {
+        match self {
+            Delivery::Clipboard { hold } => {
+                let texts: Vec<&str> = recovered.iter().map(|r| r.text.as_str()).collect();
+                if *hold && cfg!(target_os = "linux") {
+                    for text in &texts {
+                        println!("  {text}");
+                    }
+                    println!();
+                    println!("  Copied to the clipboard. Paste it before copying anything else;");
+                    println!(
+                        "  the queue entries are removed once another app takes the clipboard."
+                    );
+                }
+                // Holding blocks until another app takes the clipboard.
+                let (text, hold) = (texts.join("\n\n"), *hold);
+                tokio::task::spawn_blocking(move || crate::injector::copy_to_clipboard(&text, hold))
+                    .await
+                    .context("Clipboard task panicked")?
+            }
+            Delivery::File(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).ok();
+                }
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Cannot open {}", path.display()))?;
+                for r in recovered {
+                    writeln!(file, "[{}] {}", r.entry.created_at, r.text)
+                        .with_context(|| format!("Cannot write {}", path.display()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Delivery::Clipboard { .. } => "copied to the clipboard".into(),
+            Delivery::File(path) => format!("appended to {}", path.display()),
+        }
+    }
+}
+
+/// Which entries a pass over the queue sends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope<'a> {
+    /// The daemon's periodic retry: everything except parked entries.
+    Background,
+    /// `g-type queue retry`: every entry, parked or not.
+    All,
+    /// `g-type queue retry ID`.
+    One(&'a str),
+}
+
+/// A queued dictation transcribed on retry.
+#[derive(Debug)]
+pub struct Recovered {
+    pub entry: SpoolEntry,
+    pub transcription: Transcription,
+    /// Usage of the rewrite call, for entries with a rewrite mode.
+    pub rewrite: Option<TranscriptionRecord>,
+    /// Set when the rewrite mode failed and the transcription is delivered
+    /// as it is, as the original dictation would have typed it.
+    pub rewrite_failed: bool,
+    /// What is delivered.
+    pub text: String,
+}
+
+/// Outcome of one pass over the queue.
+#[derive(Debug, Default)]
+pub struct RetrySummary {
+    /// Transcribed, delivered and removed from the spool.
+    pub recovered: Vec<Recovered>,
+    /// Still queued after a failed attempt.
+    pub failed: usize,
+}
+
+/// Transcribe the queued entries in `scope` oldest first and deliver the
+/// results. Stops at the first failure that would affect every entry. An
+/// entry that has failed `max_attempts` times, or failed for good, is parked.
+pub async fn retry(
+    spool: &Spool,
+    transcriber: &dyn Transcriber,
+    delivery: &Delivery,
+    config: &Config,
+    scope: Scope<'_>,
+    max_attempts: u32,
+) -> Result<RetrySummary> {
+    let timeouts = Timeouts::from_config(config);
+    let mut entries = spool.list()?;
+    if let Scope::One(id) = scope {
+        entries.retain(|e| e.id == id);
+        if entries.is_empty() {
+            bail!("No queued dictation with ID {id}");
+        }
+    }
+
+    let mut summary = RetrySummary::default();
+    // Held until the results are delivered and the entries removed.
+    let mut claims = Vec::new();
+    // A problem with one entry is that entry's failure: the pass goes on, and
+    // whatever was recovered (and billed) is still delivered.
+    for entry in entries {
+        let (claim, mut entry) = match spool.claim(&entry) {
+            Ok(Some(claimed)) => claimed,
+            Ok(None) => {
+                debug!(id = %entry.id, "Queued dictation is being retried elsewhere");
+                continue;
+            }
+            Err(e) => {
+                warn!(id = %entry.id, error = %format!("{e:#}"), "Cannot claim queued dictation");
+                summary.failed += 1;
+                continue;
+            }
+        };
+        if entry.parked && scope == Scope::Background {
+            continue;
+        }
+        claims.push(claim);
+        let samples = match spool.load_audio(&entry) {
+            Ok(samples) => samples,
+            Err(e) => {
+                // A missing or damaged recording fails the same way every time.
+                warn!(id = %entry.id, error = %format!("{e:#}"), "Queued dictation parked");
+                entry.last_error = format!("{e:#}");
+                entry.parked = true;
+                spool.save_failure(&entry);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        let ctx = TranscribeContext {
+            language: entry.language.clone(),
+            prompt: entry.prompt.clone(),
+        };
+        let deadline = timeouts.overall(samples.len());
+        let started = std::time::Instant::now();
+        match transcriber::with_deadline(deadline, transcriber.transcribe(&samples, &ctx)).await {
+            Ok(result) => {
+                let remaining = deadline.saturating_sub(started.elapsed());
+                let recovered = process(config, entry, result, samples.len(), remaining).await;
+                summary.recovered.push(recovered);
+            }
+            Err(e) => {
+                debug!(id = %entry.id, error = %format!("{e:#}"), "Queued dictation failed again");
+                entry.attempts += 1;
+                entry.last_error = format!("{e:#}");
+                entry.parked = !should_spool(&e) || entry.attempts >= max_attempts;
+                if entry.parked {
+                    warn!(
+                        id = %entry.id,
+                        attempts = entry.attempts,
+                        "Queued dictation parked; `g-type queue retry` sends it again"
+                    );
+                }
+                spool.save_failure(&entry);
+                summary.failed += 1;
+                if blocks_queue(&e) {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Deliver before deleting: if delivery fails, the audio is kept.
+    summary.recovered.retain(|r| {
+        if r.text.is_empty() {
+            let _ = spool.remove(&r.entry.id);
+        }
+        !r.text.is_empty()
+    });
+    if !summary.recovered.is_empty() {
+        delivery.deliver(&summary.recovered).await?;
+        for r in &summary.recovered {
+            if let Err(e) = spool.remove(&r.entry.id) {
+                warn!(id = %r.entry.id, error = %format!("{e:#}"), "Cannot remove delivered dictation");
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Redo what the original dictation did with its transcription: format it
+/// as code, apply spoken commands or run its rewrite mode within `deadline`.
+async fn process(
+    config: &Config,
+    entry: SpoolEntry,
+    transcription: Transcription,
+    samples: usize,
+    deadline: Duration,
+) -> Recovered {
+    let mut recovered = Recovered {
+        text: transcription.text.clone(),
+        entry,
+        transcription,
+        rewrite: None,
+        rewrite_failed: false,
+    };
+    let (entry, raw) = (&recovered.entry, &recovered.transcription);
+    if raw.text.is_empty() {
+        return recovered;
+    }
+    if entry.code {
+        recovered.text = crate::code::format(&raw.text);
+    } else if entry.spoken_commands {
+        let language = raw.detected_language.as_deref().unwrap_or(&entry.language);
+        match crate::commands::Vocabulary::for_language(config, language) {
+            Ok(vocabulary) => {
+                recovered.text = crate::commands::typed_text(&vocabulary.apply(&raw.text))
+            }
+            Err(e) => {
+                warn!(id = %entry.id, error = %format!("{e:#}"), "Spoken commands not applied")
+            }
+        }
+    } else if let Some(name) = &entry.mode {
+        let rewritten = async {
+            let mode = Mode::resolve(config, name)?;
+            let rewriter = Rewriter::from_config(config)?;
+            // Nothing is typed, so the streamed reply goes nowhere.
+            let (deltas, _rx) = tokio::sync::mpsc::unbounded_channel();
+            let rewrite = rewriter.rewrite(&mode, &raw.text, samples, &deltas);
+            let result = transcriber::with_deadline(deadline, rewrite).await;
+            anyhow::Ok((mode, result?))
+        };
+        match rewritten.await {
+            Ok((mode, r)) if !r.text.is_empty() => {
+                let mut record =
+                    crate::tracking::build_rewrite_record(&r.model, &mode.name, &r.usage, &r.text);
+                if mode.target_language.is_some() {
+                    record.source_language = raw.detected_language.clone().or_else(|| {
+                        (!matches!(entry.language.as_str(), "auto" | ""))
+                            .then(|| entry.language.clone())
+                    });
+                    record.target_language = mode.target_language.clone();
+                }
+                recovered.rewrite = Some(record);
+                recovered.text = r.text;
+            }
+            Ok(_) => {
+                warn!(id = %entry.id, mode = %name, "Rewrite came back empty, delivering the transcription");
+                recovered.rewrite_failed = true;
+            }
+            Err(e) => {
+                warn!(id = %entry.id, mode = %name, error = %format!("{e:#}"), "Rewrite failed, delivering the transcription");
+                recovered.rewrite_failed = true;
+            }
+        }
+    }
+    recovered
+}
+
+/// Record usage for recovered dictations, as for live ones.
+fn track(summary: &RetrySummary) {
+    for r in &summary.recovered {
+        let t = &r.transcription;
+        let record = crate::tracking::build_record(
+            &t.model,
+            r.entry.duration_secs,
+            &t.usage,
+            &t.text,
+            t.detected_language.as_deref(),
+        );
+        for record in std::iter::once(&record).chain(&r.rewrite) {
+            if let Err(e) = crate::tracking::append_record(record) {
+                warn!(%e, "Failed to save tracking record (non-fatal)");
+            }
+        }
+    }
+}
+
+/// Tells which recovered dictations lost their rewrite mode, if any.
+fn rewrite_note(summary: &RetrySummary) -> String {
+    match summary
+        .recovered
+        .iter()
+        .filter(|r| r.rewrite_failed)
+        .count()
+    {
+        0 => String::new(),
+        n => format!(" {n} could not be rewritten and are as transcribed."),
+    }
+}
+
+/// Retry the queue every `retry_interval_secs` for the daemon's lifetime.
+/// A failed pass simply waits for the next tick — that is how connectivity
+/// coming back is noticed.
+pub fn spawn_retry_loop(config: &Config, transcriber: Arc<dyn Transcriber>) -> Result<()> {
+    let spool = Spool::open()?;
+    let delivery = Delivery::from_config(&config.queue)?;
+    let config = config.clone();
+    let interval = Duration::from_secs(config.queue.retry_interval_secs.max(5));
+    let notifications = config.notifications;
+    let max_attempts = config.queue.max_attempts.max(1);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if spool.list().map(|e| e.is_empty()).unwrap_or(true) {
+                continue;
+            }
+            let pass = retry(
+                &spool,
+                transcriber.as_ref(),
+                &delivery,
+                &config,
+                Scope::Background,
+                max_attempts,
+            );
+            match pass.await {
+                Ok(summary) if !summary.recovered.is_empty() => {
+                    track(&summary);
+                    let message = format!(
+                        "Recovered {} queued dictation(s) — {}.{}",
+                        summary.recovered.len(),
+                        delivery.describe(),
+                        rewrite_note(&summary)
+                    );
+                    info!("📬 {message}");
+                    if notifications {
+                        crate::notify::show(&message);
+                    }
+                }
+                Ok(_) => debug!("Queued dictations still failing, will retry"),
+                Err(e) => warn!(error = %format!("{e:#}"), "Queue retry failed"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// `g-type queue list|retry [ID]|drop ID|all`.
+pub async fn run_command(args: &[String]) -> Result<()> {
+    let spool = Spool::open()?;
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            let entries = spool.list()?;
+            println!();
+            if entries.is_empty() {
+                println!("  No queued dictations.");
+                println!();
+                return Ok(());
+            }
+            println!("  \x1b[1m📥 Queued dictations ({}):\x1b[0m", entries.len());
+            println!();
+            for e in &entries {
+                println!(
+                    "  {}  {}  {:>6.1}s  {} attempt(s){}",
+                    e.id,
+                    e.created_at,
+                    e.duration_secs,
+                    e.attempts,
+                    if e.parked { ", parked" } else { "" }
+                );
+                println!("     \x1b[2m{}\x1b[0m", e.last_error);
+            }
+            println!();
+        }
+        Some("retry") => {
+            let config = crate::config::load()?;
+            let transcriber = crate::transcriber::from_config(&config)?;
+            // Clipboard text on Linux lives only as long as this process.
+            let delivery = Delivery::from_config(&config.queue)?.held();
+            let scope = args.get(1).map_or(Scope::All, |id| Scope::One(id));
+            let max_attempts = config.queue.max_attempts.max(1);
+            let summary = retry(
+                &spool,
+                transcriber.as_ref(),
+                &delivery,
+                &config,
+                scope,
+                max_attempts,
+            )
+            .await?;
+            track(&summary);
+
+            println!();
+            for r in &summary.recovered {
+                println!("  \x1b[32m✔\x1b[0m {}  {}", r.entry.id, r.text);
+            }
+            println!(
+                "  Recovered {}, still queued {}.",
+                summary.recovered.len(),
+                spool.list()?.len()
+            );
+            if !summary.recovered.is_empty() {
+                println!("  Text {}.{}", delivery.describe(), rewrite_note(&summary));
+            }
+            println!();
+        }
+        Some("drop") => match args.get(1).map(String::as_str) {
+            Some("all") => {
+                let entries = spool.list()?;
+                for e in &entries {
+                    spool.remove(&e.id)?;
+                }
+                println!("  Dropped {} queued dictation(s).", entries.len());
+            }
+            Some(id) => {
+                spool.remove(id)?;
+                println!("  Dropped {id}.");
+            }
+            None => bail!("Usage: g-type queue drop <ID|all>"),
+        },
+        Some(other) => bail!("Unknown queue command '{other}'. Use list, retry or drop"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn temp_spool(name: &str) -> Spool {
+        let dir = std::env::temp_dir().join(format!("g-type-spool-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Spool::at(dir)
+    }
+
+    fn ctx() -> TranscribeContext {
+        TranscribeContext {
+            language: "it".into(),
+            prompt: "Trascrivi.".into(),
+        }
+    }
+
+    fn offline() -> anyhow::Error {
+        TranscribeError::network("dns error: failed to lookup address").into()
+    }
+
+    /// Fails with a network error until `online` is set.
+    struct FlakyTranscriber {
+        online: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Transcriber for FlakyTranscriber {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn transcribe(
+            &self,
+            samples: &[i16],
+            ctx: &TranscribeContext,
+        ) -> Result<Transcription> {
+            if !self.online.load(Ordering::SeqCst) {
+                return Err(offline());
+            }
+            Ok(Transcription {
+                text: format!("{} samples in {}", samples.len(), ctx.language),
+                model: "flaky-1".into(),
+                ..Transcription::default()
+            })
+        }
+    }
+
+    /// Always answers with the same text.
+    struct CannedTranscriber(&'static str);
+
+    #[async_trait]
+    impl Transcriber for CannedTranscriber {
+        fn name(&self) -> &'static str {
+            "canned"
+        }
+
+        async fn transcribe(&self, _: &[i16], _: &TranscribeContext) -> Result<Transcription> {
+            Ok(Transcription {
+                text: self.0.into(),
+                model: "canned-1".into(),
+                ..Transcription::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_save_list_load_remove() {
+        let spool = temp_spool("roundtrip");
+        let samples: Vec<i16> = (0..3200).map(|i| (i % 200) as i16 - 100).collect();
+        let saved = spool
+            .save(&samples, &ctx(), &Processing::default(), &offline())
+            .unwrap();
+        assert_eq!(saved.attempts, 1);
+        assert!((saved.duration_secs - 0.2).abs() < 1e-9);
+        assert!(saved.last_error.contains("network error"));
+
+        let listed = spool.list().unwrap();
+        assert_eq!(listed, vec![saved.clone()]);
+        assert_eq!(spool.load_audio(&saved).unwrap(), samples);
+
+        spool.remove(&saved.id).unwrap();
+        assert!(spool.list().unwrap().is_empty());
+        assert!(spool.remove(&saved.id).is_err());
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_waits_for_connectivity_then_delivers() {
+        let spool = temp_spool("retry");
+        let out = spool.dir.join("out.txt");
+        let delivery = Delivery::File(out.clone());
+        spool
+            .save(&[1i16; 1600], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+        spool
+            .save(&[1i16; 3200], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+
+        let config = Config::default();
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(false),
+        };
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::Background, 5)
+            .await
+            .unwrap();
+        assert!(summary.recovered.is_empty());
+        // Offline: the pass stops after the first entry.
+        assert_eq!(summary.failed, 1);
+        let entries = spool.list().unwrap();
+        assert_eq!(entries[0].attempts, 2);
+        assert_eq!(entries[1].attempts, 1);
+
+        flaky.online.store(true, Ordering::SeqCst);
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::Background, 5)
+            .await
+            .unwrap();
+        assert_eq!(summary.recovered.len(), 2);
+        assert!(spool.list().unwrap().is_empty());
+
+        let written = fs::read_to_string(&out).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] 1600 samples in it"));
+        assert!(lines[1].ends_with("] 3200 samples in it"));
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_damaged_entry_does_not_block_the_pass() {
+        let spool = temp_spool("damaged");
+        let out = spool.dir.join("out.txt");
+        let damaged = spool
+            .save(&[1i16; 1600], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+        fs::write(spool.dir.join(format!("{}.wav", damaged.id)), b"junk").unwrap();
+        spool
+            .save(&[1i16; 3200], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(true),
+        };
+        let config = Config::default();
+        let delivery = Delivery::File(out.clone());
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::Background, 5)
+            .await
+            .unwrap();
+        assert_eq!((summary.recovered.len(), summary.failed), (1, 1));
+        assert!(fs::read_to_string(&out)
+            .unwrap()
+            .trim_end()
+            .ends_with("] 3200 samples in it"));
+
+        let left = spool.list().unwrap();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].parked);
+        assert!(left[0].last_error.contains("not a WAV file"));
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_code_entries_are_formatted() {
+        let spool = temp_spool("code");
+        let out = spool.dir.join("out.txt");
+        let processing = Processing {
+            code: true,
+            ..Processing::default()
+        };
+        spool
+            .save(&[1i16; 1600], &ctx(), &processing, &offline())
+            .unwrap();
+
+        let canned = CannedTranscriber(
+            "Let snake case max retries equals open paren close paren semicolon.",
+        );
+        let config = Config::default();
+        let delivery = Delivery::File(out.clone());
+        retry(&spool, &canned, &delivery, &config, Scope::Background, 5)
+            .await
+            .unwrap();
+        let written = fs::read_to_string(&out).unwrap();
+        assert!(written.trim_end().ends_with("] let max_retries = ();"));
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_mode_is_applied() {
+        use crate::test_support::{GeminiReply, MockGemini};
+
+        let mock = MockGemini::start(GeminiReply::Text("Ciao a tutti.".into())).await;
+        let spool = temp_spool("rewrite");
+        let processing = Processing {
+            mode: Some("grammar".into()),
+            ..Processing::default()
+        };
+        spool
+            .save(&[1i16; 1600], &ctx(), &processing, &offline())
+            .unwrap();
+
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(true),
+        };
+        let delivery = Delivery::File(spool.dir.join("out.txt"));
+        let summary = retry(&spool, &flaky, &delivery, &mock.config(), Scope::All, 5)
+            .await
+            .unwrap();
+        let recovered = &summary.recovered[0];
+        assert_eq!(recovered.text, "Ciao a tutti.");
+        assert_eq!(recovered.transcription.text, "1600 samples in it");
+        let record = recovered.rewrite.as_ref().unwrap();
+        assert_eq!(record.rewrite_mode.as_deref(), Some("grammar"));
+        assert!(mock.requests()[0]
+            .body_text()
+            .contains("1600 samples in it"));
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_rewrite_delivers_transcription() {
+        let spool = temp_spool("rewrite-failed");
+        let processing = Processing {
+            mode: Some("grammar".into()),
+            ..Processing::default()
+        };
+        spool
+            .save(&[1i16; 1600], &ctx(), &processing, &offline())
+            .unwrap();
+
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(true),
+        };
+        // No API key: rewrite modes are unavailable.
+        let config = Config::default();
+        let delivery = Delivery::File(spool.dir.join("out.txt"));
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::All, 5)
+            .await
+            .unwrap();
+        let recovered = &summary.recovered[0];
+        assert_eq!(recovered.text, "1600 samples in it");
+        assert!(recovered.rewrite_failed && recovered.rewrite.is_none());
+        assert!(rewrite_note(&summary).contains("1 could not be rewritten"));
+        assert!(spool.list().unwrap().is_empty());
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spoken_commands_are_applied() {
+        let spool = temp_spool("commands");
+        let ctx = TranscribeContext {
+            language: "en".into(),
+            ..ctx()
+        };
+        let processing = Processing {
+            spoken_commands: true,
+            ..Processing::default()
+        };
+        let entry = spool
+            .save(&[1i16; 1600], &ctx, &processing, &offline())
+            .unwrap();
+        let transcription = Transcription {
+            text: "Hi comma all period new line select all bye".into(),
+            ..Transcription::default()
+        };
+        let recovered = process(
+            &Config::default(),
+            entry,
+            transcription,
+            1600,
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(recovered.text, "Hi, all.\nBye");
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_parks_failing_entries() {
+        let spool = temp_spool("park");
+        let delivery = Delivery::File(spool.dir.join("out.txt"));
+        let config = Config::default();
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(false),
+        };
+        let saved = spool
+            .save(&[1i16; 1600], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+
+        retry(&spool, &flaky, &delivery, &config, Scope::Background, 3)
+            .await
+            .unwrap();
+        assert!(!spool.list().unwrap()[0].parked);
+        retry(&spool, &flaky, &delivery, &config, Scope::Background, 3)
+            .await
+            .unwrap();
+        let parked = &spool.list().unwrap()[0];
+        assert_eq!(parked.attempts, 3);
+        assert!(parked.parked);
+
+        // The daemon leaves it alone; an explicit retry still sends it.
+        flaky.online.store(true, Ordering::SeqCst);
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::Background, 3)
+            .await
+            .unwrap();
+        assert!(summary.recovered.is_empty() && summary.failed == 0);
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::One(&saved.id), 3)
+            .await
+            .unwrap();
+        assert_eq!(summary.recovered.len(), 1);
+        assert!(spool.list().unwrap().is_empty());
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_claimed_entry_is_not_sent_twice() {
+        let spool = temp_spool("claim");
+        let delivery = Delivery::File(spool.dir.join("out.txt"));
+        let config = Config::default();
+        let flaky = FlakyTranscriber {
+            online: AtomicBool::new(true),
+        };
+        let saved = spool
+            .save(&[1i16; 1600], &ctx(), &Processing::default(), &offline())
+            .unwrap();
+
+        // Another process is retrying the entry.
+        let (claim, entry) = spool.claim(&saved).unwrap().unwrap();
+        assert_eq!(entry, saved);
+        assert!(spool.claim(&saved).unwrap().is_none());
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::All, 5)
+            .await
+            .unwrap();
+        assert!(summary.recovered.is_empty());
+
+        drop(claim);
+        let summary = retry(&spool, &flaky, &delivery, &config, Scope::All, 5)
+            .await
+            .unwrap();
+        assert_eq!(summary.recovered.len(), 1);
+        // Gone after delivery: a late claim finds nothing.
+        assert!(spool.claim(&saved).unwrap().is_none());
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+
+    #[test]
+    fn test_new_ids_are_unique() {
+        let ids: std::collections::HashSet<String> = (0..1000).map(|_| new_id()).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+
+    #[test]
+    fn test_should_spool() {
+        assert!(should_spool(&offline()));
+        let quota: anyhow::Error = TranscribeError::from_http(429, "current quota").into();
+        assert!(should_spool(&quota));
+        let unavailable: anyhow::Error = TranscribeError::from_http(503, "overloaded").into();
+        assert!(should_spool(&unavailable));
+        for permanent in [
+            TranscribeError::SafetyBlocked {
+                reason: "SAFETY".into(),
+            },
+            TranscribeError::Truncated,
+            TranscribeError::from_http(403, "API key not valid"),
+            TranscribeError::from_http(400, "Invalid JSON payload"),
+        ] {
+            assert!(!should_spool(&permanent.into()));
+        }
+        assert!(!should_spool(&anyhow::anyhow!("malformed reply")));
+    }
+
+    #[test]
+    fn test_delivery_from_config() {
+        let mut queue = QueueConfig::default();
+        assert_eq!(
+            Delivery::from_config(&queue).unwrap(),
+            Delivery::Clipboard { hold: false }
+        );
+        assert_eq!(
+            Delivery::from_config(&queue).unwrap().held(),
+            Delivery::Clipboard { hold: true }
+        );
+        queue.deliver = "file".into();
+        queue.output_file = "/tmp/dictations.txt".into();
+        assert_eq!(
+            Delivery::from_config(&queue).unwrap(),
+            Delivery::File("/tmp/dictations.txt".into())
+        );
+        queue.deliver = "email".into();
+        assert!(Delivery::from_config(&queue).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+// streaming.rs — Server-Sent Events support for Gemini `streamGenerateContent`.
+// Each SSE `data:` payload is a full generateContent-shaped JSON chunk; we pull
+// the text parts out as they arrive and forward them for progressive injection.
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, warn};
+
+use crate::error::TranscribeError;
+use crate::tracking::TokenUsage;
+use crate::transcriber::{with_partial, Transcription};
+
+/// Incremental SSE parser. Feed raw body bytes, get complete `data:` payloads.
+/// Buffers bytes (not text) so multi-byte characters split across network
+/// chunks are decoded intact.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    /// Append a body chunk and return every event payload completed by it.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some((end, sep_len)) = event_boundary(&self.buffer) {
+            let raw: Vec<u8> = self.buffer.drain(..end + sep_len).collect();
+            let raw = String::from_utf8_lossy(&raw[..end]);
+            let data: Vec<&str> = raw
+                .lines()
+                .filter_map(|l| l.strip_prefix("data:"))
+                .map(|d| d.strip_prefix(' ').unwrap_or(d))
+                .collect();
+            if !data.is_empty() {
+                events.push(data.join("\n"));
+            }
+        }
+        events
+    }
+}
+
+/// Position and length of the first blank-line event separator, if any.
+fn event_boundary(buf: &[u8]) -> Option<(usize, usize)> {
+    let lf = buf.windows(2).position(|w| w == b"\n\n").map(|p| (p, 2));
+    let crlf = buf
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|p| (p, 4));
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(if a.0 < b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Keeps streamed text tidy: drops leading whitespace of the whole response
+/// and holds back trailing whitespace until more text follows, so the typed
+/// output matches the trimmed final transcription.
+#[derive(Debug, Default)]
+pub struct DeltaTrimmer {
+    started: bool,
+    pending_ws: String,
+}
+
+impl DeltaTrimmer {
+    /// Return the text that is safe to type now (may be empty).
+    pub fn push(&mut self, delta: &str) -> String {
+        let delta = if self.started {
+            delta
+        } else {
+            delta.trim_start()
+        };
+        if delta.is_empty() {
+            return String::new();
+        }
+        self.started = true;
+
+        let body = delta.trim_end();
+        let trailing = &delta[body.len()..];
+        if body.is_empty() {
+            self.pending_ws.push_str(trailing);
+            return String::new();
+        }
+
+        let out = format!("{}{}", std::mem::take(&mut self.pending_ws), body);
+        self.pending_ws.push_str(trailing);
+        out
+    }
+}
+
+/// Extract the text parts of a single streamed chunk (untrimmed).
+pub fn chunk_text(chunk: &Value) -> String {
+    let mut text = String::new();
+    if let Some(parts) = chunk
+        .pointer("/candidates/0/content/parts")
+        .and_then(|p| p.as_array())
+    {
+        for part in parts {
+            if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                text.push_str(t);
+            }
+        }
+    }
+    text
+}
+
+/// Read an SSE response body to the end, forwarding text deltas.
+///
+/// Returns the full trimmed text and the last `usageMetadata` seen
+/// (Gemini reports cumulative usage, so the last chunk is authoritative).
+/// An in-stream `error` event or a blocking `finishReason` is returned as a
+/// `TranscribeError` once the stream ends; if text had been forwarded by
+/// then, it is attached as a `transcriber::Partial` from `model`.
+pub async fn read_stream(
+    mut response: reqwest::Response,
+    deltas: &UnboundedSender<String>,
+    extract_usage: fn(&Value) -> TokenUsage,
+    model: &str,
+) -> Result<(String, TokenUsage)> {
+    let mut parser = SseParser::default();
+    let mut trimmer = DeltaTrimmer::default();
+    let mut full_text = String::new();
+    let mut usage = TokenUsage::default();
+    let mut failure = None;
+
+    loop {
+        let bytes = match response.chunk().await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => break,
+            Err(e) => {
+                failure = Some(
+                    anyhow::Error::new(TranscribeError::request(e.is_timeout(), e))
+                        .context("Failed to read streamed response"),
+                );
+                break;
+            }
+        };
+        for event in parser.feed(&bytes) {
+            let chunk: Value = match serde_json::from_str(&event) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(%e, "Skipping malformed SSE chunk");
+                    continue;
+                }
+            };
+
+            if let Some(error) = chunk.get("error") {
+                failure = Some(TranscribeError::from_api_error(error).into());
+                continue;
+            }
+            if chunk.get("usageMetadata").is_some() {
+                usage = extract_usage(&chunk);
+            }
+            if let Some(reason) = chunk
+                .pointer("/candidates/0/finishReason")
+                .and_then(|r| r.as_str())
+            {
+                if let Some(err) = TranscribeError::from_finish_reason(reason) {
+                    failure = Some(err.into());
+                } else if reason != "STOP" {
+                    warn!(reason, "Gemini stream had non-STOP finish reason");
+                }
+            }
+
+            let delta = chunk_text(&chunk);
+            full_text.push_str(&delta);
+            let ready = trimmer.push(&delta);
+            if !ready.is_empty() {
+                debug!(len = ready.len(), "Streamed delta");
+                // Receiver gone means injection was abandoned — keep reading for usage.
+                let _ = deltas.send(ready);
+            }
+        }
+    }
+
+    if let Some(err) = failure {
+        let sent = Transcription {
+            text: full_text.trim().to_string(),
+            usage,
+            model: model.to_string(),
+            ..Transcription::default()
+        };
+        return Err(with_partial(err, sent));
+    }
+    Ok((full_text.trim().to_string(), usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sse_parser_split_across_chunks() {
+        let mut p = SseParser::default();
+        assert!(p.feed(b"data: {\"a\":").is_empty());
+        let events = p.feed(b"1}\n\ndata: {\"b\":2}\n\n");
+        assert_eq!(events, vec!["{\"a\":1}", "{\"b\":2}"]);
+    }
+
+    #[test]
+    fn test_sse_parser_crlf_and_comments() {
+        let mut p = SseParser::default();
+        let events = p.feed(b": keep-alive\r\n\r\ndata: {\"x\":true}\r\n\r\n");
+        assert_eq!(events, vec!["{\"x\":true}"]);
+    }
+
+    #[test]
+    fn test_sse_parser_multibyte_split() {
+        let mut p = SseParser::default();
+        let payload = "data: perché\n\n".as_bytes();
+        // Split inside the two-byte 'é'.
+        let cut = payload.len() - 3;
+        assert!(p.feed(&payload[..cut]).is_empty());
+        assert_eq!(p.feed(&payload[cut..]), vec!["perché"]);
+    }
+
+    #[test]
+    fn test_delta_trimmer() {
+        let mut t = DeltaTrimmer::default();
+        assert_eq!(t.push("  \n"), "");
+        assert_eq!(t.push(" Hello "), "Hello");
+        assert_eq!(t.push("world"), " world");
+        assert_eq!(t.push(".\n"), ".");
+        // Trailing newline is never emitted if nothing follows.
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_forwards_deltas() {
+        use crate::test_support::{StubResponse, StubServer};
+
+        let sse = concat!(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\" Hello\"}]}}]}\r\n\r\n",
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\" world \"}]},\"finishReason\":\"STOP\"}],",
+            "\"usageMetadata\":{\"promptTokenCount\":40,\"candidatesTokenCount\":2}}\r\n\r\n",
+        );
+        let server = StubServer::start(move |_| StubResponse::text(200, sse)).await;
+        let response = reqwest::get(&server.url).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let usage_of = |v: &Value| TokenUsage {
+            prompt_tokens: v["usageMetadata"]["promptTokenCount"].as_u64().unwrap_or(0),
+            candidates_tokens: v["usageMetadata"]["candidatesTokenCount"]
+                .as_u64()
+                .unwrap_or(0),
+            thoughts_tokens: 0,
+            total_tokens: 0,
+        };
+        let (text, usage) = read_stream(response, &tx, usage_of, "m").await.unwrap();
+        drop(tx);
+
+        assert_eq!(text, "Hello world");
+        assert_eq!(usage.prompt_tokens, 40);
+        assert_eq!(usage.candidates_tokens, 2);
+
+        let mut typed = String::new();
+        while let Some(d) = rx.recv().await {
+            typed.push_str(&d);
+        }
+        assert_eq!(typed, text);
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_truncated() {
+        use crate::test_support::{StubResponse, StubServer};
+
+        let sse = "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]},\"finishReason\":\"MAX_TOKENS\"}]}\n\n";
+        let server = StubServer::start(move |_| StubResponse::text(200, sse)).await;
+        let response = reqwest::get(&server.url).await.unwrap();
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let err = read_stream(response, &tx, |_| TokenUsage::default(), "m")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Truncated));
+        let sent = crate::transcriber::partial(&err).unwrap();
+        assert_eq!((sent.text.as_str(), sent.model.as_str()), ("Hel", "m"));
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        let chunk = json!({
+            "candidates": [{"content": {"parts": [{"text": "ciao "}, {"text": "mondo"}]}}]
+        });
+        assert_eq!(chunk_text(&chunk), "ciao mondo");
+        assert_eq!(chunk_text(&json!({})), "");
+    }
+}
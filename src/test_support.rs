@@ -0,0 +1,443 @@
+// test_support.rs — Minimal in-process HTTP and WebSocket stubs for backend tests.
+// Binds 127.0.0.1 on an ephemeral port, records every request and answers
+// with whatever the test's handler returns. HTTP/1.1 only, one request per
+// connection (`Connection: close`) — enough for reqwest-based clients.
+// `MockGemini` and `MockInjector` build on these for end-to-end pipeline tests.
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request captured by the stub server.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path including the query string.
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Look up a header value by (case-insensitive) name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Body as (lossy) UTF-8 text.
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// A canned response returned by the stub handler.
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubResponse {
+    /// JSON response with the given status code.
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    /// Plain-text response with the given status code.
+    pub fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "text/plain".into())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    /// Add a response header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&RecordedRequest) -> StubResponse + Send + Sync>;
+
+/// Running stub server. Lives until the test's tokio runtime shuts down.
+pub struct StubServer {
+    /// Base URL, e.g. `http://127.0.0.1:40123` (no trailing slash).
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl StubServer {
+    /// Start a stub that answers every request with `handler`.
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> StubResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind stub server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Handler = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let _ = serve_one(stream, recorded, handler).await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Snapshot of all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve_one(
+    mut stream: TcpStream,
+    recorded: Arc<Mutex<Vec<RecordedRequest>>>,
+    handler: Handler,
+) -> std::io::Result<()> {
+    // Read until the end of the header block.
+    let mut buf = Vec::new();
+    let header_end = loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = find(&buf, b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let mut body = buf[header_end..].to_vec();
+    if let Some(len) = header("content-length").and_then(|v| v.parse::<usize>().ok()) {
+        while body.len() < len {
+            let mut chunk = [0u8; 8192];
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+    } else if header("transfer-encoding").is_some_and(|v| v.contains("chunked")) {
+        while find(&body, b"0\r\n\r\n").is_none() {
+            let mut chunk = [0u8; 8192];
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+        body = decode_chunked(&body);
+    }
+
+    let request = RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let response = handler(&request);
+    recorded.lock().unwrap().push(request);
+
+    let mut out = format!("HTTP/1.1 {} Stub\r\n", response.status);
+    for (k, v) in &response.headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(out.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await
+}
+
+type WsHandler = Arc<dyn Fn(&serde_json::Value) -> Option<Vec<serde_json::Value>> + Send + Sync>;
+
+/// Running WebSocket stub speaking JSON text frames.
+pub struct WsStub {
+    /// Base URL, e.g. `ws://127.0.0.1:40123`.
+    pub url: String,
+    messages: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl WsStub {
+    /// Start a stub that answers each client JSON message with the frames
+    /// `handler` returns. Returning `None` closes the connection; so does a
+    /// `null` frame, after the frames before it were sent.
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> Option<Vec<serde_json::Value>> + Send + Sync + 'static,
+    {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind ws stub");
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let handler: WsHandler = Arc::new(handler);
+
+        let recorded = messages.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                    continue;
+                };
+                while let Some(Ok(msg)) = ws.next().await {
+                    let Ok(text) = msg.to_text() else { continue };
+                    let Ok(value) = serde_json::from_str::<serde_json::Value>(text) else {
+                        continue;
+                    };
+                    let replies = handler(&value);
+                    recorded.lock().unwrap().push(value);
+                    let Some(replies) = replies else { break };
+                    for reply in replies {
+                        if reply.is_null() {
+                            let _ = ws.close(None).await;
+                            break;
+                        }
+                        if ws
+                            .send(Message::Text(reply.to_string().into()))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { url, messages }
+    }
+
+    /// Snapshot of all client messages received so far.
+    pub fn messages(&self) -> Vec<serde_json::Value> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+/// Canned outcome for every `generateContent` call on a `MockGemini`.
+#[derive(Debug, Clone)]
+pub enum GeminiReply {
+    /// A normal answer with this text.
+    Text(String),
+    /// HTTP 429 RESOURCE_EXHAUSTED.
+    RateLimited,
+    /// HTTP 403 PERMISSION_DENIED.
+    Forbidden,
+    /// HTTP 200 with `finishReason: SAFETY` and no text.
+    Safety,
+    /// HTTP 200 with partial text and `finishReason: MAX_TOKENS`.
+    MaxTokens,
+    /// HTTP 200 with a body that is not JSON.
+    Malformed,
+}
+
+/// Usage metadata sent with every successful `MockGemini` reply.
+pub const MOCK_USAGE: (u64, u64, u64) = (120, 8, 4);
+
+/// Stub of the Gemini REST API answering both `:generateContent` and
+/// `:streamGenerateContent` (SSE) with canned replies.
+pub struct MockGemini {
+    pub server: StubServer,
+}
+
+impl MockGemini {
+    /// Answer every request with `reply`.
+    pub async fn start(reply: GeminiReply) -> Self {
+        Self::sequence(vec![reply]).await
+    }
+
+    /// Answer requests with `replies` in order, repeating the last one —
+    /// e.g. a transcription followed by a rewrite.
+    pub async fn sequence(replies: Vec<GeminiReply>) -> Self {
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let server = StubServer::start(move |req| {
+            if req.method != "POST" || !req.path.contains(':') {
+                return StubResponse::text(404, "not found");
+            }
+            let index = next.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let body = gemini_body(&replies[index.min(replies.len() - 1)]);
+            if req.path.contains(":streamGenerateContent") && body.status == 200 {
+                let events = String::from_utf8_lossy(&body.body).into_owned();
+                StubResponse::text(200, &format!("data: {events}\n\n"))
+                    .with_header("Content-Type", "text/event-stream")
+            } else {
+                body
+            }
+        })
+        .await;
+        Self { server }
+    }
+
+    /// A config pointing the Gemini backend at this mock: one model, no
+    /// retries, no chunking, no beeps or notifications.
+    pub fn config(&self) -> crate::config::Config {
+        crate::config::Config {
+            api_key: "AIzaSyMock".into(),
+            model: "models/gemini-2.5-flash".into(),
+            api_base: self.server.url.clone(),
+            max_retries: 0,
+            sound_enabled: false,
+            notifications: false,
+            chunking: crate::config::ChunkingConfig {
+                enabled: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Snapshot of all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.server.requests()
+    }
+}
+
+fn gemini_body(reply: &GeminiReply) -> StubResponse {
+    use serde_json::json;
+
+    let (prompt, candidates, thoughts) = MOCK_USAGE;
+    let usage = json!({
+        "promptTokenCount": prompt,
+        "candidatesTokenCount": candidates,
+        "thoughtsTokenCount": thoughts,
+        "totalTokenCount": prompt + candidates + thoughts
+    });
+    let candidate = |text: &str, finish: &str| {
+        json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": text }], "role": "model" },
+                "finishReason": finish
+            }],
+            "usageMetadata": usage
+        })
+    };
+    match reply {
+        GeminiReply::Text(text) => StubResponse::json(200, candidate(text, "STOP")),
+        GeminiReply::RateLimited => StubResponse::json(
+            429,
+            json!({"error": {"code": 429, "message": "Resource has been exhausted", "status": "RESOURCE_EXHAUSTED"}}),
+        ),
+        GeminiReply::Forbidden => StubResponse::json(
+            403,
+            json!({"error": {"code": 403, "message": "Permission denied", "status": "PERMISSION_DENIED"}}),
+        ),
+        GeminiReply::Safety => StubResponse::json(
+            200,
+            json!({"candidates": [{"finishReason": "SAFETY"}], "usageMetadata": usage}),
+        ),
+        GeminiReply::MaxTokens => StubResponse::json(200, candidate("Testo tronc", "MAX_TOKENS")),
+        GeminiReply::Malformed => StubResponse::text(200, "{\"candidates\": [ oops"),
+    }
+}
+
+/// Injector that records what would have been typed.
+#[derive(Default)]
+pub struct MockInjector {
+    typed: Mutex<String>,
+    /// Text the focused app has selected.
+    selection: String,
+}
+
+impl MockInjector {
+    /// An injector whose focused app has `text` selected.
+    pub fn with_selection(text: &str) -> Self {
+        Self {
+            selection: text.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Everything typed so far, fragments concatenated.
+    pub fn typed(&self) -> String {
+        self.typed.lock().unwrap().clone()
+    }
+}
+
+impl crate::injector::Injector for MockInjector {
+    fn inject(&self, text: &str) -> anyhow::Result<()> {
+        self.typed.lock().unwrap().push_str(text);
+        Ok(())
+    }
+
+    fn inject_delta(&self, delta: &str) -> anyhow::Result<()> {
+        self.inject(delta)
+    }
+
+    /// Recorded as `{label}` in the typed text.
+    fn press(&self, shortcut: &crate::injector::Shortcut) -> anyhow::Result<()> {
+        self.inject(&format!("{{{}}}", shortcut.label))
+    }
+
+    fn selected_text(&self) -> anyhow::Result<String> {
+        Ok(self.selection.clone())
+    }
+
+    /// Recorded as `[text]` in the typed text.
+    fn replace_selection(&self, text: &str) -> anyhow::Result<()> {
+        self.inject(&format!("[{text}]"))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn decode_chunked(mut raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(line_end) = find(raw, b"\r\n") {
+        let size_str = String::from_utf8_lossy(&raw[..line_end]);
+        let size = usize::from_str_radix(size_str.trim(), 16).unwrap_or(0);
+        if size == 0 {
+            break;
+        }
+        let start = line_end + 2;
+        let end = (start + size).min(raw.len());
+        out.extend_from_slice(&raw[start..end]);
+        raw = &raw[(end + 2).min(raw.len())..];
+    }
+    out
+}
@@ -6,6 +6,7 @@
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
@@ -21,8 +22,7 @@ use std::path::PathBuf;
 pub struct ModelPricing {
     /// USD per 1M input tokens (audio).
     pub input_audio_per_m: f64,
-    /// USD per 1M input tokens (text — for the prompt).
-    #[allow(dead_code)]
+    /// USD per 1M input tokens (text — the prompt, and rewrite-mode input).
     pub input_text_per_m: f64,
     /// USD per 1M output tokens (including thinking tokens).
     pub output_per_m: f64,
@@ -31,6 +31,15 @@ pub struct ModelPricing {
 /// Look up pricing for a model identifier.
 /// Returns None for unknown models (cost will be logged as 0).
 pub fn model_pricing(model: &str) -> Option<ModelPricing> {
+    // On-device models (`local/<name>`) are free to run.
+    if model.starts_with("local/") {
+        return Some(ModelPricing {
+            input_audio_per_m: 0.0,
+            input_text_per_m: 0.0,
+            output_per_m: 0.0,
+        });
+    }
+
     // Strip the "models/" prefix if present.
     let name = model.strip_prefix("models/").unwrap_or(model);
 
@@ -144,6 +153,9 @@ pub struct TranscriptionRecord {
     pub input_tokens: u64,
     /// Number of output tokens (from usageMetadata).
     pub output_tokens: u64,
+    /// Thinking tokens spent before answering, billed as output.
+    #[serde(default)]
+    pub thinking_tokens: u64,
     /// Cost of input in USD.
     pub input_cost_usd: f64,
     /// Cost of output in USD.
@@ -154,6 +166,56 @@ pub struct TranscriptionRecord {
     pub word_count: u32,
     /// Character count of the transcribed text.
     pub char_count: u32,
+    /// Spoken language reported by the model (`structured_output` only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_language: Option<String>,
+    /// Per-phase timings of the dictation (absent in older records).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Latency>,
+    /// Set on the line item of a rewrite-mode call; the transcription it
+    /// rewrote is a record of its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite_mode: Option<String>,
+    /// Language the user spoke, on the line item of a translation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_language: Option<String>,
+    /// Language the dictation was translated into and typed in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_language: Option<String>,
+}
+
+/// Where the time of a dictation went, in milliseconds. Streamed text is
+/// typed while the response is still arriving, so phases may overlap.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Latency {
+    /// Key release → recorded audio handed over by the capture thread.
+    pub capture_stop_ms: u64,
+    /// Encoding the audio for upload (reported by the backend).
+    pub encode_ms: u64,
+    /// Request sent → response headers received: upload plus server queueing
+    /// (reported by the backend).
+    pub upload_ms: u64,
+    /// Key release → first text ready to type. Measured where the text is
+    /// typed, not at the network's first byte.
+    pub first_text_ms: u64,
+    /// Key release → complete transcription.
+    pub response_ms: u64,
+    /// Complete transcription → last keystroke injected.
+    pub injection_ms: u64,
+}
+
+impl Latency {
+    /// Phase labels and values, in pipeline order.
+    pub fn phases(&self) -> [(&'static str, u64); 6] {
+        [
+            ("Capture stop", self.capture_stop_ms),
+            ("Encode", self.encode_ms),
+            ("Upload", self.upload_ms),
+            ("First text", self.first_text_ms),
+            ("Full response", self.response_ms),
+            ("Injection", self.injection_ms),
+        ]
+    }
 }
 
 /// Token usage returned from the Gemini API response.
@@ -161,6 +223,8 @@ pub struct TranscriptionRecord {
 pub struct TokenUsage {
     pub prompt_tokens: u64,
     pub candidates_tokens: u64,
+    /// `thoughtsTokenCount` — not part of `candidates_tokens`.
+    pub thoughts_tokens: u64,
     #[allow(dead_code)]
     pub total_tokens: u64,
 }
@@ -173,7 +237,9 @@ pub fn calculate_cost(model: &str, usage: &TokenUsage) -> (f64, f64, f64) {
             // The text prompt is ~50-80 tokens. We attribute all input tokens
             // to the audio rate for simplicity (the text portion is negligible).
             let input_cost = usage.prompt_tokens as f64 * pricing.input_audio_per_m / 1_000_000.0;
-            let output_cost = usage.candidates_tokens as f64 * pricing.output_per_m / 1_000_000.0;
+            // Thinking tokens are billed at the output rate.
+            let output_tokens = usage.candidates_tokens + usage.thoughts_tokens;
+            let output_cost = output_tokens as f64 * pricing.output_per_m / 1_000_000.0;
             let total = input_cost + output_cost;
             (input_cost, output_cost, total)
         }
@@ -181,12 +247,26 @@ pub fn calculate_cost(model: &str, usage: &TokenUsage) -> (f64, f64, f64) {
     }
 }
 
+/// Cost of a text-only call (rewrite modes): input at the text rate.
+pub fn calculate_text_cost(model: &str, usage: &TokenUsage) -> (f64, f64, f64) {
+    match model_pricing(model) {
+        Some(pricing) => {
+            let input_cost = usage.prompt_tokens as f64 * pricing.input_text_per_m / 1_000_000.0;
+            let output_tokens = usage.candidates_tokens + usage.thoughts_tokens;
+            let output_cost = output_tokens as f64 * pricing.output_per_m / 1_000_000.0;
+            (input_cost, output_cost, input_cost + output_cost)
+        }
+        None => (0.0, 0.0, 0.0),
+    }
+}
+
 /// Build a TranscriptionRecord from the transcription result.
 pub fn build_record(
     model: &str,
     audio_duration_secs: f64,
     usage: &TokenUsage,
     transcription: &str,
+    detected_language: Option<&str>,
 ) -> TranscriptionRecord {
     let (input_cost, output_cost, total_cost) = calculate_cost(model, usage);
     let word_count = transcription.split_whitespace().count() as u32;
@@ -198,16 +278,50 @@ pub fn build_record(
         audio_duration_secs,
         input_tokens: usage.prompt_tokens,
         output_tokens: usage.candidates_tokens,
+        thinking_tokens: usage.thoughts_tokens,
         input_cost_usd: input_cost,
         output_cost_usd: output_cost,
         total_cost_usd: total_cost,
         word_count,
         char_count,
+        detected_language: detected_language.map(str::to_string),
+        latency: None,
+        rewrite_mode: None,
+        source_language: None,
+        target_language: None,
+    }
+}
+
+/// Build the line item for a rewrite-mode call on a transcription.
+pub fn build_rewrite_record(
+    model: &str,
+    mode: &str,
+    usage: &TokenUsage,
+    rewritten: &str,
+) -> TranscriptionRecord {
+    let (input_cost, output_cost, total_cost) = calculate_text_cost(model, usage);
+    TranscriptionRecord {
+        timestamp: chrono_now_utc(),
+        model: model.to_string(),
+        audio_duration_secs: 0.0,
+        input_tokens: usage.prompt_tokens,
+        output_tokens: usage.candidates_tokens,
+        thinking_tokens: usage.thoughts_tokens,
+        input_cost_usd: input_cost,
+        output_cost_usd: output_cost,
+        total_cost_usd: total_cost,
+        word_count: rewritten.split_whitespace().count() as u32,
+        char_count: rewritten.chars().count() as u32,
+        detected_language: None,
+        latency: None,
+        rewrite_mode: Some(mode.to_string()),
+        source_language: None,
+        target_language: None,
     }
 }
 
 /// Get current UTC timestamp as ISO 8601 string without external crate.
-fn chrono_now_utc() -> String {
+pub fn chrono_now_utc() -> String {
     // Use std::time to get Unix epoch seconds, then format manually.
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -324,6 +438,7 @@ pub struct Stats {
     pub count: u64,
     pub total_input_tokens: u64,
     pub total_output_tokens: u64,
+    pub total_thinking_tokens: u64,
     pub total_input_cost_usd: f64,
     pub total_output_cost_usd: f64,
     pub total_cost_usd: f64,
@@ -332,6 +447,10 @@ pub struct Stats {
     pub total_audio_secs: f64,
     /// Estimated time saved in seconds (voice vs keyboard).
     pub time_saved_secs: f64,
+    /// Rewrite-mode calls; their cost is part of the totals above, their
+    /// words and audio are not.
+    pub rewrite_count: u64,
+    pub rewrite_cost_usd: f64,
 }
 
 impl Stats {
@@ -339,12 +458,18 @@ impl Stats {
     pub fn from_records(records: &[TranscriptionRecord]) -> Self {
         let mut s = Stats::default();
         for r in records {
-            s.count += 1;
             s.total_input_tokens += r.input_tokens;
             s.total_output_tokens += r.output_tokens;
+            s.total_thinking_tokens += r.thinking_tokens;
             s.total_input_cost_usd += r.input_cost_usd;
             s.total_output_cost_usd += r.output_cost_usd;
             s.total_cost_usd += r.total_cost_usd;
+            if r.rewrite_mode.is_some() {
+                s.rewrite_count += 1;
+                s.rewrite_cost_usd += r.total_cost_usd;
+                continue;
+            }
+            s.count += 1;
             s.total_words += r.word_count as u64;
             s.total_chars += r.char_count as u64;
             s.total_audio_secs += r.audio_duration_secs;
@@ -480,7 +605,7 @@ pub fn print_stats(currency: &str) -> Result<()> {
 
 /// Print a single stats section (today / week / total).
 fn print_stats_section(stats: &Stats, currency: &str) {
-    if stats.count == 0 {
+    if stats.count == 0 && stats.rewrite_count == 0 {
         println!("     No transcriptions in this period.");
         println!();
         return;
@@ -500,6 +625,19 @@ fn print_stats_section(stats: &Stats, currency: &str) {
         "     Output cost:     {}",
         format_cost(stats.total_output_cost_usd, currency)
     );
+    if stats.total_thinking_tokens > 0 {
+        println!(
+            "     Thinking tokens: {} (billed as output)",
+            stats.total_thinking_tokens
+        );
+    }
+    if stats.rewrite_count > 0 {
+        println!(
+            "     Rewrites:        {} ({})",
+            stats.rewrite_count,
+            format_cost(stats.rewrite_cost_usd, currency)
+        );
+    }
     println!(
         "     \x1b[1mTotal cost:       {}\x1b[0m",
         format_cost(stats.total_cost_usd, currency)
@@ -512,8 +650,108 @@ fn print_stats_section(stats: &Stats, currency: &str) {
     println!();
 }
 
+/// p50/p95 of each latency phase for one model.
+#[derive(Debug)]
+pub struct ModelLatency {
+    pub model: String,
+    /// Records with latency data.
+    pub count: usize,
+    /// `(phase, p50_ms, p95_ms)` in pipeline order.
+    pub phases: Vec<(&'static str, u64, u64)>,
+}
+
+/// Latency percentiles per model, sorted by model name. Records written
+/// before latency was tracked are ignored.
+pub fn latency_by_model(records: &[TranscriptionRecord]) -> Vec<ModelLatency> {
+    let mut by_model: BTreeMap<&str, Vec<&Latency>> = BTreeMap::new();
+    for r in records {
+        if let Some(latency) = &r.latency {
+            by_model.entry(&r.model).or_default().push(latency);
+        }
+    }
+
+    by_model
+        .into_iter()
+        .map(|(model, latencies)| {
+            let phases = Latency::default()
+                .phases()
+                .iter()
+                .enumerate()
+                .map(|(i, (phase, _))| {
+                    let mut values: Vec<u64> = latencies.iter().map(|l| l.phases()[i].1).collect();
+                    values.sort_unstable();
+                    (*phase, percentile(&values, 50.0), percentile(&values, 95.0))
+                })
+                .collect();
+            ModelLatency {
+                model: model.to_string(),
+                count: latencies.len(),
+                phases,
+            }
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile of an ascending slice (0 if empty).
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Print `g-type stats --latency`: p50/p95 per phase for each model.
+pub fn print_latency() -> Result<()> {
+    let records = load_records()?;
+    let models = latency_by_model(&records);
+
+    println!();
+    if models.is_empty() {
+        println!("  No latency data yet. Dictations are timed from this version on.");
+        println!();
+        return Ok(());
+    }
+
+    println!("  \x1b[36m╔══════════════════════════════════════════════╗\x1b[0m");
+    println!("  \x1b[36m║          G-Type Latency (p50 / p95)          ║\x1b[0m");
+    println!("  \x1b[36m╚══════════════════════════════════════════════╝\x1b[0m");
+    println!();
+
+    for m in &models {
+        println!("  \x1b[1m⏱️  {} ({} dictations):\x1b[0m", m.model, m.count);
+        for (phase, p50, p95) in &m.phases {
+            println!(
+                "     {:<15} {:>6} ms / {:>6} ms",
+                format!("{phase}:"),
+                p50,
+                p95
+            );
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
 /// Format a single-line cost summary for the daemon log after each transcription.
 pub fn format_log_line(record: &TranscriptionRecord, currency: &str) -> String {
+    if let Some(mode) = &record.rewrite_mode {
+        let languages = match (&record.source_language, &record.target_language) {
+            (Some(source), Some(target)) => format!(" {source} → {target}"),
+            (None, Some(target)) => format!(" → {target}"),
+            _ => String::new(),
+        };
+        return format!(
+            "✍️ Rewrite '{}'{}: {} (in: {}, out: {}) | {} words",
+            mode,
+            languages,
+            format_cost(record.total_cost_usd, currency),
+            format_cost(record.input_cost_usd, currency),
+            format_cost(record.output_cost_usd, currency),
+            record.word_count,
+        );
+    }
     format!(
         "💰 Cost: {} (in: {}, out: {}) | {} words, {:.1}s audio | ⏱️ ~{} saved",
         format_cost(record.total_cost_usd, currency),
@@ -553,11 +791,25 @@ mod tests {
         assert!(model_pricing("models/gemini-99-turbo").is_none());
     }
 
+    #[test]
+    fn test_model_pricing_local_is_free() {
+        let usage = TokenUsage {
+            prompt_tokens: 5000,
+            candidates_tokens: 500,
+            thoughts_tokens: 0,
+            total_tokens: 5500,
+        };
+        assert!(model_pricing("local/ggml-base").is_some());
+        let r = build_record("local/ggml-base", 10.0, &usage, "offline text", None);
+        assert_eq!(r.total_cost_usd, 0.0);
+    }
+
     #[test]
     fn test_calculate_cost() {
         let usage = TokenUsage {
             prompt_tokens: 1_000_000,
             candidates_tokens: 1_000_000,
+            thoughts_tokens: 0,
             total_tokens: 2_000_000,
         };
         let (input, output, total) = calculate_cost("models/gemini-2.0-flash", &usage);
@@ -566,11 +818,26 @@ mod tests {
         assert!((total - 1.10).abs() < 0.001);
     }
 
+    #[test]
+    fn test_calculate_cost_includes_thinking() {
+        let usage = TokenUsage {
+            prompt_tokens: 0,
+            candidates_tokens: 500_000,
+            thoughts_tokens: 500_000,
+            total_tokens: 1_000_000,
+        };
+        let (_, output, _) = calculate_cost("models/gemini-2.0-flash", &usage);
+        assert!((output - 0.40).abs() < 0.001);
+        let r = build_record("models/gemini-2.0-flash", 1.0, &usage, "ok", None);
+        assert_eq!(r.thinking_tokens, 500_000);
+    }
+
     #[test]
     fn test_calculate_cost_unknown_model() {
         let usage = TokenUsage {
             prompt_tokens: 1000,
             candidates_tokens: 500,
+            thoughts_tokens: 0,
             total_tokens: 1500,
         };
         let (input, output, total) = calculate_cost("models/unknown", &usage);
@@ -619,9 +886,16 @@ mod tests {
         let usage = TokenUsage {
             prompt_tokens: 100,
             candidates_tokens: 50,
+            thoughts_tokens: 0,
             total_tokens: 150,
         };
-        let r = build_record("models/gemini-2.0-flash", 3.5, &usage, "ciao mondo test");
+        let r = build_record(
+            "models/gemini-2.0-flash",
+            3.5,
+            &usage,
+            "ciao mondo test",
+            None,
+        );
         assert_eq!(r.word_count, 3);
         assert_eq!(r.char_count, 15);
         assert!((r.audio_duration_secs - 3.5).abs() < 0.001);
@@ -637,11 +911,17 @@ mod tests {
                 audio_duration_secs: 5.0,
                 input_tokens: 160,
                 output_tokens: 30,
+                thinking_tokens: 0,
                 input_cost_usd: 0.000112,
                 output_cost_usd: 0.000012,
                 total_cost_usd: 0.000124,
                 word_count: 20,
                 char_count: 100,
+                detected_language: None,
+                latency: None,
+                rewrite_mode: None,
+                source_language: None,
+                target_language: None,
             },
             TranscriptionRecord {
                 timestamp: "2025-01-15T11:00:00Z".into(),
@@ -649,11 +929,17 @@ mod tests {
                 audio_duration_secs: 3.0,
                 input_tokens: 96,
                 output_tokens: 15,
+                thinking_tokens: 0,
                 input_cost_usd: 0.0000672,
                 output_cost_usd: 0.000006,
                 total_cost_usd: 0.0000732,
                 word_count: 10,
                 char_count: 50,
+                detected_language: None,
+                latency: None,
+                rewrite_mode: None,
+                source_language: None,
+                target_language: None,
             },
         ];
         let stats = Stats::from_records(&records);
@@ -664,6 +950,100 @@ mod tests {
         assert!(stats.time_saved_secs > 30.0);
     }
 
+    #[test]
+    fn test_rewrite_is_a_separate_line_item() {
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            candidates_tokens: 100,
+            thoughts_tokens: 0,
+            total_tokens: 1100,
+        };
+        let dictation = build_record(
+            "models/gemini-2.5-flash",
+            6.0,
+            &usage,
+            "one two three",
+            None,
+        );
+        let rewrite = build_rewrite_record(
+            "models/gemini-2.5-flash",
+            "email",
+            &usage,
+            "Hi,\n\none two three.",
+        );
+        // Text input is billed below the audio rate.
+        assert!(rewrite.input_cost_usd < dictation.input_cost_usd);
+        assert!(format_log_line(&rewrite, "USD").contains("Rewrite 'email':"));
+        let translation = TranscriptionRecord {
+            source_language: Some("it".into()),
+            target_language: Some("en".into()),
+            ..rewrite.clone()
+        };
+        assert!(format_log_line(&translation, "USD").contains("Rewrite 'email' it → en:"));
+
+        let stats = Stats::from_records(&[dictation.clone(), rewrite.clone()]);
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.total_words, 3);
+        assert_eq!(stats.rewrite_count, 1);
+        assert!((stats.rewrite_cost_usd - rewrite.total_cost_usd).abs() < 1e-12);
+        assert!(
+            (stats.total_cost_usd - dictation.total_cost_usd - rewrite.total_cost_usd).abs()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn test_latency_by_model() {
+        let record = |model: &str, response_ms: Option<u64>| TranscriptionRecord {
+            timestamp: "2025-01-15T10:00:00Z".into(),
+            model: model.into(),
+            audio_duration_secs: 1.0,
+            input_tokens: 10,
+            output_tokens: 5,
+            thinking_tokens: 0,
+            input_cost_usd: 0.0,
+            output_cost_usd: 0.0,
+            total_cost_usd: 0.0,
+            word_count: 5,
+            char_count: 20,
+            detected_language: None,
+            latency: response_ms.map(|response_ms| Latency {
+                response_ms,
+                ..Latency::default()
+            }),
+            rewrite_mode: None,
+            source_language: None,
+            target_language: None,
+        };
+        let mut records: Vec<_> = (1..=20)
+            .map(|i| record("models/gemini-2.5-flash", Some(i * 100)))
+            .collect();
+        records.push(record("models/gemini-2.0-flash", Some(700)));
+        records.push(record("models/gemini-2.0-flash", None));
+
+        let models = latency_by_model(&records);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].model, "models/gemini-2.0-flash");
+        assert_eq!(models[0].count, 1);
+
+        let flash = &models[1];
+        assert_eq!(flash.count, 20);
+        let response = flash
+            .phases
+            .iter()
+            .find(|(phase, _, _)| *phase == "Full response")
+            .unwrap();
+        assert_eq!((response.1, response.2), (1000, 1900));
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50.0), 0);
+        assert_eq!(percentile(&[7], 95.0), 7);
+        assert_eq!(percentile(&[1, 2, 3, 4], 50.0), 2);
+        assert_eq!(percentile(&[1, 2, 3, 4], 95.0), 4);
+    }
+
     #[test]
     fn test_format_duration() {
         assert_eq!(format_duration(30.0), "30s");
@@ -704,11 +1084,17 @@ mod tests {
                 audio_duration_secs: 1.0,
                 input_tokens: 10,
                 output_tokens: 5,
+                thinking_tokens: 0,
                 input_cost_usd: 0.0,
                 output_cost_usd: 0.0,
                 total_cost_usd: 0.0,
                 word_count: 5,
                 char_count: 20,
+                detected_language: None,
+                latency: None,
+                rewrite_mode: None,
+                source_language: None,
+                target_language: None,
             },
             TranscriptionRecord {
                 timestamp: "2025-01-16T10:00:00Z".into(),
@@ -716,11 +1102,17 @@ mod tests {
                 audio_duration_secs: 1.0,
                 input_tokens: 10,
                 output_tokens: 5,
+                thinking_tokens: 0,
                 input_cost_usd: 0.0,
                 output_cost_usd: 0.0,
                 total_cost_usd: 0.0,
                 word_count: 5,
                 char_count: 20,
+                detected_language: None,
+                latency: None,
+                rewrite_mode: None,
+                source_language: None,
+                target_language: None,
             },
         ];
         let filtered = filter_records_by_date(&records, "2025-01-15");
@@ -735,17 +1127,32 @@ mod tests {
             audio_duration_secs: 4.2,
             input_tokens: 134,
             output_tokens: 25,
+            thinking_tokens: 0,
             input_cost_usd: 0.0000938,
             output_cost_usd: 0.00001,
             total_cost_usd: 0.0001038,
             word_count: 12,
             char_count: 60,
+            detected_language: Some("it".into()),
+            latency: Some(Latency {
+                capture_stop_ms: 12,
+                encode_ms: 40,
+                upload_ms: 350,
+                first_text_ms: 610,
+                response_ms: 820,
+                injection_ms: 95,
+            }),
+            rewrite_mode: None,
+            source_language: None,
+            target_language: None,
         };
 
         let json = serde_json::to_string(&record).unwrap();
         let deserialized: TranscriptionRecord = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.model, record.model);
         assert_eq!(deserialized.word_count, record.word_count);
+        assert_eq!(deserialized.detected_language.as_deref(), Some("it"));
+        assert_eq!(deserialized.latency, record.latency);
         assert!((deserialized.total_cost_usd - record.total_cost_usd).abs() < 1e-10);
     }
 }
@@ -0,0 +1,246 @@
+// transcriber.rs — Pluggable speech-to-text backends.
+// The daemon only talks to the `Transcriber` trait; `from_config` picks the
+// concrete implementation from the `backend` key in config.toml, wrapped in
+// a `ChunkedTranscriber` so long recordings are split and sent in parallel.
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::chunking::ChunkedTranscriber;
+use crate::config::Config;
+use crate::error::TranscribeError;
+use crate::network::GeminiTranscriber;
+use crate::openai::OpenAiTranscriber;
+use crate::prompt::PromptContext;
+use crate::tracking::{Latency, TokenUsage};
+
+/// Backend identifiers accepted by the `backend` config key.
+pub const BACKENDS: &[&str] = &["gemini", "vertex", "openai", "local"];
+
+/// Per-request context passed to a backend alongside the audio.
+#[derive(Debug, Clone)]
+pub struct TranscribeContext {
+    /// Language code from config ("auto" for auto-detect).
+    pub language: String,
+    /// Instruction prompt, for backends that accept one.
+    pub prompt: String,
+}
+
+impl TranscribeContext {
+    /// Build the context for a dictation from the current config and where
+    /// the text is going.
+    pub fn from_config(config: &Config, context: &PromptContext) -> Self {
+        Self {
+            language: config.language.clone(),
+            // Live mode has no response schema, so it keeps the plain wording.
+            prompt: crate::prompt::transcription_prompt(
+                config,
+                config.structured_output && !config.live.enabled,
+                context,
+            ),
+        }
+    }
+}
+
+/// Text and usage metadata returned by a backend.
+#[derive(Debug, Clone, Default)]
+pub struct Transcription {
+    /// Transcribed text, trimmed.
+    pub text: String,
+    /// Token usage reported by the provider (zero when not applicable).
+    pub usage: TokenUsage,
+    /// Model that produced the text — recorded in usage history.
+    pub model: String,
+    /// Language the provider says was spoken, when it reports one.
+    pub detected_language: Option<String>,
+    /// Encode and upload time measured by the backend; the daemon fills in
+    /// the other phases.
+    pub latency: Latency,
+}
+
+/// Context on the error of a transcription that failed after part of its
+/// text was ready (and, when streaming, sent to `deltas`): that text and the
+/// usage reported so far, so the caller can account for what was typed.
+#[derive(Debug, Clone)]
+pub struct Partial(pub Transcription);
+
+impl fmt::Display for Partial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed after {} characters were streamed",
+            self.0.text.chars().count()
+        )
+    }
+}
+
+/// Attach `sent` to `err` as a `Partial`, unless no text was sent.
+pub fn with_partial(err: anyhow::Error, sent: Transcription) -> anyhow::Error {
+    if sent.text.is_empty() {
+        err
+    } else {
+        err.context(Partial(sent))
+    }
+}
+
+/// The text sent to `deltas` before `err`, if any (see `Partial`).
+pub fn partial(err: &anyhow::Error) -> Option<&Transcription> {
+    err.downcast_ref::<Partial>().map(|p| &p.0)
+}
+
+/// A speech-to-text provider.
+///
+/// `samples` are always PCM i16, 16kHz mono — the format produced by
+/// `audio::start_capture`. Implementations handle encoding themselves.
+#[async_trait]
+pub trait Transcriber: Send + Sync {
+    /// Short identifier used in logs (matches the `backend` config value).
+    fn name(&self) -> &'static str;
+
+    /// Transcribe a complete recording.
+    async fn transcribe(&self, samples: &[i16], ctx: &TranscribeContext) -> Result<Transcription>;
+
+    /// Transcribe, sending text to `deltas` as soon as it is available so the
+    /// caller can start typing early. The concatenated deltas equal the final
+    /// `Transcription::text`. Backends without streaming send it in one piece.
+    async fn transcribe_streaming(
+        &self,
+        samples: &[i16],
+        ctx: &TranscribeContext,
+        deltas: &UnboundedSender<String>,
+    ) -> Result<Transcription> {
+        let result = self.transcribe(samples, ctx).await?;
+        if !result.text.is_empty() {
+            let _ = deltas.send(result.text.clone());
+        }
+        Ok(result)
+    }
+
+    /// Open or refresh the pooled connection to the provider so the upload
+    /// at hotkey release skips DNS, TCP and TLS setup. Called in the
+    /// background when recording starts; failures only cost the head start.
+    async fn warm_up(&self) {}
+}
+
+/// Run a transcription under an overall deadline (see `network::Timeouts`).
+/// On expiry the future is dropped, which cancels in-flight requests and
+/// outstanding segments.
+pub async fn with_deadline(
+    deadline: Duration,
+    transcription: impl Future<Output = Result<Transcription>>,
+) -> Result<Transcription> {
+    match tokio::time::timeout(deadline, transcription).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(
+            anyhow::Error::new(TranscribeError::Timeout).context(format!(
+                "Transcription did not finish within {}s",
+                deadline.as_secs()
+            )),
+        ),
+    }
+}
+
+/// Instantiate the backend selected by `config.backend`.
+pub fn from_config(config: &Config) -> Result<Arc<dyn Transcriber>> {
+    let backend = backend_from_config(config)?;
+    // Whisper already uses every CPU for one recording; parallel segments
+    // would only compete for them.
+    if config.chunking.enabled && config.backend != "local" {
+        Ok(Arc::new(ChunkedTranscriber::new(backend, &config.chunking)))
+    } else {
+        Ok(backend)
+    }
+}
+
+fn backend_from_config(config: &Config) -> Result<Arc<dyn Transcriber>> {
+    match config.backend.as_str() {
+        "gemini" => Ok(Arc::new(GeminiTranscriber::new(config)?)),
+        "vertex" => Ok(Arc::new(GeminiTranscriber::vertex(config)?)),
+        "openai" => Ok(Arc::new(OpenAiTranscriber::new(config)?)),
+        #[cfg(feature = "local-whisper")]
+        "local" => Ok(Arc::new(crate::local::LocalTranscriber::new(config)?)),
+        #[cfg(not(feature = "local-whisper"))]
+        "local" => bail!(
+            "This build has no on-device transcription. \
+             Rebuild with `cargo install --path . --features local-whisper`"
+        ),
+        other => bail!(
+            "Unknown transcription backend '{}'. Available: {}",
+            other,
+            BACKENDS.join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config_gemini() {
+        let cfg = Config::default();
+        let t = from_config(&cfg).unwrap();
+        assert_eq!(t.name(), "gemini");
+    }
+
+    #[test]
+    fn test_from_config_openai() {
+        let cfg = Config {
+            backend: "openai".into(),
+            ..Config::default()
+        };
+        let t = from_config(&cfg).unwrap();
+        assert_eq!(t.name(), "openai");
+    }
+
+    #[cfg(not(feature = "local-whisper"))]
+    #[test]
+    fn test_from_config_local_without_feature() {
+        let cfg = Config {
+            backend: "local".into(),
+            ..Config::default()
+        };
+        let err = from_config(&cfg).err().unwrap();
+        assert!(err.to_string().contains("local-whisper"));
+    }
+
+    #[test]
+    fn test_from_config_unknown_backend() {
+        let cfg = Config {
+            backend: "carrier-pigeon".into(),
+            ..Config::default()
+        };
+        let err = from_config(&cfg).err().unwrap();
+        assert!(err.to_string().contains("carrier-pigeon"));
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_cancels() {
+        let err = with_deadline(Duration::from_millis(20), std::future::pending())
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::find(&err), Some(&TranscribeError::Timeout));
+
+        let ok = with_deadline(Duration::from_secs(1), async {
+            Ok(Transcription::default())
+        })
+        .await;
+        assert!(ok.is_ok());
+    }
+
+    #[test]
+    fn test_context_uses_language() {
+        let cfg = Config {
+            language: "it".into(),
+            ..Config::default()
+        };
+        let ctx = TranscribeContext::from_config(&cfg, &PromptContext::default());
+        assert_eq!(ctx.language, "it");
+        assert!(ctx.prompt.contains("(it)"));
+    }
+}
@@ -0,0 +1,397 @@
+// vertex.rs — Service-account authentication for Gemini on Vertex AI.
+// Signs an RS256 JWT with the key from a service-account JSON file, exchanges
+// it at the OAuth token endpoint for an access token, and caches the token
+// until shortly before it expires. Requests then use `Authorization: Bearer`.
+
+use anyhow::{bail, Context, Result};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::error::TranscribeError;
+use crate::network::Timeouts;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+/// Refresh this long before the token actually expires.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+/// Requested assertion lifetime (Google's maximum).
+const ASSERTION_TTL_SECS: u64 = 3600;
+
+/// The fields we need from a service-account key file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub project_id: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.into()
+}
+
+impl ServiceAccountKey {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read service-account key {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid service-account key {}", path.display()))
+    }
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default = "default_expires_in")]
+    expires_in: u64,
+}
+
+fn default_expires_in() -> u64 {
+    ASSERTION_TTL_SECS
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+/// Hands out OAuth access tokens for a service account, refreshing on demand.
+pub struct TokenProvider {
+    key: ServiceAccountKey,
+    signing_key: EncodingKey,
+    client: reqwest::Client,
+    cache: Mutex<Option<CachedToken>>,
+}
+
+impl TokenProvider {
+    /// Token requests carry no audio, so they get the base attempt timeout.
+    pub fn new(key: ServiceAccountKey, timeouts: &Timeouts) -> Result<Self> {
+        let signing_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .context("Service-account private_key is not a valid RSA PEM key")?;
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.attempt(0))
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            key,
+            signing_key,
+            client,
+            cache: Mutex::new(None),
+        })
+    }
+
+    /// A valid access token, from cache when possible. Concurrent callers
+    /// share a single refresh.
+    pub async fn access_token(&self) -> Result<String> {
+        let mut cache = self.cache.lock().await;
+        if let Some(cached) = cache.as_ref() {
+            if cached.expires_at > Instant::now() + EXPIRY_MARGIN {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let fresh = self.fetch_token().await?;
+        let token = fresh.token.clone();
+        *cache = Some(fresh);
+        Ok(token)
+    }
+
+    /// Sign a JWT assertion and exchange it at `token_uri`.
+    async fn fetch_token(&self) -> Result<CachedToken> {
+        let assertion = self.sign_assertion()?;
+        let response = self
+            .client
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+            .context("OAuth token request failed")?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| TranscribeError::request(e.is_timeout(), e))
+            .context("Failed to read OAuth token response")?;
+        if !status.is_success() {
+            // invalid_grant & co. are credential problems, whatever the status.
+            let status = if status.as_u16() == 400 {
+                401
+            } else {
+                status.as_u16()
+            };
+            return Err(TranscribeError::from_http(status, &body))
+                .context("Service-account token exchange rejected");
+        }
+
+        let parsed: TokenResponse =
+            serde_json::from_str(&body).context("Failed to parse OAuth token response")?;
+        debug!(
+            expires_in = parsed.expires_in,
+            "Obtained Vertex AI access token"
+        );
+        Ok(CachedToken {
+            token: parsed.access_token,
+            expires_at: Instant::now() + Duration::from_secs(parsed.expires_in),
+        })
+    }
+
+    fn sign_assertion(&self) -> Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("System clock before 1970")?
+            .as_secs();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: SCOPE,
+            aud: &self.key.token_uri,
+            iat: now,
+            exp: now + ASSERTION_TTL_SECS,
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.signing_key)
+            .context("Failed to sign service-account JWT")
+    }
+}
+
+/// Resolved Vertex AI settings: where to send requests and how to authenticate.
+pub struct VertexTarget {
+    region: String,
+    project: String,
+    pub tokens: TokenProvider,
+}
+
+impl VertexTarget {
+    /// Load the key file and resolve project and region.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let credentials = if config.vertex.credentials.is_empty() {
+            std::env::var("GOOGLE_APPLICATION_CREDENTIALS").unwrap_or_default()
+        } else {
+            config.vertex.credentials.clone()
+        };
+        if credentials.is_empty() {
+            bail!(
+                "backend = \"vertex\" requires [vertex] credentials \
+                 (or GOOGLE_APPLICATION_CREDENTIALS) pointing to a service-account JSON key"
+            );
+        }
+        let key = ServiceAccountKey::from_file(Path::new(&credentials))?;
+
+        let project = if config.vertex.project.is_empty() {
+            key.project_id.clone()
+        } else {
+            config.vertex.project.clone()
+        };
+        if project.is_empty() {
+            bail!("Set [vertex] project — the key file has no project_id");
+        }
+
+        Ok(Self {
+            region: config.vertex.region.clone(),
+            project,
+            tokens: TokenProvider::new(key, &Timeouts::from_config(config))?,
+        })
+    }
+
+    /// `generateContent` URL for `model`.
+    pub fn url(&self, model: &str) -> String {
+        format!(
+            "{}:generateContent",
+            endpoint_base(&self.region, &self.project, model)
+        )
+    }
+
+    /// `streamGenerateContent` (SSE) URL for `model`.
+    pub fn stream_url(&self, model: &str) -> String {
+        format!(
+            "{}:streamGenerateContent?alt=sse",
+            endpoint_base(&self.region, &self.project, model)
+        )
+    }
+}
+
+/// `https://{region}-aiplatform.googleapis.com/v1/projects/.../models/{model}`.
+/// The `global` region has no regional host prefix.
+fn endpoint_base(region: &str, project: &str, model: &str) -> String {
+    let model = model.strip_prefix("models/").unwrap_or(model);
+    let host = if region == "global" {
+        "aiplatform.googleapis.com".to_string()
+    } else {
+        format!("{region}-aiplatform.googleapis.com")
+    };
+    format!(
+        "https://{host}/v1/projects/{project}/locations/{region}/publishers/google/models/{model}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{StubResponse, StubServer};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    /// Throwaway RSA key for signing test assertions, generated once per run
+    /// so no private key is checked in.
+    fn test_key() -> &'static str {
+        use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+        static KEY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        KEY.get_or_init(|| {
+            rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048)
+                .unwrap()
+                .to_pkcs8_pem(LineEnding::LF)
+                .unwrap()
+                .to_string()
+        })
+    }
+
+    fn key(token_uri: &str) -> ServiceAccountKey {
+        ServiceAccountKey {
+            client_email: "dictation@acme-prod.iam.gserviceaccount.com".into(),
+            private_key: test_key().into(),
+            project_id: "acme-prod".into(),
+            token_uri: token_uri.into(),
+        }
+    }
+
+    fn timeouts() -> Timeouts {
+        Timeouts::from_config(&Config::default())
+    }
+
+    fn jwt_part(jwt: &str, index: usize) -> Value {
+        let part = jwt.split('.').nth(index).unwrap();
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_endpoint_base() {
+        assert_eq!(
+            endpoint_base("europe-west4", "acme", "models/gemini-2.0-flash"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/acme/locations/europe-west4/publishers/google/models/gemini-2.0-flash"
+        );
+        assert!(endpoint_base("global", "acme", "gemini-2.5-flash")
+            .starts_with("https://aiplatform.googleapis.com/v1/projects/acme/locations/global/"));
+    }
+
+    #[tokio::test]
+    async fn test_token_exchange_and_cache() {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({"access_token": "ya29.test", "expires_in": 3599, "token_type": "Bearer"}),
+            )
+        })
+        .await;
+        let uri = format!("{}/token", server.url);
+        let provider = TokenProvider::new(key(&uri), &timeouts()).unwrap();
+
+        assert_eq!(provider.access_token().await.unwrap(), "ya29.test");
+        assert_eq!(provider.access_token().await.unwrap(), "ya29.test");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1, "second call must hit the cache");
+        let form = requests[0].body_text();
+        assert!(form.contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer"));
+
+        let assertion = form.split("assertion=").nth(1).unwrap();
+        assert_eq!(jwt_part(assertion, 0)["alg"], "RS256");
+        let claims = jwt_part(assertion, 1);
+        assert_eq!(claims["iss"], "dictation@acme-prod.iam.gserviceaccount.com");
+        assert_eq!(claims["aud"], uri);
+        assert_eq!(claims["scope"], SCOPE);
+        assert_eq!(
+            claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap(),
+            3600
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_refreshed_near_expiry() {
+        let server = StubServer::start(|_| {
+            StubResponse::json(200, json!({"access_token": "short", "expires_in": 30}))
+        })
+        .await;
+        let provider =
+            TokenProvider::new(key(&format!("{}/token", server.url)), &timeouts()).unwrap();
+
+        provider.access_token().await.unwrap();
+        provider.access_token().await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_token_exchange_rejected() {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                400,
+                json!({"error": "invalid_grant", "error_description": "Invalid JWT Signature."}),
+            )
+        })
+        .await;
+        let provider =
+            TokenProvider::new(key(&format!("{}/token", server.url)), &timeouts()).unwrap();
+        let err = provider.access_token().await.unwrap_err();
+        assert!(matches!(
+            crate::error::find(&err),
+            Some(TranscribeError::Auth { .. })
+        ));
+    }
+
+    #[test]
+    fn test_target_from_config() {
+        let path = std::env::temp_dir().join(format!("g-type-sa-{}.json", std::process::id()));
+        let file = json!({
+            "type": "service_account",
+            "project_id": "acme-prod",
+            "client_email": "dictation@acme-prod.iam.gserviceaccount.com",
+            "private_key": test_key(),
+            "token_uri": "https://oauth2.googleapis.com/token"
+        });
+        std::fs::write(&path, file.to_string()).unwrap();
+
+        let mut config = Config {
+            backend: "vertex".into(),
+            ..Config::default()
+        };
+        config.vertex.credentials = path.display().to_string();
+        config.vertex.region = "europe-west4".into();
+        let target = VertexTarget::from_config(&config);
+        std::fs::remove_file(&path).unwrap();
+
+        let target = target.unwrap();
+        let url = target.url("models/gemini-2.0-flash");
+        assert!(url.contains("/projects/acme-prod/locations/europe-west4/"));
+        assert!(url.ends_with("/models/gemini-2.0-flash:generateContent"));
+        assert!(target
+            .stream_url("gemini-2.5-pro")
+            .ends_with("/models/gemini-2.5-pro:streamGenerateContent?alt=sse"));
+    }
+
+    #[test]
+    fn test_invalid_private_key() {
+        let mut bad = key("http://127.0.0.1:1/token");
+        bad.private_key = "not a key".into();
+        assert!(TokenProvider::new(bad, &timeouts()).is_err());
+    }
+}